@@ -0,0 +1,226 @@
+/*
+ *  tsp-sol - an experimental environment for traveling salesman solution analysis
+ *  Copyright (C) 2023  Jeffrey McAteer <jeffrey@jmcateer.com>
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; version 2 of the License ONLY.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License along
+ *  with this program; if not, write to the Free Software Foundation, Inc.,
+ *  51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+ */
+
+use super::*;
+
+use serde::Serialize;
+
+const PNG_SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+
+/// A png chunk's 4 byte type and its data.
+type PngChunk = ([u8; 4], Vec<u8>);
+
+/// Describes a frame-sequence directory well enough to rebuild the animation elsewhere.
+#[derive(Serialize, Debug)]
+pub struct FrameSequence {
+  pub fps: usize,
+  pub width: u32,
+  pub height: u32,
+  pub frames: Vec<String>,
+  pub ffmpeg: String,
+}
+
+/// Writes a lossless animated PNG looping over frame_files, which must all share one size.
+/// The png crate cannot write APNG chunks itself, so each frame is encoded as a normal RGBA png
+/// and its IDAT payload is re-wrapped as IDAT (first frame) or fdAT (later frames).
+pub fn write_apng(frame_files: &[String], output_file: &str, fps: usize) -> Result<(), String> {
+  if frame_files.is_empty() {
+    return Err(format!("No frames to write to {}", output_file));
+  }
+  if fps < 1 || fps > u16::MAX as usize {
+    return Err(format!("fps must be between 1 and {}, got {}", u16::MAX, fps));
+  }
+
+  let mut apng: Vec<u8> = PNG_SIGNATURE.to_vec();
+  let mut sequence_num: u32 = 0;
+  let mut dimensions: Option<(u32, u32)> = None;
+
+  for (frame_i, frame_file) in frame_files.iter().enumerate() {
+    let frame = image::open(frame_file).map_err(|e| format!("Cannot read frame {}: {}", frame_file, e))?.to_rgba();
+    let (width, height) = frame.dimensions();
+    match dimensions {
+      None => {
+        dimensions = Some((width, height));
+        let png_bytes = encode_rgba_png(frame.as_raw(), width, height)?;
+        // IHDR from the first frame describes the whole canvas, then the animation control chunk
+        for (chunk_type, chunk_data) in png_chunks(&png_bytes)? {
+          if &chunk_type == b"IHDR" {
+            write_chunk(&mut apng, b"IHDR", &chunk_data);
+          }
+        }
+        let mut actl = vec![];
+        actl.extend_from_slice(&(frame_files.len() as u32).to_be_bytes());
+        actl.extend_from_slice(&0u32.to_be_bytes()); // loop forever
+        write_chunk(&mut apng, b"acTL", &actl);
+        append_frame(&mut apng, &png_bytes, frame_i, &mut sequence_num, width, height, fps)?;
+      }
+      Some((first_width, first_height)) => {
+        if (width, height) != (first_width, first_height) {
+          return Err(format!("Frame {} is {}x{} but the first frame is {}x{}", frame_file, width, height, first_width, first_height));
+        }
+        let png_bytes = encode_rgba_png(frame.as_raw(), width, height)?;
+        append_frame(&mut apng, &png_bytes, frame_i, &mut sequence_num, width, height, fps)?;
+      }
+    }
+  }
+  write_chunk(&mut apng, b"IEND", &[]);
+
+  fs::write(output_file, apng).map_err(|e| format!("Error writing {}: {}", output_file, e))?;
+  Ok(())
+}
+
+/// Copies frame_files into frame_dir as 00000.png, 00001.png, ... and writes frame_dir/metadata.json
+/// with the fps, size and an ffmpeg command that turns the sequence into a video.
+pub fn write_frame_sequence(frame_files: &[String], frame_dir: &str, fps: usize) -> Result<FrameSequence, String> {
+  fs::create_dir_all(frame_dir).map_err(|e| format!("Cannot create {}: {}", frame_dir, e))?;
+
+  let mut frames = vec![];
+  let mut dimensions = (0, 0);
+  for (frame_i, frame_file) in frame_files.iter().enumerate() {
+    let frame_name = format!("{:05}.png", frame_i);
+    let frame_path = Path::new(frame_dir).join(&frame_name);
+    fs::copy(frame_file, &frame_path).map_err(|e| format!("Cannot copy {} to {}: {}", frame_file, frame_path.display(), e))?;
+    if frame_i == 0 {
+      dimensions = image::image_dimensions(frame_file).map_err(|e| format!("Cannot read frame {}: {}", frame_file, e))?;
+    }
+    frames.push(frame_name);
+  }
+
+  let sequence = FrameSequence {
+    fps,
+    width: dimensions.0,
+    height: dimensions.1,
+    frames,
+    // libx264rgb at crf 0 keeps the frames' RGB values exactly, yuv420p would blur region edges
+    ffmpeg: format!(
+      "ffmpeg -framerate {} -i {}/%05d.png -c:v libx264rgb -crf 0 -pix_fmt rgb24 {}.mkv",
+      fps, frame_dir, frame_dir.trim_end_matches('/').trim_end_matches("-frames")
+    ),
+  };
+
+  let metadata_file = Path::new(frame_dir).join("metadata.json");
+  let json_s = serde_json::to_string_pretty(&sequence).map_err(|e| format!("Error serializing {}: {}", metadata_file.display(), e))?;
+  fs::write(&metadata_file, json_s).map_err(|e| format!("Error writing {}: {}", metadata_file.display(), e))?;
+
+  Ok(sequence)
+}
+
+fn append_frame(apng: &mut Vec<u8>, png_bytes: &[u8], frame_i: usize, sequence_num: &mut u32, width: u32, height: u32, fps: usize) -> Result<(), String> {
+  let mut fctl = vec![];
+  fctl.extend_from_slice(&sequence_num.to_be_bytes());
+  fctl.extend_from_slice(&width.to_be_bytes());
+  fctl.extend_from_slice(&height.to_be_bytes());
+  fctl.extend_from_slice(&0u32.to_be_bytes()); // x offset
+  fctl.extend_from_slice(&0u32.to_be_bytes()); // y offset
+  fctl.extend_from_slice(&1u16.to_be_bytes()); // delay numerator
+  fctl.extend_from_slice(&(fps as u16).to_be_bytes()); // delay denominator
+  fctl.push(0); // dispose: none
+  fctl.push(0); // blend: source
+  write_chunk(apng, b"fcTL", &fctl);
+  *sequence_num += 1;
+
+  for (chunk_type, chunk_data) in png_chunks(png_bytes)? {
+    if &chunk_type != b"IDAT" {
+      continue;
+    }
+    if frame_i == 0 {
+      write_chunk(apng, b"IDAT", &chunk_data);
+    }
+    else {
+      let mut fdat = sequence_num.to_be_bytes().to_vec();
+      fdat.extend_from_slice(&chunk_data);
+      write_chunk(apng, b"fdAT", &fdat);
+      *sequence_num += 1;
+    }
+  }
+  Ok(())
+}
+
+fn encode_rgba_png(rgba: &[u8], width: u32, height: u32) -> Result<Vec<u8>, String> {
+  let mut png_bytes = vec![];
+  {
+    let mut encoder = png::Encoder::new(&mut png_bytes, width, height);
+    encoder.set_color(png::ColorType::RGBA);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| format!("Error encoding frame: {}", e))?;
+    writer.write_image_data(rgba).map_err(|e| format!("Error encoding frame: {}", e))?;
+  }
+  Ok(png_bytes)
+}
+
+/// Splits an encoded png into its chunks.
+fn png_chunks(png_bytes: &[u8]) -> Result<Vec<PngChunk>, String> {
+  if png_bytes.len() < PNG_SIGNATURE.len() || png_bytes[..PNG_SIGNATURE.len()] != PNG_SIGNATURE {
+    return Err("Encoded frame is not a png".to_string());
+  }
+  let mut chunks = vec![];
+  let mut i = PNG_SIGNATURE.len();
+  while i + 12 <= png_bytes.len() {
+    let length = u32::from_be_bytes([png_bytes[i], png_bytes[i + 1], png_bytes[i + 2], png_bytes[i + 3]]) as usize;
+    let chunk_type = [png_bytes[i + 4], png_bytes[i + 5], png_bytes[i + 6], png_bytes[i + 7]];
+    if i + 12 + length > png_bytes.len() {
+      return Err(format!("Truncated {} chunk in encoded frame", String::from_utf8_lossy(&chunk_type)));
+    }
+    chunks.push((chunk_type, png_bytes[i + 8..i + 8 + length].to_vec()));
+    i += 12 + length;
+  }
+  Ok(chunks)
+}
+
+fn write_chunk(out: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8]) {
+  out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+  out.extend_from_slice(chunk_type);
+  out.extend_from_slice(data);
+  let mut hasher = crc32fast::Hasher::new();
+  hasher.update(chunk_type);
+  hasher.update(data);
+  out.extend_from_slice(&hasher.finalize().to_be_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn apng_decodes_frame_by_frame() {
+    let dir = env::temp_dir().join(format!("tsp-sol-apng-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let colors = [[255u8, 0, 0], [0, 255, 0], [0, 0, 255]];
+    let mut frame_files = vec![];
+    for (i, color) in colors.iter().enumerate() {
+      let frame_file = dir.join(format!("frame-{}.png", i)).to_string_lossy().to_string();
+      RgbImage::from_pixel(5, 3, Rgb(*color)).save(&frame_file).unwrap();
+      frame_files.push(frame_file);
+    }
+    let apng_file = dir.join("anim.apng").to_string_lossy().to_string();
+    write_apng(&frame_files, &apng_file, 12).unwrap();
+
+    let (output_info, mut reader) = png::Decoder::new(File::open(&apng_file).unwrap()).read_info().unwrap();
+    assert_eq!((output_info.width, output_info.height), (5, 3));
+    assert_eq!(reader.info().animation_control().map(|actl| actl.num_frames), Some(3));
+    let mut buf = vec![0u8; reader.output_buffer_size()];
+    for color in colors.iter() {
+      reader.next_frame(&mut buf).unwrap();
+      let fctl = reader.info().frame_control().cloned().unwrap();
+      assert_eq!((fctl.width, fctl.height, fctl.delay_num, fctl.delay_den), (5, 3, 1, 12));
+      assert!(buf.chunks(4).all(|pixel| pixel == [color[0], color[1], color[2], 255]));
+    }
+    assert!(reader.next_frame(&mut buf).is_err());
+    fs::remove_dir_all(&dir).unwrap_or(());
+  }
+}
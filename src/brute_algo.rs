@@ -168,7 +168,7 @@ pub static MULTI_PICKLE_DB: Lazy<MySyncUnsafeCell< PickleDb >> = Lazy::new(|| {
 });
 
 pub fn solve(node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>, weights: &Vec<Vec<CityWeight>>, save_run_prefix: Option<String>, thread_pool: &ThreadPool) -> Vec<CityNum> {
-  if let Some(cached_solution_vec) = get_cached_solution(node_coordinates) {
+  if let Some(cached_solution_vec) = get_cached_solution(node_coordinates, weights) {
       // Store solution
       match &save_run_prefix {
         Some(prefix) => {
@@ -199,12 +199,7 @@ pub fn solve(node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>, weight
     return cached_solution_vec;
   }
 
-  let best_path = if weights.len() < 7 {
-    solve_st(node_coordinates, weights, 0, get_num_permutations(weights) ) // avoid thread overhead
-  }
-  else {
-    solve_mt(node_coordinates, weights, thread_pool)
-  };
+  let best_path = solve_uncached(node_coordinates, weights, thread_pool);
   
   // Store solution
   match &save_run_prefix {
@@ -233,14 +228,23 @@ pub fn solve(node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>, weight
      None => { }
   }
 
-  cache_solution(node_coordinates, &best_path);
+  cache_solution(node_coordinates, weights, &best_path);
 
   return best_path;
 }
 
+/// solve without reading or writing the solution cache, for weights the coordinates do not describe.
+pub fn solve_uncached(node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>, weights: &Vec<Vec<CityWeight>>, thread_pool: &ThreadPool) -> Vec<CityNum> {
+  if weights.len() < 7 {
+    solve_st(node_coordinates, weights, 0, get_num_permutations(weights) ) // avoid thread overhead
+  }
+  else {
+    solve_mt(node_coordinates, weights, thread_pool)
+  }
+}
 
 pub fn solve_all(node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>, weights: &Vec<Vec<CityWeight>>, save_run_prefix: Option<String>, thread_pool: &ThreadPool) -> Vec<Vec<CityNum>> {
-  if let Some(cached_solution_vecs) = get_cached_solutions(node_coordinates) {
+  if let Some(cached_solution_vecs) = get_cached_solutions(node_coordinates, weights) {
     // Store solution
     match &save_run_prefix {
       Some(prefix) => {
@@ -329,7 +333,7 @@ pub fn solve_all(node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>, we
     None => { }
   }
 
-  cache_solutions(node_coordinates, &best_paths);
+  cache_solutions(node_coordinates, weights, &best_paths);
 
   return best_paths;
 }
@@ -374,18 +378,29 @@ fn cached_solution_file(node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoor
   format!("target/_cached_solution_{}.txt", cached_solution_key(node_coordinates) )
 }
 
-fn get_cached_solution(node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>) -> Option<Vec<CityNum>> {
+// The cache is keyed by coordinates alone, so only cities whose weights are the coordinates' euclidean
+// distances are cached; ATSP matrices, other metrics and derived problems may share a layout.
+fn get_cached_solution(node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>, weights: &Vec<Vec<CityWeight>>) -> Option<Vec<CityNum>> {
+  if !weights_match_coordinates(node_coordinates, weights) {
+    return None;
+  }
   PICKLE_DB.get_mut().get::<Vec<CityNum>>( &cached_solution_key(node_coordinates) )
 }
 
-fn get_cached_solutions(node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>) -> Option<Vec<Vec<CityNum>>> {
+fn get_cached_solutions(node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>, weights: &Vec<Vec<CityWeight>>) -> Option<Vec<Vec<CityNum>>> {
+  if !weights_match_coordinates(node_coordinates, weights) {
+    return None;
+  }
   MULTI_PICKLE_DB.get_mut().get::<Vec<Vec<CityNum>>>( &cached_solution_key(node_coordinates) )
 }
 
-fn cache_solution(node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>, solution_best_path: &Vec<CityNum>) -> Option<()> {
+fn cache_solution(node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>, weights: &Vec<Vec<CityWeight>>, solution_best_path: &Vec<CityNum>) -> Option<()> {
   if ! unsafe { USE_BRUTE_CACHE.get().as_ref()?.load(Ordering::SeqCst) } {
     return Some(()); // Do not cache if cache disabled
   }
+  if !weights_match_coordinates(node_coordinates, weights) {
+    return Some(());
+  }
   //PICKLE_DB.lock().unwrap().set::<Vec<CityNum>>( &cached_solution_key(node_coordinates), solution_best_path ).ok()?;
   if let Err(e) = PICKLE_DB.get_mut().set::<Vec<CityNum>>( &cached_solution_key(node_coordinates), solution_best_path ) {
     eprintln!("Error setting: {:?}", e);
@@ -395,10 +410,13 @@ fn cache_solution(node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>, s
   Some(())
 }
 
-fn cache_solutions(node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>, solution_best_paths: &Vec<Vec<CityNum>>) -> Option<()> {
+fn cache_solutions(node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>, weights: &Vec<Vec<CityWeight>>, solution_best_paths: &Vec<Vec<CityNum>>) -> Option<()> {
   if ! unsafe { USE_BRUTE_CACHE.get().as_ref()?.load(Ordering::SeqCst) } {
     return Some(()); // Do not cache if cache disabled
   }
+  if !weights_match_coordinates(node_coordinates, weights) {
+    return Some(());
+  }
 
   if let Err(e) = MULTI_PICKLE_DB.get_mut().set::<Vec<Vec<CityNum>>>( &cached_solution_key(node_coordinates), solution_best_paths ) {
     eprintln!("Error setting: {:?}", e);
@@ -470,6 +488,32 @@ pub fn solve_st(node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>, wei
    return best_path;
 }
 
+/// Shortest tour starting at first_city among the ones is_allowed accepts, None if it rejects every tour.
+/// Only the (n-1)! orders of the other cities are walked since the start is fixed; nothing is cached.
+pub fn solve_st_filtered(weights: &Vec<Vec<CityWeight>>, first_city: CityNum, is_allowed: &dyn Fn(&Vec<CityNum>) -> bool) -> Option<Vec<CityNum>> {
+  let mut rest: Vec<CityNum> = (0..weights.len()).filter(|c| *c != first_city).collect(); // sorted, the first permutation
+  let mut best_path: Option<(Vec<CityNum>, CityWeight)> = None;
+  loop {
+    let mut current_path = Vec::with_capacity(weights.len());
+    current_path.push(first_city);
+    current_path.extend_from_slice(&rest);
+
+    let this_dist = compute_dist(weights, &current_path);
+    let is_better = match &best_path {
+      Some((_path, best_dist)) => this_dist < *best_dist,
+      None => true,
+    };
+    if is_better && is_allowed(&current_path) {
+      best_path = Some((current_path, this_dist));
+    }
+
+    if !rest.next_permutation() {
+      break;
+    }
+  }
+  best_path.map(|(path, _dist)| path)
+}
+
 pub fn solve_st_all(node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>, weights: &Vec<Vec<CityWeight>>, begin_permutation_num: CityNum, max_permutation_num: CityNum) -> Vec<Vec<CityNum>> {
   let mut current_path = vec![];
    if let Some(cached_path) = PERMUTATIONS_CACHE.lock().unwrap().get( &get_permutation_cache_key(begin_permutation_num, weights.len()) ) {
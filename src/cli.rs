@@ -0,0 +1,507 @@
+/*
+ *  tsp-sol - an experimental environment for traveling salesman solution analysis
+ *  Copyright (C) 2023  Jeffrey McAteer <jeffrey@jmcateer.com>
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; version 2 of the License ONLY.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License along
+ *  with this program; if not, write to the Free Software Foundation, Inc.,
+ *  51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+ */
+
+// Command line: one subcommand per experiment, typed options with their defaults in --help, and
+// TSP_* environment variables as fallbacks for the options that have one.
+
+use super::*;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use clap::builder::{PossibleValuesParser, TypedValueParser};
+
+#[derive(Parser, Debug)]
+#[command(name = "tsp-sol", version, about = "An experimental environment for traveling salesman solution analysis",
+  arg_required_else_help = true,
+  after_help = "`tsp-sol FILE ...` is short for `tsp-sol solve FILE ...`.\n\
+Environment: PREF_GPU (none, print or a device name), PREF_BACKEND, USE_BRUTE_CACHE=f, SHRINK_FAILURES=f.")]
+pub struct Cli {
+  #[command(subcommand)]
+  pub command: Command,
+  /// Directory images, traces and scan data are written to, created when missing
+  #[arg(long, global = true, env = "TSP_OUT_DIR", default_value = "views")]
+  pub out_dir: String,
+  /// Write into a new OUT_DIR/YYYYmmdd-HHMMSS-COMMAND/ directory per run, so parallel runs do not overwrite each other
+  #[arg(long, global = true, env = "TSP_TIMESTAMPED")]
+  pub timestamped: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+  Solve(SolveArgs),
+  Delta(DeltaArgs),
+  Selective(SelectiveArgs),
+  Shrink(ShrinkArgs),
+  Generate(GenerateArgs),
+  Stepcheck(StepcheckArgs),
+  Spray(SprayArgs),
+  PatternScan(PatternScanArgs),
+  MultiPatternScan(MultiPatternScanArgs),
+  SprayPatternSearch(SprayPatternSearchArgs),
+  Serve(ServeArgs),
+}
+
+impl Cli {
+  /// Parses args, reading them as `solve` arguments when the first one is not a subcommand or option.
+  pub fn parse_args(args: Vec<String>) -> Result<Cli, clap::Error> {
+    let mut args = args;
+    if let Some(first) = args.get(1) {
+      let is_subcommand = Cli::command_names().iter().any(|name| name == first);
+      if !is_subcommand && !first.starts_with('-') {
+        args.insert(1, "solve".to_string());
+      }
+    }
+    Cli::try_parse_from(args)
+  }
+
+  fn command_names() -> Vec<String> {
+    let mut names: Vec<String> = <Cli as clap::CommandFactory>::command().get_subcommands().map(|c| c.get_name().to_string()).collect();
+    names.push("help".to_string());
+    names
+  }
+}
+
+impl Command {
+  /// The subcommand as typed, e.g. "pattern-scan".
+  pub fn name(&self) -> &'static str {
+    match self {
+      Command::Solve(_) => "solve",
+      Command::Delta(_) => "delta",
+      Command::Selective(_) => "selective",
+      Command::Shrink(_) => "shrink",
+      Command::Generate(_) => "generate",
+      Command::Stepcheck(_) => "stepcheck",
+      Command::Spray(_) => "spray",
+      Command::PatternScan(_) => "pattern-scan",
+      Command::MultiPatternScan(_) => "multi-pattern-scan",
+      Command::SprayPatternSearch(_) => "spray-pattern-search",
+      Command::Serve(_) => "serve",
+    }
+  }
+}
+
+/// JeffAlgo's knobs, shared by every command that runs it. Unset options keep a scenario file's own choice.
+#[derive(Args, Debug, Default)]
+pub struct JalgoArgs {
+  /// Which city JeffAlgo inserts next: first, last, middle, nearest, farthest, cheapest, random[:SEED], convex-hull
+  /// or spatial-curve [default: first, the lowest numbered city not yet in the tour]
+  #[arg(long, env = "TSP_INSERTION_ORDER", value_parser = parse_insertion_order)]
+  pub insertion_order: Option<InsertionOrder>,
+  /// Sub-tour JeffAlgo starts inserting into; convex-hull starts from every hull city in hull order [default: largest-triangle]
+  #[arg(long, env = "TSP_INITIAL_TOUR", value_parser = initial_tour_parser())]
+  pub initial_tour: Option<InitialTour>,
+  /// Tour cities removed and re-inserted around every insertion; each level multiplies runtime by the city count,
+  /// 0 is plain cheapest insertion [default: 3]
+  #[arg(long, env = "TSP_DEEP")]
+  pub deep: Option<usize>,
+}
+
+impl JalgoArgs {
+  pub fn flags(&self) -> JalgoFlags {
+    JalgoFlags {
+      insertion_order: self.insertion_order,
+      initial_tour: self.initial_tour,
+      deep: self.deep,
+    }
+  }
+}
+
+/// Where the research commands get their cities from.
+#[derive(Args, Debug, Default)]
+pub struct ScenarioArgs {
+  /// Scenario file (.json or .toml) used in place of the coordinates and positional arguments
+  #[arg(long, env = "TSP_SCENARIO")]
+  pub scenario: Option<String>,
+  /// Cities as "x,y x,y ...", random cities when unset
+  #[arg(long, env = "TSP_INITIAL_COORDS")]
+  pub initial_coords: Option<String>,
+}
+
+/// Solve a .tsp, .atsp, .vrp, .csv or .geojson file and print the tour.
+///
+/// Files with more than 5000 cities, or --fast, are solved with a coordinate-only cheapest insertion that looks up
+/// nearby tour edges in a grid instead of building a weights matrix; it honors --insertion-order and always starts
+/// from the hull. CVRPLIB .vrp files (DEMAND_SECTION, DEPOT_SECTION, CAPACITY) are split into capacity-respecting
+/// routes from the depot. Asymmetric .atsp files (EXPLICIT FULL_MATRIX) keep tour direction, a reversed tour counts
+/// as a different tour. .csv files hold "id,x,y[,z]" rows, optionally under a header naming id, x and y (or lon and
+/// lat, measured with haversine); .geojson files give every Point feature as a city, measured with haversine.
+#[derive(Args, Debug)]
+pub struct SolveArgs {
+  /// Problem file
+  pub file: String,
+  /// Older spelling of the solver flags, applied in order
+  #[arg(value_enum)]
+  pub words: Vec<SolveWord>,
+  /// Also solve with BruteAlgo
+  #[arg(long)]
+  pub brute: bool,
+  /// Skip JeffAlgo
+  #[arg(long)]
+  pub no_jalgo: bool,
+  /// Write the tour to OUT_DIR/tsp_problem/ with a step trace (jalgo-trace.json, jalgo-trace/step-NNN.png, jalgo-trace.apng)
+  #[arg(long)]
+  pub view: bool,
+  /// Use the coordinate-only solver regardless of size
+  #[arg(long)]
+  pub fast: bool,
+  /// Solve the shortest path through every city instead of a closed tour
+  #[arg(long)]
+  pub open: bool,
+  /// First city of the path, as numbered in the file (implies --open)
+  #[arg(long, value_name = "ID")]
+  pub start: Option<usize>,
+  /// Last city of the path, as numbered in the file (implies --open)
+  #[arg(long, value_name = "ID")]
+  pub end: Option<usize>,
+  /// Fleet size: K salesmen on a .tsp file, or a fixed number of CVRP vehicles
+  #[arg(long, value_name = "K", value_parser = clap::value_parser!(u64).range(1..))]
+  pub vehicles: Option<u64>,
+  /// Depot city as numbered in the file [default: the first city]
+  #[arg(long, value_name = "ID")]
+  pub depot: Option<usize>,
+  /// "CITY EARLIEST LATEST [SERVICE]" lines; tours leave --depot at time 0 and early arrivals wait.
+  /// BruteAlgo handles up to 11 cities
+  #[arg(long, value_name = "FILE")]
+  pub time_windows: Option<String>,
+  /// How distances are measured [default: the file's EDGE_WEIGHT_TYPE]; haversine reads x as longitude and y as
+  /// latitude in degrees, geo is TSPLIB's DDD.MM convention
+  #[arg(long, env = "TSP_METRIC", value_parser = metric_parser())]
+  pub metric: Option<Metric>,
+  /// Longest tour from --depot; only the cities it reaches are visited, maximizing the prize collected.
+  /// BruteAlgo handles up to 10 cities
+  #[arg(long, value_name = "B", value_parser = parse_positive)]
+  pub budget: Option<fp>,
+  /// "CITY PRIZE" lines for --budget; unlisted cities are worth 0 [default: every city is worth 1]
+  #[arg(long, value_name = "FILE", requires = "budget")]
+  pub prizes: Option<String>,
+  /// json prints one solve report (instance, N, distance function, each solver's tour, length and runtime,
+  /// whether the tours agree) instead of text; errors go to stderr with exit status 1
+  #[arg(long, env = "TSP_FORMAT", value_enum, default_value_t = OutputFormat::Text)]
+  pub format: OutputFormat,
+  /// Write the shortest tour found as GeoJSON (.geojson/.json), a GPX route (.gpx) or KML (.kml); repeatable
+  #[arg(long, value_name = "FILE", value_parser = parse_export_file)]
+  pub export: Vec<String>,
+  #[command(flatten)]
+  pub jalgo: JalgoArgs,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum SolveWord {
+  Jalgo,
+  Brute,
+  NoJalgo,
+  NoBrute,
+  View,
+  NoView,
+  Fast,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+  Text,
+  Json,
+}
+
+impl SolveArgs {
+  /// (use_jalgo, use_brute, write_solution_out_to_views, use_fast) after the flags and the older words.
+  pub fn solvers(&self) -> (bool, bool, bool, bool) {
+    let (mut use_jalgo, mut use_brute, mut view, mut fast) = (!self.no_jalgo, self.brute, self.view, self.fast);
+    for word in self.words.iter() {
+      match word {
+        SolveWord::Jalgo => use_jalgo = true,
+        SolveWord::Brute => use_brute = true,
+        SolveWord::NoJalgo => use_jalgo = false,
+        SolveWord::NoBrute => use_brute = false,
+        SolveWord::View => view = true,
+        SolveWord::NoView => view = false,
+        SolveWord::Fast => fast = true,
+      }
+    }
+    (use_jalgo, use_brute, view, fast)
+  }
+}
+
+/// Compare JeffAlgo with BruteAlgo on random cities of 4 to 7 points.
+///
+/// Incorrect JeffAlgo runs are dumped to OUT_DIR/ and shrunk to the smallest counterexample (set SHRINK_FAILURES=f to
+/// skip that). Only --insertion-order is tested unless --all-insertion-orders is given.
+#[derive(Args, Debug)]
+pub struct DeltaArgs {
+  /// Number of random cities to test
+  #[arg(long, default_value_t = 1000, value_parser = clap::value_parser!(u64).range(1..))]
+  pub runs: u64,
+  #[arg(long, env = "TSP_FAMILY", default_value = "uniform", value_parser = family_parser())]
+  pub family: Family,
+  /// Also run every other insertion order on each city and print their mismatch rates side by side
+  #[arg(long)]
+  pub all_insertion_orders: bool,
+  #[command(flatten)]
+  pub jalgo: JalgoArgs,
+}
+
+/// Test increasingly large random cities and stop when JeffAlgo does not match BruteAlgo.
+///
+/// Failures are shrunk like delta's.
+#[derive(Args, Debug)]
+pub struct SelectiveArgs {
+  /// Largest city size to test
+  #[arg(default_value_t = 11, value_parser = clap::value_parser!(u64).range(4..))]
+  pub max_cities: u64,
+  /// How many sizes below max-cities to test
+  #[arg(default_value_t = 2, value_parser = clap::value_parser!(u64).range(1..))]
+  pub num_to_test_before: u64,
+  /// Structured family the cities are drawn from
+  #[arg(long, env = "TSP_FAMILY", default_value = "uniform", value_parser = family_parser())]
+  pub family: Family,
+  #[command(flatten)]
+  pub jalgo: JalgoArgs,
+}
+
+/// Shrink N cities where JeffAlgo disagrees with BruteAlgo down to the smallest counterexample, in OUT_DIR/shrunk/.
+#[derive(Args, Debug)]
+pub struct ShrinkArgs {
+  /// Number of cities
+  #[arg(default_value_t = 5)]
+  pub n: usize,
+  #[command(flatten)]
+  pub cities: ScenarioArgs,
+  #[command(flatten)]
+  pub jalgo: JalgoArgs,
+}
+
+/// Write N cities of a family as a TSPLIB file.
+///
+/// DIMACS families use the challenge's 1,000,000 x 1,000,000 grid, the others the 0-15 box.
+#[derive(Args, Debug)]
+pub struct GenerateArgs {
+  #[arg(value_parser = family_parser())]
+  pub family: Family,
+  /// Number of cities
+  #[arg(default_value_t = 100)]
+  pub n: usize,
+  /// Output file [default: OUT_DIR/generated/FAMILY-N.tsp]
+  pub file: Option<String>,
+}
+
+/// Compare JeffAlgo's tour after every insertion with BruteAlgo on the cities inserted so far.
+///
+/// Prints the first step where the tour stops being optimal and its gap, saving both tours and the step trace to
+/// OUT_DIR/stepcheck/. With runs > 1 further random cities of size N are checked until one breaks.
+#[derive(Args, Debug)]
+pub struct StepcheckArgs {
+  /// Number of cities
+  #[arg(default_value_t = 8)]
+  pub n: usize,
+  /// Number of cities to check
+  #[arg(default_value_t = 1)]
+  pub runs: usize,
+  #[command(flatten)]
+  pub cities: ScenarioArgs,
+  #[command(flatten)]
+  pub jalgo: JalgoArgs,
+}
+
+/// Insert every point of a grid into N-1 cities and plot where JeffAlgo and BruteAlgo disagree.
+///
+/// Matching tours plot a green pixel on OUT_DIR/spray.png, mismatches a red one, showing where JeffAlgo fails to
+/// uphold the hamiltonian cycle invariant from city size N to N+1.
+#[derive(Args, Debug)]
+pub struct SprayArgs {
+  /// Number of cities before a sprayed point is added
+  #[arg(default_value_t = 5)]
+  pub n: usize,
+  /// Distance between sprayed points
+  #[arg(default_value_t = 0.25, value_parser = parse_positive)]
+  pub granularity: fp,
+  /// Insert each sprayed point into JeffAlgo's N-1 tour with a single cheapest insertion instead of re-solving
+  #[arg(long)]
+  pub one_step: bool,
+  #[command(flatten)]
+  pub cities: ScenarioArgs,
+  #[command(flatten)]
+  pub jalgo: JalgoArgs,
+}
+
+/// Add one more city at every point of a grid and color regions sharing the same optimal tour.
+#[derive(Args, Debug)]
+pub struct PatternScanArgs {
+  /// Number of cities before the scanned point is added
+  #[arg(default_value_t = 5)]
+  pub n: usize,
+  /// Distance between scanned points
+  #[arg(default_value_t = 0.25, value_parser = parse_positive)]
+  pub granularity: fp,
+  #[command(flatten)]
+  pub cities: ScenarioArgs,
+  #[command(flatten)]
+  pub jalgo: JalgoArgs,
+}
+
+/// Run pattern-scan in steps while the cities move from --initial-coords to --ending-coords.
+///
+/// A steps value of 3 scans the initial cities, the midpoint of both arrangements and the ending cities.
+/// Writes OUT_DIR/multi-pattern-scan.gif, a lossless OUT_DIR/multi-pattern-scan.apng and a
+/// OUT_DIR/multi-pattern-scan-frames/ directory with numbered frames, metadata.json and an ffmpeg command.
+///
+/// A --scenario file lists named cities, the scan bounds, granularity, steps, the keyframes the cities move
+/// through and the solver (brute or jalgo) scans color by:
+///   { "cities": [ {"name": "a", "x": 4.0, "y": 9.0}, ... ],
+///     "bounds": {"x_min": 0.0, "x_max": 15.0, "y_min": 0.0, "y_max": 15.0},
+///     "granularity": 0.05, "steps": 10, "solver": "brute", "fps": 5,
+///     "keyframes": [ {"cities": [...]}, {"cities": [...]} ] }
+/// A keyframe may replace "cities" with a "transform" of the previous keyframe
+/// ({"scale": 1.5, "rotate_degrees": 90, "translate_x": 0, "translate_y": 0, "origin_x": 7.5, "origin_y": 7.5}),
+/// and controls how cities travel into it with "easing" (linear, ease-in, ease-out, ease-in-out, step)
+/// and per-city "paths":
+///   {"path": "orbit", "city": 2, "center_x": 7.5, "center_y": 7.5, "turns": 1}
+///   {"path": "curve", "city": 0, "control_x": 3.0, "control_y": 14.0}
+/// Every run writes the scenario it used next to its outputs (eg OUT_DIR/multi-pattern-scan.scenario.json).
+#[derive(Args, Debug)]
+#[command(verbatim_doc_comment)]
+pub struct MultiPatternScanArgs {
+  /// Number of cities before the scanned point is added
+  #[arg(default_value_t = 5)]
+  pub n: usize,
+  /// Distance between scanned points
+  #[arg(default_value_t = 0.25, value_parser = parse_positive)]
+  pub granularity: fp,
+  /// Number of pattern-scans between the first and last arrangement
+  #[arg(default_value_t = 10, value_parser = clap::value_parser!(u64).range(1..))]
+  pub steps: u64,
+  /// Cities of the last step as "x,y x,y ...", random cities when unset
+  #[arg(long, env = "TSP_ENDING_COORDS")]
+  pub ending_coords: Option<String>,
+  /// Animation frames per second, overriding the scenario's fps [default: 5]
+  #[arg(long, env = "TSP_FPS", value_parser = clap::value_parser!(u16).range(1..))]
+  pub fps: Option<u16>,
+  #[command(flatten)]
+  pub cities: ScenarioArgs,
+  #[command(flatten)]
+  pub jalgo: JalgoArgs,
+}
+
+/// Pattern-scan many random cities to test conjectures about edge weights and optimal tour patterns.
+#[derive(Args, Debug)]
+pub struct SprayPatternSearchArgs {
+  /// Number of cities before the scanned point is added
+  #[arg(default_value_t = 5)]
+  pub n: usize,
+  /// Distance between scanned points
+  #[arg(default_value_t = 0.25, value_parser = parse_positive)]
+  pub granularity: fp,
+  /// Number of random cities to scan
+  #[arg(default_value_t = 100)]
+  pub sprays: usize,
+  /// Scenario file (.json or .toml) scanned instead of random cities
+  #[arg(long, env = "TSP_SCENARIO")]
+  pub scenario: Option<String>,
+  #[command(flatten)]
+  pub jalgo: JalgoArgs,
+}
+
+/// Browse the scan data (*.json) the pattern-scan commands write next to their images.
+///
+/// Hover a region to see its tour, click to pin a point, drag cities around and press Re-solve to solve the edited
+/// city on demand, and step or play through multi-pattern-scan frames.
+#[derive(Args, Debug)]
+pub struct ServeArgs {
+  /// Directory holding the scan data [default: OUT_DIR]
+  pub directory: Option<String>,
+  #[arg(default_value_t = 8080)]
+  pub port: u16,
+}
+
+// not a PossibleValuesParser: random takes an optional :SEED
+fn parse_insertion_order(value_s: &str) -> Result<InsertionOrder, String> {
+  InsertionOrder::try_from(value_s.to_string())
+}
+
+fn initial_tour_parser() -> impl TypedValueParser<Value = InitialTour> {
+  let names: Vec<&'static str> = InitialTour::all().iter().map(|t| t.name()).collect();
+  PossibleValuesParser::new(names).map(|name| InitialTour::from_name(&name).unwrap_or_default())
+}
+
+fn family_parser() -> impl TypedValueParser<Value = Family> {
+  let names: Vec<&'static str> = Family::all().iter().map(|f| f.name()).collect();
+  PossibleValuesParser::new(names).map(|name| Family::from_name(&name).unwrap_or_default())
+}
+
+fn metric_parser() -> impl TypedValueParser<Value = Metric> {
+  let names: Vec<&'static str> = Metric::all().iter().map(|m| m.name()).collect();
+  PossibleValuesParser::new(names).map(|name| Metric::from_name(&name).unwrap_or_default())
+}
+
+fn parse_positive(value_s: &str) -> Result<fp, String> {
+  match value_s.parse::<fp>() {
+    Ok(value) if value > 0.0 && value.is_finite() => Ok(value),
+    Ok(_) => Err("must be a positive number".to_string()),
+    Err(e) => Err(e.to_string()),
+  }
+}
+
+fn parse_export_file(file_arg: &str) -> Result<String, String> {
+  match exporters::ExportFormat::from_file(file_arg) {
+    Some(_) => Ok(file_arg.to_string()),
+    None => Err("expected a .geojson, .json, .gpx or .kml file".to_string()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn parse(args: &str) -> Result<Cli, clap::Error> {
+    Cli::parse_args(args.split_whitespace().map(|a| a.to_string()).collect())
+  }
+
+  #[test]
+  fn files_default_to_solve_and_bad_values_are_errors() {
+    let cli = parse("tsp-sol tsp/berlin52.tsp brute --view --insertion-order cheapest").unwrap();
+    match cli.command {
+      Command::Solve(args) => {
+        assert_eq!(args.file, "tsp/berlin52.tsp");
+        assert_eq!(args.solvers(), (true, true, true, false));
+        assert_eq!(args.jalgo.insertion_order, Some(InsertionOrder::Cheapest));
+      }
+      other => panic!("expected solve, got {:?}", other),
+    }
+
+    // a file named like a solver word is still the file
+    match parse("tsp-sol solve view").unwrap().command {
+      Command::Solve(args) => assert_eq!((args.file.as_str(), args.solvers()), ("view", (true, false, false, false))),
+      other => panic!("expected solve, got {:?}", other),
+    }
+
+    match parse("tsp-sol spray 4 0.2 --one-step").unwrap().command {
+      Command::Spray(args) => assert_eq!((args.n, args.granularity, args.one_step), (4, 0.2, true)),
+      other => panic!("expected spray, got {:?}", other),
+    }
+
+    assert!(parse("tsp-sol spray four").is_err());
+    assert!(parse("tsp-sol spray 4 -0.5").is_err());
+    assert!(parse("tsp-sol delta --family lines").is_err());
+    match parse("tsp-sol delta --runs 5").unwrap().command {
+      Command::Delta(args) => assert_eq!((args.runs, args.all_insertion_orders), (5, false)),
+      other => panic!("expected delta, got {:?}", other),
+    }
+    match parse("tsp-sol delta --all-insertion-orders").unwrap().command {
+      Command::Delta(args) => assert!(args.all_insertion_orders),
+      other => panic!("expected delta, got {:?}", other),
+    }
+    assert!(parse("tsp-sol tsp/berlin52.tsp --prizes p.txt").is_err());
+  }
+}
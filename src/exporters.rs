@@ -0,0 +1,196 @@
+/*
+ *  tsp-sol - an experimental environment for traveling salesman solution analysis
+ *  Copyright (C) 2023  Jeffrey McAteer <jeffrey@jmcateer.com>
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; version 2 of the License ONLY.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License along
+ *  with this program; if not, write to the Free Software Foundation, Inc.,
+ *  51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+ */
+
+// Solved tours written for mapping tools: a GeoJSON FeatureCollection, a GPX route or a KML document,
+// each carrying the tour's order, its city ids and its length.
+
+use super::*;
+
+use serde_json::json;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+  GeoJson,
+  Gpx,
+  Kml,
+}
+
+impl ExportFormat {
+  /// The format a file name's extension asks for, None for anything else.
+  pub fn from_file(file_arg: &str) -> Option<ExportFormat> {
+    let extension = Path::new(file_arg).extension()?.to_string_lossy().to_lowercase();
+    match extension.as_str() {
+      "geojson" | "json" => Some(ExportFormat::GeoJson),
+      "gpx" => Some(ExportFormat::Gpx),
+      "kml" => Some(ExportFormat::Kml),
+      _ => None,
+    }
+  }
+}
+
+/// A tour in visiting order, the cities being (file id, longitude, latitude).
+pub struct ExportedTour {
+  /// Instance name, usually the file stem
+  pub name: String,
+  /// Solver that produced the tour, e.g. "jeff_algo"
+  pub algo: String,
+  pub length: CityWeight,
+  pub cities: Vec<(CityNum, f64, f64)>,
+}
+
+impl ExportedTour {
+  /// tour's cities as the file wrote them: geographic metrics give decimal degrees, planar
+  /// coordinates are passed through as x = longitude, y = latitude. Both are rounded to 6 decimals
+  /// (about 0.1 m) so f32 noise does not end up in the file.
+  pub fn new(name: &str, algo: &str, file_coordinates: &[(CityNum, CityXYCoord, CityXYCoord)], metric: Metric, tour: &[CityNum], length: CityWeight) -> ExportedTour {
+    let cities = tour.iter().map(|citynum| {
+      let (id, x, y) = file_coordinates[*citynum];
+      let (lon, lat) = match metric.lon_lat_radians(x, y) {
+        Some((lon, lat)) => (lon.to_degrees(), lat.to_degrees()),
+        None => (x as f64, y as f64),
+      };
+      (id, (lon * 1e6).round() / 1e6, (lat * 1e6).round() / 1e6)
+    }).collect();
+    ExportedTour {
+      name: name.to_string(),
+      algo: algo.to_string(),
+      length,
+      cities,
+    }
+  }
+
+  // positions of the closed tour, back to the first city
+  fn closed_positions(&self) -> Vec<(f64, f64)> {
+    let mut positions: Vec<(f64, f64)> = self.cities.iter().map(|(_id, lon, lat)| (*lon, *lat)).collect();
+    if let Some(first) = positions.first().cloned() {
+      positions.push(first);
+    }
+    positions
+  }
+
+  fn city_ids(&self) -> Vec<CityNum> {
+    self.cities.iter().map(|(id, _lon, _lat)| *id).collect()
+  }
+
+  /// A LineString feature for the tour followed by one Point feature per city, numbered by its file id.
+  pub fn to_geojson(&self) -> String {
+    let mut features = vec![json!({
+      "type": "Feature",
+      "geometry": {
+        "type": "LineString",
+        "coordinates": self.closed_positions().iter().map(|(lon, lat)| vec![*lon, *lat]).collect::<Vec<_>>(),
+      },
+      "properties": {
+        "name": self.name,
+        "algo": self.algo,
+        "length": self.length,
+        "city_ids": self.city_ids(),
+      },
+    })];
+    for (order, (id, lon, lat)) in self.cities.iter().enumerate() {
+      features.push(json!({
+        "type": "Feature",
+        "id": id,
+        "geometry": { "type": "Point", "coordinates": [lon, lat] },
+        "properties": { "id": id, "order": order },
+      }));
+    }
+    let collection = json!({ "type": "FeatureCollection", "features": features });
+    serde_json::to_string_pretty(&collection).unwrap_or_default() + "\n"
+  }
+
+  /// One <rte> whose points are the cities in order, ending back at the first.
+  pub fn to_gpx(&self) -> String {
+    let mut gpx = String::new();
+    gpx += "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n";
+    gpx += "<gpx version=\"1.1\" creator=\"tsp-sol\" xmlns=\"http://www.topografix.com/GPX/1/1\">\n";
+    gpx += "  <rte>\n";
+    gpx += &format!("    <name>{}</name>\n", xml_escape(&self.name));
+    gpx += &format!("    <desc>{} tour, length {}</desc>\n", xml_escape(&self.algo), self.length);
+    let mut ids = self.city_ids();
+    ids.extend(ids.first().cloned());
+    for (id, (lon, lat)) in ids.iter().zip(self.closed_positions()) {
+      gpx += &format!("    <rtept lat=\"{}\" lon=\"{}\"><name>{}</name></rtept>\n", lat, lon, id);
+    }
+    gpx += "  </rte>\n";
+    gpx += "</gpx>\n";
+    gpx
+  }
+
+  /// A Placemark with the tour's LineString, then one Placemark per city named by its file id.
+  pub fn to_kml(&self) -> String {
+    let mut kml = String::new();
+    kml += "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n";
+    kml += "<kml xmlns=\"http://www.opengis.net/kml/2.2\">\n";
+    kml += "  <Document>\n";
+    kml += &format!("    <name>{}</name>\n", xml_escape(&self.name));
+    kml += "    <Placemark>\n";
+    kml += &format!("      <name>{} tour</name>\n", xml_escape(&self.algo));
+    kml += "      <ExtendedData>\n";
+    kml += &format!("        <Data name=\"length\"><value>{}</value></Data>\n", self.length);
+    let ids: Vec<String> = self.city_ids().iter().map(|id| format!("{}", id)).collect();
+    kml += &format!("        <Data name=\"city_ids\"><value>{}</value></Data>\n", ids.join(" "));
+    kml += "      </ExtendedData>\n";
+    let positions: Vec<String> = self.closed_positions().iter().map(|(lon, lat)| format!("{},{}", lon, lat)).collect();
+    kml += &format!("      <LineString><coordinates>{}</coordinates></LineString>\n", positions.join(" "));
+    kml += "    </Placemark>\n";
+    for (id, lon, lat) in self.cities.iter() {
+      kml += &format!("    <Placemark><name>{}</name><Point><coordinates>{},{}</coordinates></Point></Placemark>\n", id, lon, lat);
+    }
+    kml += "  </Document>\n";
+    kml += "</kml>\n";
+    kml
+  }
+
+  /// Writes the tour in the format file_arg's extension names.
+  pub fn write(&self, file_arg: &str) -> Result<(), String> {
+    let text = match ExportFormat::from_file(file_arg) {
+      Some(ExportFormat::GeoJson) => self.to_geojson(),
+      Some(ExportFormat::Gpx) => self.to_gpx(),
+      Some(ExportFormat::Kml) => self.to_kml(),
+      None => return Err(format!("{}: expected a .geojson, .json, .gpx or .kml file", file_arg)),
+    };
+    fs::write(file_arg, text).map_err(|e| format!("{}: {}", file_arg, e))
+  }
+}
+
+fn xml_escape(s: &str) -> String {
+  s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn exported_geojson_reads_back_in_tour_order() {
+    let file_coordinates: Vec<(CityNum, CityXYCoord, CityXYCoord)> = vec![(1, 2.0, 2.0), (2, 6.0, 2.0), (3, 6.0, 5.0)];
+    let weights = compute_weight_coords(&file_coordinates);
+    let tour = vec![2, 0, 1];
+    let exported = ExportedTour::new("tri & co", "jeff_algo", &file_coordinates, Metric::Euclidean, &tour, compute_dist(&weights, &tour));
+    assert!((exported.length - 12.0).abs() < fp_epsilon);
+
+    let (cities, _metric) = importers::parse_geojson(&exported.to_geojson()).unwrap();
+    assert_eq!(cities, vec![(3, 6.0, 5.0), (1, 2.0, 2.0), (2, 6.0, 2.0)]);
+
+    let gpx = exported.to_gpx();
+    assert_eq!(gpx.matches("<rtept ").count(), 4);
+    assert!(gpx.contains("<name>tri &amp; co</name>"));
+    assert!(exported.to_kml().contains("<coordinates>6,5 2,2 6,2 6,5</coordinates>"));
+  }
+}
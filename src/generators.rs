@@ -0,0 +1,241 @@
+/*
+ *  tsp-sol - an experimental environment for traveling salesman solution analysis
+ *  Copyright (C) 2023  Jeffrey McAteer <jeffrey@jmcateer.com>
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; version 2 of the License ONLY.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License along
+ *  with this program; if not, write to the Free Software Foundation, Inc.,
+ *  51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+ */
+
+use super::*;
+
+use rand::distributions::{Distribution, Normal};
+
+/// DIMACS challenge instances (portcgen) place cities on a 1,000,000 x 1,000,000 integer grid.
+const DIMACS_GRID: fp = 1_000_000.0;
+
+/// Shapes of city delta, selective and generate can draw from.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Family {
+  /// Uniform random points in the box, what delta and selective always used
+  #[default]
+  Uniform,
+  /// Gaussian blobs around a few random centers
+  Clustered,
+  /// Evenly spaced rows and columns, full of equal-length tours
+  Grid,
+  /// Grid with every city nudged by up to 15% of the spacing
+  PerturbedGrid,
+  /// Rings around the center of the box
+  Circles,
+  /// Every city on a random convex polygon, so the hull is the only optimal tour
+  ConvexPolygon,
+  /// DIMACS "E" instances: uniform on the integer grid
+  DimacsUniform,
+  /// DIMACS "C" instances: n/10 centers with normally distributed cities around each
+  DimacsClustered,
+}
+
+impl Family {
+  pub fn all() -> Vec<Family> {
+    vec![
+      Family::Uniform, Family::Clustered, Family::Grid, Family::PerturbedGrid,
+      Family::Circles, Family::ConvexPolygon, Family::DimacsUniform, Family::DimacsClustered,
+    ]
+  }
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      Family::Uniform => "uniform",
+      Family::Clustered => "clustered",
+      Family::Grid => "grid",
+      Family::PerturbedGrid => "perturbed-grid",
+      Family::Circles => "circles",
+      Family::ConvexPolygon => "convex-polygon",
+      Family::DimacsUniform => "dimacs-uniform",
+      Family::DimacsClustered => "dimacs-clustered",
+    }
+  }
+
+  pub fn from_name(name: &str) -> Option<Family> {
+    Family::all().into_iter().find(|f| f.name() == name)
+  }
+
+  /// Box `generate` writes TSPLIB files in: the challenge's grid for DIMACS families, the views' 0-15 box otherwise.
+  pub fn tsplib_bounds(&self) -> (fp, fp, fp, fp) {
+    match self {
+      Family::DimacsUniform | Family::DimacsClustered => (0.0, DIMACS_GRID, 0.0, DIMACS_GRID),
+      _ => (x_min_bound, x_max_bound, y_min_bound, y_max_bound),
+    }
+  }
+}
+
+/// num_points cities of the given family inside bounds (x_min, x_max, y_min, y_max), numbered from 0.
+pub fn generate(family: Family, num_points: usize, bounds: (fp, fp, fp, fp)) -> Vec<(CityNum, CityXYCoord, CityXYCoord)> {
+  generate_seeded(family, num_points, bounds, rand::thread_rng().gen())
+}
+
+/// Like generate, but the same seed always gives the same cities.
+pub fn generate_seeded(family: Family, num_points: usize, bounds: (fp, fp, fp, fp), seed: u64) -> Vec<(CityNum, CityXYCoord, CityXYCoord)> {
+  let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+  let (min_x, max_x, min_y, max_y) = bounds;
+  let (width, height) = (max_x - min_x, max_y - min_y);
+  let (center_x, center_y) = (min_x + (width / 2.0), min_y + (height / 2.0));
+
+  let points: Vec<(fp, fp)> = match family {
+    Family::Uniform => {
+      (0..num_points).map(|_| (rng.gen_range(min_x, max_x), rng.gen_range(min_y, max_y)) ).collect()
+    }
+    Family::Clustered => {
+      let num_clusters = (num_points / 5).max(2);
+      let std_dev = 0.06 * width.min(height);
+      let centers: Vec<(fp, fp)> = (0..num_clusters).map(|_| (
+        rng.gen_range(min_x + (0.15 * width), max_x - (0.15 * width)),
+        rng.gen_range(min_y + (0.15 * height), max_y - (0.15 * height)),
+      )).collect();
+      let normal = Normal::new(0.0, std_dev as f64);
+      (0..num_points).map(|i| {
+        let (cx, cy) = centers[i % num_clusters];
+        (
+          (cx + normal.sample(&mut rng) as fp).clamp(min_x, max_x),
+          (cy + normal.sample(&mut rng) as fp).clamp(min_y, max_y),
+        )
+      }).collect()
+    }
+    Family::Grid | Family::PerturbedGrid => {
+      let cols = (num_points as fp).sqrt().ceil().max(1.0) as usize;
+      let rows = num_points.div_ceil(cols).max(1);
+      let (spacing_x, spacing_y) = (width / (cols as fp), height / (rows as fp));
+      let jitter = if family == Family::PerturbedGrid { 0.15 } else { 0.0 };
+      (0..num_points).map(|i| {
+        let (col, row) = (i % cols, i / cols);
+        let (mut x, mut y) = (min_x + (spacing_x * (col as fp + 0.5)), min_y + (spacing_y * (row as fp + 0.5)));
+        if jitter > 0.0 {
+          x += rng.gen_range(-jitter, jitter) * spacing_x;
+          y += rng.gen_range(-jitter, jitter) * spacing_y;
+        }
+        (x, y)
+      }).collect()
+    }
+    Family::Circles => {
+      // outer rings get more cities, in proportion to their radius
+      let num_rings = ((num_points as fp).sqrt() / 2.0).round().max(1.0) as usize;
+      let max_radius = 0.45 * width.min(height);
+      let ring_weight_total: usize = (1..=num_rings).sum();
+      let mut points = vec![];
+      for ring in 1..=num_rings {
+        let mut ring_points = (num_points * ring) / ring_weight_total;
+        if ring == num_rings {
+          ring_points = num_points - points.len();
+        }
+        let radius = max_radius * (ring as fp) / (num_rings as fp);
+        let phase = rng.gen_range(0.0, std::f32::consts::TAU);
+        for k in 0..ring_points {
+          let angle = phase + (std::f32::consts::TAU * (k as fp) / (ring_points as fp));
+          points.push((center_x + (radius * angle.cos()), center_y + (radius * angle.sin())));
+        }
+      }
+      points
+    }
+    Family::ConvexPolygon => {
+      // points on an ellipse are always in convex position; one random angle per equal slice
+      // keeps neighbours far enough apart that they never look collinear
+      let (radius_x, radius_y) = (rng.gen_range(0.25, 0.45) * width, rng.gen_range(0.25, 0.45) * height);
+      (0..num_points).map(|k| {
+        let angle = std::f32::consts::TAU * ((k as fp) + rng.gen_range(0.1, 0.9)) / (num_points as fp);
+        (center_x + (radius_x * angle.cos()), center_y + (radius_y * angle.sin()))
+      }).collect()
+    }
+    Family::DimacsUniform => {
+      (0..num_points).map(|_| {
+        let (x, y) = (rng.gen_range(0.0, DIMACS_GRID).floor(), rng.gen_range(0.0, DIMACS_GRID).floor());
+        scale_from_dimacs((x, y), bounds)
+      }).collect()
+    }
+    Family::DimacsClustered => {
+      let num_centers = (num_points / 10).max(1);
+      let centers: Vec<(fp, fp)> = (0..num_centers).map(|_| (rng.gen_range(0.0, DIMACS_GRID), rng.gen_range(0.0, DIMACS_GRID)) ).collect();
+      let normal = Normal::new(0.0, (DIMACS_GRID as f64) / (num_points as f64).sqrt());
+      (0..num_points).map(|_| {
+        let (cx, cy) = centers[rng.gen_range(0, num_centers)];
+        let x = (cx + normal.sample(&mut rng) as fp).floor().clamp(0.0, DIMACS_GRID - 1.0);
+        let y = (cy + normal.sample(&mut rng) as fp).floor().clamp(0.0, DIMACS_GRID - 1.0);
+        scale_from_dimacs((x, y), bounds)
+      }).collect()
+    }
+  };
+
+  points.into_iter().enumerate().map(|(i, (x, y))| (i, x, y) ).collect()
+}
+
+fn scale_from_dimacs(point: (fp, fp), bounds: (fp, fp, fp, fp)) -> (fp, fp) {
+  let (min_x, max_x, min_y, max_y) = bounds;
+  // scale before multiplying so the challenge's own grid comes back exactly
+  (min_x + (point.0 * ((max_x - min_x) / DIMACS_GRID)), min_y + (point.1 * ((max_y - min_y) / DIMACS_GRID)))
+}
+
+/// Writes node_coordinates as a EUC_2D TSPLIB file, numbering cities from 1 like the files under tsp/.
+pub fn write_tsplib(file_path: &str, name: &str, comment: &str, node_coordinates: &[(CityNum, CityXYCoord, CityXYCoord)]) -> Result<(), String> {
+  let mut tsp_s = String::new();
+  tsp_s += &format!("NAME : {}\n", name);
+  tsp_s += &format!("COMMENT : {}\n", comment);
+  tsp_s += "TYPE : TSP\n";
+  tsp_s += &format!("DIMENSION : {}\n", node_coordinates.len());
+  tsp_s += "EDGE_WEIGHT_TYPE : EUC_2D\n";
+  tsp_s += "NODE_COORD_SECTION\n";
+  for (i, (_citynum, x, y)) in node_coordinates.iter().enumerate() {
+    tsp_s += &format!("{} {} {}\n", i + 1, x, y);
+  }
+  tsp_s += "EOF\n";
+
+  if let Some(parent) = Path::new(file_path).parent() {
+    fs::create_dir_all(parent).map_err(|e| format!("Cannot create {}: {}", parent.display(), e))?;
+  }
+  fs::write(file_path, tsp_s).map_err(|e| format!("Error writing {}: {}", file_path, e))?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn families_stay_in_bounds() {
+    let bounds = (3.0, 12.0, 3.0, 12.0);
+    for family in Family::all() {
+      for (seed, num_points) in [4, 7, 50].into_iter().enumerate() {
+        let cities = generate_seeded(family, num_points, bounds, seed as u64);
+        assert_eq!(cities.len(), num_points, "{}", family.name());
+        for (i, x, y) in cities.iter() {
+          assert!(*x >= bounds.0 && *x <= bounds.1 && *y >= bounds.2 && *y <= bounds.3, "{} city {} at ({}, {})", family.name(), i, x, y);
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn convex_polygon_is_its_own_hull() {
+    for seed in 0..10 {
+      let cities = generate_seeded(Family::ConvexPolygon, 30, (0.0, 15.0, 0.0, 15.0), seed);
+      assert_eq!(geometry::convex_hull(&cities).len(), cities.len(), "seed {}", seed);
+    }
+  }
+
+  #[test]
+  fn same_seed_same_cities() {
+    let bounds = (0.0, 15.0, 0.0, 15.0);
+    for family in Family::all() {
+      assert_eq!(generate_seeded(family, 20, bounds, 42), generate_seeded(family, 20, bounds, 42), "{}", family.name());
+    }
+    assert_ne!(generate_seeded(Family::Uniform, 20, bounds, 42), generate_seeded(Family::Uniform, 20, bounds, 43));
+  }
+}
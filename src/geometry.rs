@@ -0,0 +1,106 @@
+/*
+ *  tsp-sol - an experimental environment for traveling salesman solution analysis
+ *  Copyright (C) 2023  Jeffrey McAteer <jeffrey@jmcateer.com>
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; version 2 of the License ONLY.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License along
+ *  with this program; if not, write to the Free Software Foundation, Inc.,
+ *  51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+ */
+
+use super::*;
+
+/// Side length of the grid cities are snapped to before walking the hilbert curve.
+const HILBERT_ORDER: u32 = 1 << 16;
+
+// > 0 when o -> a -> b turns counter-clockwise
+fn cross(o: &(CityNum, CityXYCoord, CityXYCoord), a: &(CityNum, CityXYCoord, CityXYCoord), b: &(CityNum, CityXYCoord, CityXYCoord)) -> fp {
+  ((a.1 - o.1) * (b.2 - o.2)) - ((a.2 - o.2) * (b.1 - o.1))
+}
+
+/// Indexes of the cities on the convex hull in counter-clockwise order (Andrew's monotone chain).
+/// Collinear cities along an edge are left off, so 3 cities on a line give a 2-city "hull".
+pub fn convex_hull(node_coordinates: &[(CityNum, CityXYCoord, CityXYCoord)]) -> Vec<CityNum> {
+  // work on indexes, tsplib files number their cities from 1
+  let mut points: Vec<(CityNum, CityXYCoord, CityXYCoord)> = node_coordinates.iter().enumerate().map(|(i, c)| (i, c.1, c.2) ).collect();
+  points.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal).then(a.2.partial_cmp(&b.2).unwrap_or(std::cmp::Ordering::Equal)) );
+  if points.len() < 3 {
+    return points.iter().map(|p| p.0).collect();
+  }
+
+  let mut hull: Vec<(CityNum, CityXYCoord, CityXYCoord)> = vec![];
+  // lower hull, left to right
+  for p in points.iter() {
+    while hull.len() >= 2 && cross(&hull[hull.len() - 2], &hull[hull.len() - 1], p) <= 0.0 {
+      hull.pop();
+    }
+    hull.push(*p);
+  }
+  // upper hull, right to left
+  let lower_len = hull.len() + 1;
+  for p in points.iter().rev().skip(1) {
+    while hull.len() >= lower_len && cross(&hull[hull.len() - 2], &hull[hull.len() - 1], p) <= 0.0 {
+      hull.pop();
+    }
+    hull.push(*p);
+  }
+  hull.pop(); // last point is the first point again
+
+  hull.iter().map(|p| p.0).collect()
+}
+
+/// Indexes of the cities sorted by their position along a hilbert curve over the cities' bounding box,
+/// so cities close in the order are close on the map.
+pub fn hilbert_order(node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>) -> Vec<CityNum> {
+  let (smallest_x, largest_y, largest_x, smallest_y) = get_point_extents(node_coordinates);
+  let span = (largest_x - smallest_x).max(largest_y - smallest_y).max(fp_epsilon);
+
+  let mut keyed: Vec<(u64, CityNum)> = node_coordinates.iter().enumerate().map(|(i, (_num, x, y))| {
+    let grid_x = (((x - smallest_x) / span) * (HILBERT_ORDER - 1) as fp) as u32;
+    let grid_y = (((y - smallest_y) / span) * (HILBERT_ORDER - 1) as fp) as u32;
+    (hilbert_distance(grid_x, grid_y), i)
+  }).collect();
+  keyed.sort();
+
+  keyed.iter().map(|(_d, i)| *i).collect()
+}
+
+// Classic xy -> d walk of a HILBERT_ORDER x HILBERT_ORDER hilbert curve
+fn hilbert_distance(mut x: u32, mut y: u32) -> u64 {
+  let mut d: u64 = 0;
+  let mut s = HILBERT_ORDER / 2;
+  while s > 0 {
+    let rx = if (x & s) > 0 { 1 } else { 0 };
+    let ry = if (y & s) > 0 { 1 } else { 0 };
+    d += (s as u64) * (s as u64) * ((3 * rx) ^ ry) as u64;
+    // rotate the quadrant so the sub-curve lines up
+    if ry == 0 {
+      if rx == 1 {
+        x = HILBERT_ORDER - 1 - x;
+        y = HILBERT_ORDER - 1 - y;
+      }
+      std::mem::swap(&mut x, &mut y);
+    }
+    s /= 2;
+  }
+  d
+}
+
+/// num_points cities evenly spaced on a circle in the middle of the view, for instances
+/// (explicit ATSP matrices) that come without coordinates to draw.
+pub fn circle_layout(num_points: usize) -> Vec<(CityNum, CityXYCoord, CityXYCoord)> {
+  let (center_x, center_y) = ((x_min_bound + x_max_bound) / 2.0, (y_min_bound + y_max_bound) / 2.0);
+  let radius = 0.4 * (x_max_bound - x_min_bound).min(y_max_bound - y_min_bound);
+  (0..num_points).map(|i| {
+    let angle = std::f32::consts::TAU * (i as fp) / (num_points.max(1) as fp);
+    (i, center_x + (radius * angle.cos()), center_y + (radius * angle.sin()))
+  }).collect()
+}
@@ -0,0 +1,225 @@
+/*
+ *  tsp-sol - an experimental environment for traveling salesman solution analysis
+ *  Copyright (C) 2023  Jeffrey McAteer <jeffrey@jmcateer.com>
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; version 2 of the License ONLY.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License along
+ *  with this program; if not, write to the Free Software Foundation, Inc.,
+ *  51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+ */
+
+// Cities from files that are not TSPLIB: CSV rows and GeoJSON points. Both produce the same
+// (file id, x, y) list open_tsp_coordinates returns for .tsp files, plus the metric the data implies.
+
+use super::*;
+
+use serde_json::Value;
+use std::collections::HashSet;
+
+/// Which importer handles file_arg, by extension; None for TSPLIB files.
+pub fn format_of(file_arg: &str) -> Option<&'static str> {
+  let extension = Path::new(file_arg).extension()?.to_string_lossy().to_lowercase();
+  match extension.as_str() {
+    "csv" => Some("csv"),
+    "geojson" | "json" => Some("geojson"),
+    _ => None,
+  }
+}
+
+/// Reads file_arg with the importer format_of picks. Errors are printed and give None.
+pub fn open(file_arg: &str) -> Option<(Vec<City>, Metric)> {
+  let text = match fs::read_to_string(file_arg) {
+    Ok(text) => text,
+    Err(e) => {
+      report_error(&format!("Cannot open {}: {}", file_arg, e));
+      return None;
+    }
+  };
+  let imported = match format_of(file_arg) {
+    Some("csv") => parse_csv(&text),
+    Some("geojson") => parse_geojson(&text),
+    _ => Err("not a CSV or GeoJSON file".to_string()),
+  };
+  match imported {
+    Ok((node_coordinates, _metric)) if node_coordinates.is_empty() => {
+      report_error(&format!("Err: no cities found in {}", file_arg));
+      None
+    }
+    Ok(imported) => Some(imported),
+    Err(e) => {
+      report_error(&format!("Error importing {}: {}", file_arg, e));
+      None
+    }
+  }
+}
+
+/// "id,x,y[,z]" rows; z is dropped. An optional header may name the columns (id, x, y, z, or
+/// lon/lng/longitude and lat/latitude, which switch to the haversine metric) in any order.
+pub fn parse_csv(text: &str) -> Result<(Vec<City>, Metric), String> {
+  let rows: Vec<(usize, Vec<String>)> = text.lines().enumerate()
+    .filter(|(_line_i, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+    .map(|(line_i, line)| (line_i + 1, line.split(',').map(|field| field.trim().trim_matches('"').to_string()).collect()))
+    .collect();
+
+  // positions of the id, x and y columns
+  let mut columns = (0, 1, 2);
+  let mut metric = Metric::Euclidean;
+  let mut data_rows = &rows[..];
+  if let Some((_line_no, header)) = rows.first() {
+    if header.iter().any(|field| field.parse::<fp>().is_err()) {
+      let find = |names: &[&str]| header.iter().position(|field| names.contains(&field.to_lowercase().as_str()));
+      let lon_lat = (find(&["lon", "lng", "longitude"]), find(&["lat", "latitude"]));
+      let (x_col, y_col) = match lon_lat {
+        (Some(lon_col), Some(lat_col)) => {
+          metric = Metric::Haversine;
+          (lon_col, lat_col)
+        }
+        _ => match (find(&["x"]), find(&["y"])) {
+          (Some(x_col), Some(y_col)) => (x_col, y_col),
+          _ => return Err(format!("header {:?} needs x and y (or lon and lat) columns", header.join(","))),
+        },
+      };
+      let id_col = find(&["id"]).ok_or_else(|| format!("header {:?} needs an id column", header.join(",")))?;
+      columns = (id_col, x_col, y_col);
+      data_rows = &rows[1..];
+    }
+  }
+
+  let (id_col, x_col, y_col) = columns;
+  let mut node_coordinates = vec![];
+  for (line_no, fields) in data_rows.iter() {
+    let field = |col: usize| fields.get(col).ok_or_else(|| format!("line {}: expected at least {} columns, got {:?}", line_no, col + 1, fields.join(",")));
+    let id = field(id_col)?.parse::<usize>().map_err(|e| format!("line {}: id {:?}: {}", line_no, fields[id_col], e))?;
+    let x = field(x_col)?.parse::<fp>().map_err(|e| format!("line {}: x {:?}: {}", line_no, fields[x_col], e))?;
+    let y = field(y_col)?.parse::<fp>().map_err(|e| format!("line {}: y {:?}: {}", line_no, fields[y_col], e))?;
+    if !x.is_finite() {
+      return Err(format!("line {}: x is not finite", line_no));
+    }
+    if !y.is_finite() {
+      return Err(format!("line {}: y is not finite", line_no));
+    }
+    node_coordinates.push((id, x, y));
+  }
+  Ok((node_coordinates, metric))
+}
+
+/// Every Point (and each position of a MultiPoint) in a FeatureCollection, Feature or bare geometry.
+/// GeoJSON positions are [longitude, latitude(, altitude)], so the metric is haversine. A feature's
+/// "id" member, or else its "id" property, names the city; cities without one are numbered from 1.
+/// Two cities with the same id are an error.
+pub fn parse_geojson(text: &str) -> Result<(Vec<City>, Metric), String> {
+  let root: Value = serde_json::from_str(text).map_err(|e| format!("invalid JSON: {}", e))?;
+  let mut points: Vec<(Option<usize>, fp, fp)> = vec![];
+  collect_geojson_points(&root, None, &mut points)?;
+
+  let mut node_coordinates = vec![];
+  let mut ids = HashSet::new();
+  for (i, (id, lon, lat)) in points.into_iter().enumerate() {
+    let id = id.unwrap_or(i + 1);
+    if !ids.insert(id) {
+      return Err(format!("city id {} is used by more than one point", id));
+    }
+    node_coordinates.push((id, lon, lat));
+  }
+  Ok((node_coordinates, Metric::Haversine))
+}
+
+fn collect_geojson_points(value: &Value, feature_id: Option<usize>, points: &mut Vec<(Option<usize>, fp, fp)>) -> Result<(), String> {
+  let position = |coordinates: &Value| -> Result<(fp, fp), String> {
+    match coordinates.as_array().map(|a| a.as_slice()) {
+      Some([lon, lat, ..]) => match (lon.as_f64(), lat.as_f64()) {
+        (Some(lon), Some(lat)) if (lon as fp).is_finite() && (lat as fp).is_finite() => Ok((lon as fp, lat as fp)),
+        (Some(_lon), Some(_lat)) => Err(format!("position {} is not finite", coordinates)),
+        _ => Err(format!("position {} is not numeric", coordinates)),
+      },
+      _ => Err(format!("position {} needs a longitude and a latitude", coordinates)),
+    }
+  };
+
+  match value.get("type").and_then(|t| t.as_str()) {
+    Some("FeatureCollection") => {
+      for feature in value.get("features").and_then(|f| f.as_array()).ok_or("FeatureCollection without features")? {
+        collect_geojson_points(feature, None, points)?;
+      }
+    }
+    Some("Feature") => {
+      let id = value.get("id").or_else(|| value.get("properties").and_then(|p| p.get("id")))
+        .and_then(|id| id.as_u64().or_else(|| id.as_str().and_then(|s| s.parse().ok())))
+        .map(|id| id as usize);
+      if let Some(geometry) = value.get("geometry").filter(|g| !g.is_null()) {
+        collect_geojson_points(geometry, id, points)?;
+      }
+    }
+    Some("GeometryCollection") => {
+      for geometry in value.get("geometries").and_then(|g| g.as_array()).ok_or("GeometryCollection without geometries")? {
+        collect_geojson_points(geometry, feature_id, points)?;
+      }
+    }
+    Some("Point") => {
+      let (lon, lat) = position(value.get("coordinates").ok_or("Point without coordinates")?)?;
+      points.push((feature_id, lon, lat));
+    }
+    Some("MultiPoint") => {
+      // one feature id cannot name several cities
+      for coordinates in value.get("coordinates").and_then(|c| c.as_array()).ok_or("MultiPoint without coordinates")? {
+        let (lon, lat) = position(coordinates)?;
+        points.push((None, lon, lat));
+      }
+    }
+    // lines and polygons are not cities
+    Some(_) => {}
+    None => return Err("GeoJSON object without a type".to_string()),
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn csv_and_geojson_give_the_same_cities() {
+    let (plain, plain_metric) = parse_csv("1,3.5,4\n2,10,4.25,7\n\n3,6,11\n").unwrap();
+    assert_eq!(plain, vec![(1, 3.5, 4.0), (2, 10.0, 4.25), (3, 6.0, 11.0)]);
+    assert_eq!(plain_metric, Metric::Euclidean);
+
+    let (named, named_metric) = parse_csv("lat,lon,id\n4,3.5,1\n4.25,10,2\n11,6,3\n").unwrap();
+    assert_eq!(named, plain);
+    assert_eq!(named_metric, Metric::Haversine);
+
+    let geojson = r#"{"type": "FeatureCollection", "features": [
+      {"type": "Feature", "id": 1, "geometry": {"type": "Point", "coordinates": [3.5, 4]}, "properties": {}},
+      {"type": "Feature", "geometry": {"type": "Point", "coordinates": [10, 4.25, 120]}, "properties": {"id": "2"}},
+      {"type": "Feature", "geometry": {"type": "LineString", "coordinates": [[0, 0], [1, 1]]}, "properties": {}},
+      {"type": "Feature", "geometry": {"type": "Point", "coordinates": [6, 11]}, "properties": {"id": 3}}
+    ]}"#;
+    let (from_geojson, geojson_metric) = parse_geojson(geojson).unwrap();
+    assert_eq!(from_geojson, plain);
+    assert_eq!(geojson_metric, Metric::Haversine);
+
+    assert!(parse_csv("1,3.5\n").is_err());
+    assert!(parse_geojson("{\"type\": \"Point\"}").is_err());
+  }
+
+  #[test]
+  fn non_finite_values_and_repeated_ids_are_errors() {
+    assert_eq!(parse_csv("1,0,0\n2,nan,3\n"), Err("line 2: x is not finite".to_string()));
+    assert_eq!(parse_csv("id,x,y\n1,0,0\n2,5,inf\n"), Err("line 3: y is not finite".to_string()));
+    assert!(parse_geojson(r#"{"type": "Point", "coordinates": [1e300, 4]}"#).is_err());
+
+    // the id-less first point is numbered 1 like the second one's explicit id
+    let geojson = r#"{"type": "FeatureCollection", "features": [
+      {"type": "Feature", "geometry": {"type": "Point", "coordinates": [3.5, 4]}, "properties": {}},
+      {"type": "Feature", "id": 1, "geometry": {"type": "Point", "coordinates": [10, 4.25]}, "properties": {}}
+    ]}"#;
+    assert_eq!(parse_geojson(geojson), Err("city id 1 is used by more than one point".to_string()));
+  }
+}
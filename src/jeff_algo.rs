@@ -1,4 +1,4 @@
-/**
+/*
  *  tsp-sol - an experimental environment for traveling salesman solution analysis
  *  Copyright (C) 2023  Jeffrey McAteer <jeffrey@jmcateer.com>
  *
@@ -18,56 +18,379 @@
 
 use super::*;
 
-pub fn solve(node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>, weights: &Vec<Vec<CityWeight>>, save_run_prefix: Option<String>) -> Vec<usize> {
-  let mut ordered_visits = compute_largest_triangle(node_coordinates, weights);
+use serde::{Serialize, Deserialize};
+
+/// Picks the next city to insert given the tour so far and the weights; see InsertionOrder::next_city_num_fn.
+pub type NextCityFn<'a> = dyn Fn(&[CityNum], &[Vec<CityWeight>]) -> CityNum + 'a;
+
+/// Which not-yet-inserted city solve() adds to the tour next.
+/// Serialized by label(), e.g. "cheapest" or "random:42".
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(into = "String", try_from = "String")]
+pub enum InsertionOrder {
+  /// Lowest city number first, the original behavior
+  #[default]
+  First,
+  Last,
+  Middle,
+  /// City closest to any city already in the tour
+  Nearest,
+  /// City whose closest tour city is furthest away
+  Farthest,
+  /// City that lengthens the tour the least
+  Cheapest,
+  /// Cities shuffled by a StdRng seeded with this, so a failing order can be rerun;
+  /// written random:SEED, plain random is seed 0
+  Random(u64),
+  /// Convex hull cities in hull order, then cheapest
+  ConvexHull,
+  /// Cities in hilbert curve order
+  SpatialCurve,
+}
 
-  // let mut indicies_and_citynums_removed_so_far: Vec<(usize, CityNum)> = vec![];
+impl InsertionOrder {
+  pub fn all() -> Vec<InsertionOrder> {
+    vec![
+      InsertionOrder::First, InsertionOrder::Last, InsertionOrder::Middle,
+      InsertionOrder::Nearest, InsertionOrder::Farthest, InsertionOrder::Cheapest,
+      InsertionOrder::Random(0), InsertionOrder::ConvexHull, InsertionOrder::SpatialCurve,
+    ]
+  }
 
-  while ordered_visits.len() < weights.len() {
-    /*if ordered_visits.len() > 5 {
-      ordered_visits = next_step_5_deep(&ordered_visits, &node_coordinates, &weights, &next_city_num_first_not_inserted);
+  pub fn name(&self) -> &'static str {
+    match self {
+      InsertionOrder::First => "first",
+      InsertionOrder::Last => "last",
+      InsertionOrder::Middle => "middle",
+      InsertionOrder::Nearest => "nearest",
+      InsertionOrder::Farthest => "farthest",
+      InsertionOrder::Cheapest => "cheapest",
+      InsertionOrder::Random(_seed) => "random",
+      InsertionOrder::ConvexHull => "convex-hull",
+      InsertionOrder::SpatialCurve => "spatial-curve",
     }
-    else *//* if ordered_visits.len() > 4 {
-      ordered_visits = next_step_4_deep(&ordered_visits, &node_coordinates, &weights, &next_city_num_first_not_inserted);
+  }
+
+  /// name() plus the seed of random orders, e.g. "random:42".
+  pub fn label(&self) -> String {
+    match self {
+      InsertionOrder::Random(seed) => format!("random:{}", seed),
+      _ => self.name().to_string(),
     }
-    else */ if ordered_visits.len() > 3 {
-      ordered_visits = next_step_3_deep(&ordered_visits, &node_coordinates, &weights, &next_city_num_first_not_inserted);
+  }
+
+  /// Reads a name() or a label().
+  pub fn from_name(name: &str) -> Option<InsertionOrder> {
+    if let Some(seed) = name.strip_prefix("random:") {
+      return seed.parse().ok().map(InsertionOrder::Random);
     }
-    else {
-      ordered_visits = next_step(&ordered_visits, &node_coordinates, &weights, &next_city_num_first_not_inserted);
+    InsertionOrder::all().into_iter().find(|o| o.name() == name)
+  }
+
+  /// Builds the next_city_num_fn the next_step_* functions take; some orders precompute
+  /// a priority list from the coordinates so they are built once per solve.
+  pub fn next_city_num_fn(&self, node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>) -> Box<NextCityFn<'static>> {
+    match self {
+      InsertionOrder::First => Box::new(next_city_num_first_not_inserted),
+      InsertionOrder::Last => Box::new(next_city_num_last_not_inserted),
+      InsertionOrder::Middle => Box::new(next_city_num_middle_not_inserted),
+      InsertionOrder::Nearest => Box::new(next_city_num_nearest_not_inserted),
+      InsertionOrder::Farthest => Box::new(next_city_num_farthest_not_inserted),
+      InsertionOrder::Cheapest => Box::new(next_city_num_cheapest_not_inserted),
+      InsertionOrder::Random(seed) => {
+        let priority = random_priority(node_coordinates.len(), *seed);
+        Box::new(move |ordered_visits, weights| next_city_num_by_priority(&priority, ordered_visits, weights) )
+      }
+      InsertionOrder::ConvexHull => {
+        let hull = geometry::convex_hull(node_coordinates);
+        Box::new(move |ordered_visits, weights| {
+          for citynum in hull.iter() {
+            if !ordered_visits.contains(citynum) {
+              return *citynum;
+            }
+          }
+          next_city_num_cheapest_not_inserted(ordered_visits, weights)
+        })
+      }
+      InsertionOrder::SpatialCurve => {
+        let priority = geometry::hilbert_order(node_coordinates);
+        Box::new(move |ordered_visits, weights| next_city_num_by_priority(&priority, ordered_visits, weights) )
+      }
     }
+  }
+
+  /// The whole insertion order up front for orders that do not depend on the tour built so far,
+  /// None for the ones that look at the tour (middle, nearest, farthest, cheapest).
+  pub fn fixed_priority(&self, node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>) -> Option<Vec<CityNum>> {
+    match self {
+      InsertionOrder::First => Some( (0..node_coordinates.len()).collect() ),
+      InsertionOrder::Last => Some( (0..node_coordinates.len()).rev().collect() ),
+      InsertionOrder::Random(seed) => Some( random_priority(node_coordinates.len(), *seed) ),
+      InsertionOrder::ConvexHull => {
+        let mut priority = geometry::convex_hull(node_coordinates);
+        let mut on_hull = vec![false; node_coordinates.len()];
+        for citynum in priority.iter() {
+          on_hull[*citynum] = true;
+        }
+        priority.extend( (0..node_coordinates.len()).filter(|c| !on_hull[*c]) );
+        Some(priority)
+      }
+      InsertionOrder::SpatialCurve => Some( geometry::hilbert_order(node_coordinates) ),
+      InsertionOrder::Middle | InsertionOrder::Nearest | InsertionOrder::Farthest | InsertionOrder::Cheapest => None,
+    }
+  }
+}
+
+impl From<InsertionOrder> for String {
+  fn from(order: InsertionOrder) -> String {
+    order.label()
+  }
+}
+
+impl TryFrom<String> for InsertionOrder {
+  type Error = String;
+  fn try_from(name: String) -> Result<InsertionOrder, String> {
+    let names: Vec<&'static str> = InsertionOrder::all().iter().map(|o| o.name()).collect();
+    InsertionOrder::from_name(&name).ok_or_else(|| format!("unknown insertion order {:?}, expected one of {} or random:SEED", name, names.join(", ")))
+  }
+}
+
+// 0..num_cities shuffled by a StdRng seeded with seed
+fn random_priority(num_cities: usize, seed: u64) -> Vec<CityNum> {
+  let mut priority: Vec<CityNum> = (0..num_cities).collect();
+  rand::rngs::StdRng::seed_from_u64(seed).shuffle(&mut priority);
+  priority
+}
+
+/// The sub-tour solve() grows by insertion.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum InitialTour {
+  /// Longest edge plus the city furthest from both its ends, the original behavior
+  #[default]
+  LargestTriangle,
+  SmallestTriangle,
+  /// Every convex hull city in hull order; the optimal euclidean tour visits them in this order
+  ConvexHull,
+}
+
+impl InitialTour {
+  pub fn all() -> Vec<InitialTour> {
+    vec![ InitialTour::LargestTriangle, InitialTour::SmallestTriangle, InitialTour::ConvexHull ]
+  }
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      InitialTour::LargestTriangle => "largest-triangle",
+      InitialTour::SmallestTriangle => "smallest-triangle",
+      InitialTour::ConvexHull => "convex-hull",
+    }
+  }
+
+  pub fn from_name(name: &str) -> Option<InitialTour> {
+    InitialTour::all().into_iter().find(|t| t.name() == name)
+  }
+
+  pub fn compute(&self, node_coordinates: &[(CityNum, CityXYCoord, CityXYCoord)], weights: &[Vec<CityWeight>]) -> Vec<CityNum> {
+    let mut tour = self.compute_unoriented(node_coordinates, weights);
+    // with one-way (asymmetric) weights the same cycle costs differently in each direction
+    let mut reversed = tour.clone();
+    reversed.reverse();
+    if compute_dist(weights, &reversed) < compute_dist(weights, &tour) - fp_epsilon {
+      tour = reversed;
+    }
+    tour
+  }
+
+  fn compute_unoriented(&self, node_coordinates: &[(CityNum, CityXYCoord, CityXYCoord)], weights: &[Vec<CityWeight>]) -> Vec<CityNum> {
+    match self {
+      InitialTour::LargestTriangle => compute_largest_triangle(node_coordinates, weights),
+      InitialTour::SmallestTriangle => compute_smallest_triangle(node_coordinates, weights),
+      InitialTour::ConvexHull => {
+        let hull = geometry::convex_hull(node_coordinates);
+        if hull.len() < 3 {
+          // every city is on one line, there is no hull to start from
+          return compute_largest_triangle(node_coordinates, weights);
+        }
+        hull
+      }
+    }
+  }
+}
+
+/// Knobs for solve_with_options; the default reproduces solve().
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SolveOptions {
+  pub insertion_order: InsertionOrder,
+  pub initial_tour: InitialTour,
+  /// Cities removed and re-inserted around each insertion (see next_step_n_deep); each extra level multiplies runtime by N.
+  pub deep: usize,
+}
+
+/// solve() has always re-inserted 3 cities once the tour has more than 3.
+pub const DEFAULT_DEEP: usize = 3;
+
+impl Default for SolveOptions {
+  fn default() -> Self {
+    SolveOptions {
+      insertion_order: InsertionOrder::default(),
+      initial_tour: InitialTour::default(),
+      deep: DEFAULT_DEEP,
+    }
+  }
+}
+
+pub fn solve(node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>, weights: &Vec<Vec<CityWeight>>, save_run_prefix: Option<String>) -> Vec<usize> {
+  solve_with_options(node_coordinates, weights, save_run_prefix, &SolveOptions::default())
+}
+
+pub fn solve_with_options(node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>, weights: &Vec<Vec<CityWeight>>, save_run_prefix: Option<String>, options: &SolveOptions) -> Vec<usize> {
+  let next_city_num_fn = options.insertion_order.next_city_num_fn(node_coordinates);
+  let mut ordered_visits = options.initial_tour.compute(node_coordinates, weights);
+
+  // Runs that save images also record every step, see trace.rs
+  let mut solve_trace = save_run_prefix.as_ref().map(|_prefix| trace::SolveTrace::new(node_coordinates, weights, options, &ordered_visits));
 
-    // let citynum_to_insert = next_city_num_first_not_inserted(&ordered_visits, &weights);
-    // let num_steps = if ordered_visits.len() > 3 { 1 } else { 0 };
-    // indicies_and_citynums_removed_so_far.clear();
-    // next_step_n_deep(
-    //   &mut ordered_visits,
-    //   &node_coordinates,
-    //   &weights,
-    //   citynum_to_insert,
-    //   num_steps,
-    //   0.0,
-    //   &mut indicies_and_citynums_removed_so_far,
-    // );
-    // println!("\nsolve() end-of-while-loop\n");
+  while ordered_visits.len() < weights.len() {
+    match solve_trace.as_mut() {
+      Some(solve_trace) => {
+        let (next_ordered_visits, choice) = next_step_n_deep_choice(&ordered_visits, node_coordinates, weights, options.deep, &*next_city_num_fn);
+        solve_trace.record_step(weights, &ordered_visits, &next_ordered_visits, &choice);
+        ordered_visits = next_ordered_visits;
+      }
+      None => {
+        ordered_visits = next_step_n_deep(&ordered_visits, node_coordinates, weights, options.deep, &*next_city_num_fn);
+      }
+    }
   }
 
   // Store solution
-  match &save_run_prefix {
-    Some(prefix) => {
-      save_state_image(format!("{}/jalgo-{:03}.png", prefix, ordered_visits.len()), &ordered_visits, &node_coordinates);
-      fs::write(
-        format!("{}/jalgo-path.txt", prefix),
-        format!("{:?}\nDistance:{}", ordered_visits, compute_dist(weights, &ordered_visits))
-      ).expect("Unable to write file");
+  if let Some(prefix) = &save_run_prefix {
+    if let Some(solve_trace) = &solve_trace {
+      if let Err(e) = solve_trace.write(prefix) {
+        println!("Error writing step trace to {}: {}", prefix, e);
+      }
     }
-    None => { }
+    save_state_image(format!("{}/jalgo-{:03}.png", prefix, ordered_visits.len()), &ordered_visits, node_coordinates);
+    fs::write(
+      format!("{}/jalgo-path.txt", prefix),
+      format!("{:?}\nDistance:{}", ordered_visits, compute_dist(weights, &ordered_visits))
+    ).expect("Unable to write file");
   }
 
-  return ordered_visits;
+  ordered_visits
 }
 
-fn next_city_num_first_not_inserted(ordered_visits: &Vec<CityNum>, weights: &Vec<Vec<CityWeight>>) -> CityNum {
+/// The tour after the initial tour and after every insertion of solve_with_options, ending with its result.
+pub fn solve_steps(node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>, weights: &Vec<Vec<CityWeight>>, options: &SolveOptions) -> Vec<Vec<CityNum>> {
+  let next_city_num_fn = options.insertion_order.next_city_num_fn(node_coordinates);
+  let mut ordered_visits = options.initial_tour.compute(node_coordinates, weights);
+  let mut steps = vec![ordered_visits.clone()];
+  while ordered_visits.len() < weights.len() {
+    ordered_visits = next_step_n_deep(&ordered_visits, node_coordinates, weights, options.deep, &*next_city_num_fn);
+    steps.push(ordered_visits.clone());
+  }
+  steps
+}
+
+/// Candidate tour cities looked at around every city solve_fast inserts.
+const FAST_INSERT_CANDIDATES: usize = 8;
+
+/// Cheapest insertion for cities too large for a weights matrix (pla85900 would need ~30GB of it).
+/// Starts from the convex hull and inserts every other city next to one of the FAST_INSERT_CANDIDATES
+/// tour cities closest to it, found through a spatial::CityGrid, which is ~O( N log N ) overall.
+/// Distances are computed from coordinates; options.deep and options.initial_tour are not used.
+/// Orders that depend on the tour (middle, nearest, farthest, cheapest) fall back to first.
+pub fn solve_fast(node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>, options: &SolveOptions) -> Vec<CityNum> {
+  let n = node_coordinates.len();
+  if n < 4 {
+    return (0..n).collect();
+  }
+
+  let priority = match options.insertion_order.fixed_priority(node_coordinates) {
+    Some(priority) => priority,
+    None => {
+      println!("solve_fast cannot use insertion order {}, inserting in order first", options.insertion_order.label());
+      InsertionOrder::First.fixed_priority(node_coordinates).unwrap_or_default()
+    }
+  };
+
+  let mut initial_tour = geometry::convex_hull(node_coordinates);
+  // a non-finite coordinate can make the hull repeat cities, the linked list below holds each city once
+  let mut in_hull = vec![false; n];
+  initial_tour.retain(|citynum| !std::mem::replace(&mut in_hull[*citynum], true));
+  if initial_tour.len() < 3 {
+    // every city is on one line, any 3 cities make a (flat) starting tour
+    initial_tour = priority[..3].to_vec();
+  }
+
+  // Tour as a doubly linked list so an insertion does not shift every later city
+  let mut next: Vec<CityNum> = vec![usize::MAX; n];
+  let mut prev: Vec<CityNum> = vec![usize::MAX; n];
+  let mut grid = spatial::CityGrid::new(node_coordinates);
+  for i in 0..initial_tour.len() {
+    let citynum = initial_tour[i];
+    next[citynum] = initial_tour[(i + 1) % initial_tour.len()];
+    prev[citynum] = initial_tour[(i + initial_tour.len() - 1) % initial_tour.len()];
+    grid.insert(node_coordinates, citynum);
+  }
+
+  for citynum_to_insert in priority {
+    if next[citynum_to_insert] != usize::MAX {
+      continue; // already in the initial tour
+    }
+    let p = &node_coordinates[citynum_to_insert];
+
+    let mut best_delta = fp::INFINITY;
+    let mut best_from = usize::MAX;
+    for candidate in grid.nearby(p.1, p.2, FAST_INSERT_CANDIDATES) {
+      // both edges touching the candidate city
+      for (from, to) in [(prev[candidate], candidate), (candidate, next[candidate])] {
+        let delta =
+          (-coord_dist(&node_coordinates[from], &node_coordinates[to])) +
+          coord_dist(&node_coordinates[from], p) +
+          coord_dist(p, &node_coordinates[to]);
+        if delta < best_delta {
+          best_delta = delta;
+          best_from = from;
+        }
+      }
+    }
+    if best_from == usize::MAX {
+      // no nearby edge gave a finite delta, try every edge of the tour
+      for (from, to) in next.iter().enumerate().filter(|(_from, to)| **to != usize::MAX) {
+        let delta =
+          (-coord_dist(&node_coordinates[from], &node_coordinates[*to])) +
+          coord_dist(&node_coordinates[from], p) +
+          coord_dist(p, &node_coordinates[*to]);
+        if delta < best_delta {
+          best_delta = delta;
+          best_from = from;
+        }
+      }
+    }
+    if best_from == usize::MAX {
+      best_from = initial_tour[0]; // p is not finite, any edge keeps every city in the tour
+    }
+
+    let best_to = next[best_from];
+    next[best_from] = citynum_to_insert;
+    prev[citynum_to_insert] = best_from;
+    next[citynum_to_insert] = best_to;
+    prev[best_to] = citynum_to_insert;
+    grid.insert(node_coordinates, citynum_to_insert);
+  }
+
+  let mut ordered_visits = Vec::with_capacity(n);
+  let mut citynum = initial_tour[0];
+  for _ in 0..n {
+    ordered_visits.push(citynum);
+    citynum = next[citynum];
+  }
+  ordered_visits
+}
+
+fn next_city_num_first_not_inserted(ordered_visits: &[CityNum], weights: &[Vec<CityWeight>]) -> CityNum {
   let mut citynum_to_insert = 0;
   'outer: for p in 0..weights.len() {
     for ordered in ordered_visits {
@@ -81,7 +404,7 @@ fn next_city_num_first_not_inserted(ordered_visits: &Vec<CityNum>, weights: &Vec
   citynum_to_insert
 }
 
-fn next_city_num_last_not_inserted(ordered_visits: &Vec<CityNum>, weights: &Vec<Vec<CityWeight>>) -> CityNum {
+fn next_city_num_last_not_inserted(ordered_visits: &[CityNum], weights: &[Vec<CityWeight>]) -> CityNum {
   let mut citynum_to_insert = 0;
   'outer: for p in 0..weights.len() {
     for ordered in ordered_visits {
@@ -94,7 +417,7 @@ fn next_city_num_last_not_inserted(ordered_visits: &Vec<CityNum>, weights: &Vec<
   citynum_to_insert
 }
 
-fn next_city_num_middle_not_inserted(ordered_visits: &Vec<CityNum>, weights: &Vec<Vec<CityWeight>>) -> CityNum {
+fn next_city_num_middle_not_inserted(ordered_visits: &[CityNum], weights: &[Vec<CityWeight>]) -> CityNum {
   let mut possible_citynums_to_insert = vec![];
   'outer: for p in 0..weights.len() {
     for ordered in ordered_visits {
@@ -107,7 +430,73 @@ fn next_city_num_middle_not_inserted(ordered_visits: &Vec<CityNum>, weights: &Ve
   possible_citynums_to_insert[ possible_citynums_to_insert.len() / 2 ]
 }
 
-fn best_of(weights: &Vec<Vec<CityWeight>>, ordered_visits_a: Vec<CityNum>, ordered_visits_b: Vec<CityNum>) -> Vec<CityNum> {
+fn next_city_num_by_priority(priority: &[CityNum], ordered_visits: &[CityNum], weights: &[Vec<CityWeight>]) -> CityNum {
+  for citynum in priority {
+    if !ordered_visits.contains(citynum) {
+      return *citynum;
+    }
+  }
+  next_city_num_first_not_inserted(ordered_visits, weights)
+}
+
+// Distance from p to the closest city already in the tour
+fn distance_to_tour(p: CityNum, ordered_visits: &[CityNum], weights: &[Vec<CityWeight>]) -> CityWeight {
+  let mut closest = fp::INFINITY;
+  for ordered in ordered_visits {
+    if weights[p][*ordered] < closest {
+      closest = weights[p][*ordered];
+    }
+  }
+  closest
+}
+
+fn next_city_num_nearest_not_inserted(ordered_visits: &[CityNum], weights: &[Vec<CityWeight>]) -> CityNum {
+  let mut citynum_to_insert = next_city_num_first_not_inserted(ordered_visits, weights);
+  let mut best_distance = fp::INFINITY;
+  for p in 0..weights.len() {
+    if ordered_visits.contains(&p) { continue; }
+    let distance = distance_to_tour(p, ordered_visits, weights);
+    if distance < best_distance {
+      best_distance = distance;
+      citynum_to_insert = p;
+    }
+  }
+  citynum_to_insert
+}
+
+fn next_city_num_farthest_not_inserted(ordered_visits: &[CityNum], weights: &[Vec<CityWeight>]) -> CityNum {
+  let mut citynum_to_insert = next_city_num_first_not_inserted(ordered_visits, weights);
+  let mut best_distance = fp::NEG_INFINITY;
+  for p in 0..weights.len() {
+    if ordered_visits.contains(&p) { continue; }
+    let distance = distance_to_tour(p, ordered_visits, weights);
+    if distance > best_distance {
+      best_distance = distance;
+      citynum_to_insert = p;
+    }
+  }
+  citynum_to_insert
+}
+
+fn next_city_num_cheapest_not_inserted(ordered_visits: &[CityNum], weights: &[Vec<CityWeight>]) -> CityNum {
+  let mut citynum_to_insert = next_city_num_first_not_inserted(ordered_visits, weights);
+  let mut best_delta = fp::INFINITY;
+  for p in 0..weights.len() {
+    if ordered_visits.contains(&p) { continue; }
+    for i in 0..ordered_visits.len() {
+      let from = ordered_visits[i];
+      let to = ordered_visits[(i + 1) % ordered_visits.len()];
+      let delta = weights[from][p] + weights[p][to] - weights[from][to];
+      if delta < best_delta {
+        best_delta = delta;
+        citynum_to_insert = p;
+      }
+    }
+  }
+  citynum_to_insert
+}
+
+fn best_of(weights: &[Vec<CityWeight>], ordered_visits_a: Vec<CityNum>, ordered_visits_b: Vec<CityNum>) -> Vec<CityNum> {
   let a_len = compute_dist(weights, &ordered_visits_a);
   let b_len = compute_dist(weights, &ordered_visits_b);
   if a_len <= b_len {
@@ -121,7 +510,7 @@ fn best_of(weights: &Vec<Vec<CityWeight>>, ordered_visits_a: Vec<CityNum>, order
 // Takes a mutable ref to ordered visits; does not add anything,
 // merely tries to swap all indexes and keeps swaps which make path shorter.
 // Dumb guesses.
-fn perform_swaps(ordered_visits: &mut Vec<CityNum>, weights: &Vec<Vec<CityWeight>>) {
+fn perform_swaps(ordered_visits: &mut [CityNum], weights: &[Vec<CityWeight>]) {
   for i in 0..ordered_visits.len() {
     let j = (i + 1) % ordered_visits.len();
     // Is swapping the value at i with j better?
@@ -130,17 +519,13 @@ fn perform_swaps(ordered_visits: &mut Vec<CityNum>, weights: &Vec<Vec<CityWeight
     let orig_len = compute_dist(weights, ordered_visits);
 
     // Swap
-    let tmp = ordered_visits[i];
-    ordered_visits[i] = ordered_visits[j];
-    ordered_visits[j] = tmp;
+    ordered_visits.swap(i, j);
 
     let new_len = compute_dist(weights, ordered_visits);
 
     if orig_len < new_len { // If we were better at the beginning (common)
       // Swap back
-      let tmp = ordered_visits[i];
-      ordered_visits[i] = ordered_visits[j];
-      ordered_visits[j] = tmp;
+      ordered_visits.swap(i, j);
     }
     else {
       println!("Found a useful swap! ({} to {})", orig_len, new_len);
@@ -150,15 +535,31 @@ fn perform_swaps(ordered_visits: &mut Vec<CityNum>, weights: &Vec<Vec<CityWeight
   }
 }
 
-// diagnostic which assumes a hamiltonian cycle of 3+ elements passed in, picks next from node_coordinates and inserts it
+// Baseline O( N^2 ) step: picks next from node_coordinates and inserts it at the cheapest edge of ordered_visits,
+// never moving a city that is already in the tour.
 pub fn next_step(
-  ordered_visits: &Vec<CityNum>,
-  node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>,
-  weights: &Vec<Vec<CityWeight>>,
-  next_city_num_fn: &dyn Fn(&Vec<CityNum>, &Vec<Vec<CityWeight>>) -> CityNum) -> Vec<CityNum>
+  ordered_visits: &[CityNum],
+  node_coordinates: &[(CityNum, CityXYCoord, CityXYCoord)],
+  weights: &[Vec<CityWeight>],
+  next_city_num_fn: &NextCityFn<'_>) -> Vec<CityNum>
 {
+  let mut ordered_visits: Vec<CityNum> = ordered_visits.to_vec();
 
-  let mut ordered_visits: Vec<CityNum> = ordered_visits.clone();
+  let citynum_to_insert = next_city_num_fn(&ordered_visits, weights);
+  insert_point_step(&mut ordered_visits, node_coordinates, weights, citynum_to_insert);
+
+  ordered_visits
+}
+
+// diagnostic which assumes a hamiltonian cycle of 3+ elements passed in, picks next from node_coordinates and inserts it
+pub fn next_step_2_deep(
+  ordered_visits: &[CityNum],
+  node_coordinates: &[(CityNum, CityXYCoord, CityXYCoord)],
+  weights: &[Vec<CityWeight>],
+  next_city_num_fn: &NextCityFn<'_>) -> Vec<CityNum>
+{
+
+  let mut ordered_visits: Vec<CityNum> = ordered_visits.to_vec();
 
   // let mut citynum_to_insert = 0;
   // 'outer: for p in 0..weights.len() {
@@ -236,19 +637,19 @@ pub fn next_step(
   // Scan + swap anything that decreases tour
   // perform_swaps(&mut ordered_visits, weights);
 
-  return ordered_visits;
+  ordered_visits
 }
 
 
 // diagnostic which assumes a hamiltonian cycle of 4+ elements passed in, picks next from node_coordinates and inserts it
 pub fn next_step_3_deep(
-  ordered_visits: &Vec<CityNum>,
-  node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>,
-  weights: &Vec<Vec<CityWeight>>,
-  next_city_num_fn: &dyn Fn(&Vec<CityNum>, &Vec<Vec<CityWeight>>) -> CityNum) -> Vec<CityNum>
+  ordered_visits: &[CityNum],
+  node_coordinates: &[(CityNum, CityXYCoord, CityXYCoord)],
+  weights: &[Vec<CityWeight>],
+  next_city_num_fn: &NextCityFn<'_>) -> Vec<CityNum>
 {
 
-  let mut ordered_visits: Vec<CityNum> = ordered_visits.clone();
+  let mut ordered_visits: Vec<CityNum> = ordered_visits.to_vec();
 
   let citynum_to_insert = next_city_num_fn(&ordered_visits, weights);
 
@@ -336,18 +737,18 @@ pub fn next_step_3_deep(
   // Scan + swap anything that decreases tour
   // perform_swaps(&mut ordered_visits, weights);
 
-  return ordered_visits;
+  ordered_visits
 }
 
 
 pub fn next_step_4_deep(
-  ordered_visits: &Vec<CityNum>,
-  node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>,
-  weights: &Vec<Vec<CityWeight>>,
-  next_city_num_fn: &dyn Fn(&Vec<CityNum>, &Vec<Vec<CityWeight>>) -> CityNum) -> Vec<CityNum>
+  ordered_visits: &[CityNum],
+  node_coordinates: &[(CityNum, CityXYCoord, CityXYCoord)],
+  weights: &[Vec<CityWeight>],
+  next_city_num_fn: &NextCityFn<'_>) -> Vec<CityNum>
 {
 
-  let mut ordered_visits: Vec<CityNum> = ordered_visits.clone();
+  let mut ordered_visits: Vec<CityNum> = ordered_visits.to_vec();
 
   let citynum_to_insert = next_city_num_fn(&ordered_visits, weights);
 
@@ -457,19 +858,19 @@ pub fn next_step_4_deep(
   // Scan + swap anything that decreases tour
   // perform_swaps(&mut ordered_visits, weights);
 
-  return ordered_visits;
+  ordered_visits
 }
 
 
 
 pub fn next_step_5_deep(
-  ordered_visits: &Vec<CityNum>,
-  node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>,
-  weights: &Vec<Vec<CityWeight>>,
-  next_city_num_fn: &dyn Fn(&Vec<CityNum>, &Vec<Vec<CityWeight>>) -> CityNum) -> Vec<CityNum>
+  ordered_visits: &[CityNum],
+  node_coordinates: &[(CityNum, CityXYCoord, CityXYCoord)],
+  weights: &[Vec<CityWeight>],
+  next_city_num_fn: &NextCityFn<'_>) -> Vec<CityNum>
 {
 
-  let mut ordered_visits: Vec<CityNum> = ordered_visits.clone();
+  let mut ordered_visits: Vec<CityNum> = ordered_visits.to_vec();
 
   let citynum_to_insert = next_city_num_fn(&ordered_visits, weights);
 
@@ -599,7 +1000,7 @@ pub fn next_step_5_deep(
   // Scan + swap anything that decreases tour
   // perform_swaps(&mut ordered_visits, weights);
 
-  return ordered_visits;
+  ordered_visits
 }
 
 
@@ -607,160 +1008,156 @@ pub fn next_step_5_deep(
 
 
 
+// Generalizes next_step (num_deep = 0), next_step_2_deep and next_step_3_deep .. next_step_5_deep: every ordered combination
+// of num_deep cities is removed, citynum_to_insert is inserted, the removed cities are re-inserted
+// last-removed-first and the combination growing the tour least is applied.
+// Runtime is O(N^(num_deep+1)); num_deep is clamped to len-1 so at least one city stays in the tour,
+// and num_deep = 0 is plain cheapest insertion (next_step).
 pub fn next_step_n_deep(
-  ordered_visits: &mut Vec<CityNum>,
+  ordered_visits: &[CityNum],
   node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>,
   weights: &Vec<Vec<CityWeight>>,
+  num_deep: usize,
+  next_city_num_fn: &NextCityFn<'_>) -> Vec<CityNum>
+{
+  next_step_n_deep_choice(ordered_visits, node_coordinates, weights, num_deep, next_city_num_fn).0
+}
 
-  citynum_to_insert: CityNum,
-
-  num_steps: usize,
+/// What next_step_n_deep decided on, for the step trace.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepChoice {
+  pub citynum_to_insert: CityNum,
+  /// Indexes removed one after the other, (n, m, l) in next_step_3_deep
+  pub removal_idxs: Vec<usize>,
+  pub removed_citynums: Vec<CityNum>,
+  /// Change in tour length, removals and re-insertions included
+  pub delta: fp,
+}
 
-  mut this_delta: fp, // tracks per-recursive-call deltas as edges are inserted/removed
-  indicies_and_citynums_removed_so_far: &mut Vec<(usize, CityNum)>,
-) -> fp
+// next_step_n_deep that also returns the choice it made
+pub fn next_step_n_deep_choice(
+  ordered_visits: &[CityNum],
+  node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>,
+  weights: &Vec<Vec<CityWeight>>,
+  num_deep: usize,
+  next_city_num_fn: &NextCityFn<'_>) -> (Vec<CityNum>, StepChoice)
 {
-  let mut best_tour_delta = fp::INFINITY;
-  let mut best_tour_n = 0;
+  let mut ordered_visits: Vec<CityNum> = ordered_visits.to_vec();
 
-  let is_final_iter = num_steps < 1;
+  let citynum_to_insert = next_city_num_fn(&ordered_visits, weights);
 
-  let begin_ordered_visits_len = ordered_visits.len();
+  let num_deep = num_deep.min(ordered_visits.len().saturating_sub(1));
+  if num_deep < 1 {
+    let delta = insert_point_step(&mut ordered_visits, node_coordinates, weights, citynum_to_insert);
+    return (ordered_visits, StepChoice { citynum_to_insert, removal_idxs: vec![], removed_citynums: vec![], delta });
+  }
 
-  println!("next_step_n_deep ordered_visits={:?} indicies_and_citynums_removed_so_far={:?} is_final_iter={}", ordered_visits, indicies_and_citynums_removed_so_far, is_final_iter);
+  let mut search = DeepSearch {
+    removed_so_far: Vec::with_capacity(num_deep),
+    best_tour_delta: fp::INFINITY,
+    best_tour_removal_idxs: vec![0; num_deep],
+  };
+  next_step_n_deep_search(&mut ordered_visits, node_coordinates, weights, citynum_to_insert, num_deep, 0.0, &mut search);
+
+  // Apply the best removals to insert citynum_to_insert
+  let mut removed_citynums = vec![];
+  for removal_idx in search.best_tour_removal_idxs.iter() {
+    removed_citynums.push( ordered_visits.remove(*removal_idx) );
+  }
+  insert_point_step(&mut ordered_visits, node_coordinates, weights, citynum_to_insert);
+  for removed_citynum in removed_citynums.iter().rev() {
+    insert_point_step(&mut ordered_visits, node_coordinates, weights, *removed_citynum);
+  }
+
+  (ordered_visits, StepChoice {
+    citynum_to_insert,
+    removal_idxs: search.best_tour_removal_idxs,
+    removed_citynums,
+    delta: search.best_tour_delta,
+  })
+}
 
-  if !is_final_iter {
-    indicies_and_citynums_removed_so_far.push( (0, ordered_visits[0]) ); // last item will track value of N in the following loop
+// Delta of inserting citynum_to_insert into every edge of ordered_visits, as insert_point_step weighs them
+pub fn insertion_deltas(ordered_visits: &[CityNum], weights: &[Vec<CityWeight>], citynum_to_insert: CityNum) -> Vec<(CityNum, CityNum, CityWeight)> {
+  let mut deltas = vec![];
+  for from_i in 0..ordered_visits.len() {
+    let from_elm = ordered_visits[from_i];
+    let to_elm = ordered_visits[(from_i+1) % ordered_visits.len()];
+    deltas.push((
+      from_elm,
+      to_elm,
+      (-weights[from_elm][to_elm]) + weights[from_elm][citynum_to_insert] + weights[citynum_to_insert][to_elm]
+    ));
   }
+  deltas
+}
 
-  let indicies_removed_i = indicies_and_citynums_removed_so_far.len() - 1;
+// What the levels of next_step_n_deep_search share
+struct DeepSearch {
+  // (index, citynum) removed by the enclosing levels
+  removed_so_far: Vec<(usize, CityNum)>,
+  best_tour_delta: fp,
+  best_tour_removal_idxs: Vec<usize>,
+}
 
+// One loop level of next_step_n_deep.
+// ordered_visits is identical to how it was passed in when this returns.
+fn next_step_n_deep_search(
+  ordered_visits: &mut Vec<CityNum>,
+  node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>,
+  weights: &Vec<Vec<CityWeight>>,
+  citynum_to_insert: CityNum,
+  num_deep_remaining: usize,
+  this_delta: fp, // removal deltas of the enclosing levels
+  search: &mut DeepSearch)
+{
   for n in 0..ordered_visits.len() {
     let removed_citynum_n = ordered_visits.remove(n);
 
-    println!("for n ... ordered_visits={:?}", ordered_visits);
-
-    if !is_final_iter {
-      indicies_and_citynums_removed_so_far[ indicies_removed_i ] = (n, removed_citynum_n);
-    }
-
     let n_left_citynum = ordered_visits[ (n + ordered_visits.len() - 1) % ordered_visits.len() ];
     let n_right_citynum = ordered_visits[ (n) % ordered_visits.len() ];
 
     // Delta must begin with the removal of 2 edges above
+    let mut this_delta: fp = this_delta;
     this_delta += (-weights[n_left_citynum][removed_citynum_n]) + (-weights[removed_citynum_n][n_right_citynum]) + weights[n_left_citynum][n_right_citynum];
 
-    if is_final_iter {
+    search.removed_so_far.push( (n, removed_citynum_n) );
 
-      //println!("PRE insert_point_step({:?} citynum_to_insert={})", ordered_visits, citynum_to_insert);
+    if num_deep_remaining > 1 {
+      next_step_n_deep_search(ordered_visits, node_coordinates, weights, citynum_to_insert, num_deep_remaining - 1, this_delta, search);
+    }
+    else {
       this_delta += insert_point_step(ordered_visits, node_coordinates, weights, citynum_to_insert);
-      //println!("POST insert_point_step({:?} citynum_to_insert={})", ordered_visits, citynum_to_insert);
-      //println!("PRE insert_all_point_steps({:?} indicies_and_citynums_removed_so_far={:?})", ordered_visits, indicies_and_citynums_removed_so_far);
-      this_delta += insert_all_point_steps(ordered_visits, node_coordinates, weights, &indicies_and_citynums_removed_so_far);
-      //println!("POST insert_all_point_steps({:?} indicies_and_citynums_removed_so_far={:?})", ordered_visits, indicies_and_citynums_removed_so_far);
+      for (_removed_idx, removed_citynum) in search.removed_so_far.iter().rev() { // last removed goes back first
+        this_delta += insert_point_step(ordered_visits, node_coordinates, weights, *removed_citynum);
+      }
 
-      if this_delta < best_tour_delta {
+      if this_delta < search.best_tour_delta {
         // Keep changes, update best_tour_delta
-        best_tour_delta = this_delta;
-        best_tour_n = n;
+        search.best_tour_delta = this_delta;
+        for (i, (removed_idx, _removed_citynum)) in search.removed_so_far.iter().enumerate() {
+          search.best_tour_removal_idxs[i] = *removed_idx;
+        }
       }
 
-      // Undo changes so ordered_visits is identical to the beginning
-      //println!("PRE remove_all_point_steps({:?} indicies_and_citynums_removed_so_far={:?})", ordered_visits, indicies_and_citynums_removed_so_far);
-      this_delta += remove_all_point_steps(ordered_visits, node_coordinates, weights, &indicies_and_citynums_removed_so_far);
-      //println!("POST remove_all_point_steps({:?} indicies_and_citynums_removed_so_far={:?})", ordered_visits, indicies_and_citynums_removed_so_far);
-      //println!("PRE remove_point_step({:?} citynum_to_insert={})", ordered_visits, citynum_to_insert);
-      this_delta += remove_point_step(ordered_visits, node_coordinates, weights, citynum_to_insert);
-      //println!("POST remove_point_step({:?} citynum_to_insert={})", ordered_visits, citynum_to_insert);
-
-    }
-    else {
-      // Go one step lower, return value is meaningless here
-      // if begin_ordered_visits_len-1 != ordered_visits.len() {
-      //   println!("ordered_visits={:?}", ordered_visits);
-      //   panic!("LOOP before next_step_n_deep: begin_ordered_visits_len={} and ordered_visits.len()={}, refusing to continue!", begin_ordered_visits_len, ordered_visits.len() );
-      // }
-      next_step_n_deep(
-        ordered_visits,
-        node_coordinates,
-        weights,
-        citynum_to_insert,
-        num_steps - 1,
-        this_delta,
-        indicies_and_citynums_removed_so_far
-      );
+      // Undo changes so ordered_visits is identical to the beginning of this level
+      for (_removed_idx, removed_citynum) in search.removed_so_far.iter() {
+        remove_point_step(ordered_visits, node_coordinates, weights, *removed_citynum);
+      }
+      remove_point_step(ordered_visits, node_coordinates, weights, citynum_to_insert);
     }
 
+    search.removed_so_far.pop();
     ordered_visits.insert(n, removed_citynum_n);
-
-    // if begin_ordered_visits_len != ordered_visits.len() {
-    //   println!("ordered_visits={:?}", ordered_visits);
-    //   panic!("LOOP: begin_ordered_visits_len={} and ordered_visits.len()={}, refusing to continue!", begin_ordered_visits_len, ordered_visits.len() );
-    // }
-
   }
-
-  // if begin_ordered_visits_len != ordered_visits.len() {
-  //   println!("ordered_visits={:?}", ordered_visits);
-  //   panic!("begin_ordered_visits_len={} and ordered_visits.len()={}, refusing to continue!", begin_ordered_visits_len, ordered_visits.len() );
-  // }
-
-  if is_final_iter {
-    // Actually do the change for all indicies_and_citynums_removed_so_far
-
-    indicies_and_citynums_removed_so_far.push( (best_tour_n, ordered_visits[best_tour_n]) );
-    println!("before insert_point_step ordered_visits={:?}", ordered_visits);
-
-    insert_point_step(ordered_visits, node_coordinates, weights, citynum_to_insert);
-
-    println!("after insert_point_step ordered_visits={:?}", ordered_visits);
-
-    // insert_all_point_steps(ordered_visits, node_coordinates, weights, &indicies_and_citynums_removed_so_far);
-  }
-
-  // if begin_ordered_visits_len+1 != ordered_visits.len() {
-  //   println!("END ordered_visits={:?}", ordered_visits);
-  //   panic!("after final insert: begin_ordered_visits_len={} and ordered_visits.len()={}, refusing to continue!", begin_ordered_visits_len, ordered_visits.len() );
-  // }
-
-  return best_tour_delta;
-}
-
-pub fn insert_all_point_steps(
-  ordered_visits: &mut Vec<CityNum>,
-  node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>,
-  weights: &Vec<Vec<CityWeight>>,
-  indicies_and_citynums_removed_so_far: &Vec<(usize, CityNum)>) -> CityWeight
-{
-  let mut this_delta = 0.0;
-  for (removed_city_idx, removed_city_num) in indicies_and_citynums_removed_so_far.iter() /*.iter().rev()*/ { // must iterate in reverse order!
-    this_delta += insert_point_step(ordered_visits, node_coordinates, weights, *removed_city_num );
-  }
-  this_delta
-}
-
-pub fn remove_all_point_steps(
-  ordered_visits: &mut Vec<CityNum>,
-  node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>,
-  weights: &Vec<Vec<CityWeight>>,
-  indicies_and_citynums_removed_so_far: &Vec<(usize, CityNum)>) -> CityWeight
-{
-  let mut this_delta = 0.0;
-  for (removed_city_idx, removed_city_num) in indicies_and_citynums_removed_so_far.iter() { // iterates in reverse of insert point steps
-    this_delta += remove_point_step(ordered_visits, node_coordinates, weights, *removed_city_num );
-  }
-  this_delta
 }
 
-
-
-
 // Modified args instead of returning a clone
 // returns the delta from this modification (aka how much did len(ordered_visits) change, smaller is better.)
 fn insert_point_step(
   ordered_visits: &mut Vec<CityNum>,
-  node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>,
-  weights: &Vec<Vec<CityWeight>>,
+  node_coordinates: &[(CityNum, CityXYCoord, CityXYCoord)],
+  weights: &[Vec<CityWeight>],
   citynum_to_insert: CityNum) -> CityWeight
 {
   let mut ideal_insert_dist_delta: CityWeight = fp::INFINITY;
@@ -789,14 +1186,39 @@ fn insert_point_step(
   ordered_visits.insert(ins_idx1, citynum_to_insert);
   // ins_idx1 points to the newly inserted city
 
-  return ideal_insert_dist_delta;
+  ideal_insert_dist_delta
+}
+
+/// insert_point_step restricted to positions is_feasible accepts (e.g. ones that keep every time window):
+/// edges are tried cheapest first and the city goes into the first one that passes. ordered_visits[0]
+/// never moves, inserting into the closing edge appends. Returns the delta, or None with ordered_visits
+/// unchanged if no position is feasible.
+pub fn insert_point_step_feasible(
+  ordered_visits: &mut Vec<CityNum>,
+  weights: &[Vec<CityWeight>],
+  citynum_to_insert: CityNum,
+  is_feasible: &dyn Fn(&Vec<CityNum>) -> bool) -> Option<CityWeight>
+{
+  let mut deltas: Vec<(usize, CityWeight)> = insertion_deltas(ordered_visits, weights, citynum_to_insert).iter().enumerate()
+    .map(|(from_i, (_from, _to, delta))| (from_i, *delta))
+    .collect();
+  deltas.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+
+  for (from_i, delta) in deltas {
+    ordered_visits.insert(from_i + 1, citynum_to_insert);
+    if is_feasible(ordered_visits) {
+      return Some(delta);
+    }
+    ordered_visits.remove(from_i + 1);
+  }
+  None
 }
 
 // undoes insert_point_step given the same citynum_to_insert and returns the delta
 fn remove_point_step(
   ordered_visits: &mut Vec<CityNum>,
-  node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>,
-  weights: &Vec<Vec<CityWeight>>,
+  node_coordinates: &[(CityNum, CityXYCoord, CityXYCoord)],
+  weights: &[Vec<CityWeight>],
   citynum_to_insert: CityNum) -> CityWeight
 {
   let from_i = ordered_visits.iter().position(|&val| val == citynum_to_insert).unwrap();
@@ -814,18 +1236,17 @@ fn remove_point_step(
       (-weights[from_elm][citynum_to_insert]) + // removed edge from -> new
       (-weights[citynum_to_insert][to_elm]);    // removed edge new -> end
 
-  return this_dist_delta;
+  this_dist_delta
 }
 
-fn compute_largest_triangle(node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>, weights: &Vec<Vec<fp>>) -> Vec<usize> {
+fn compute_largest_triangle(node_coordinates: &[(CityNum, CityXYCoord, CityXYCoord)], weights: &[Vec<fp>]) -> Vec<usize> {
   let mut ordered_visits: Vec<usize> = vec![0, 1, 2]; // holds the path as a vector of indexes relating to the city number beginning at 0
 
   // Make the first 2 points the furthest away in the entire graph
-  for r in 0..weights.len() {
-    for c in 0..weights.len() {
+  for (r, row) in weights.iter().enumerate() {
+    for (c, &this_largest_w) in row.iter().enumerate() {
       if r == c { continue; }
       let best_largest_w = weights[ordered_visits[0]][ordered_visits[1]];
-      let this_largest_w    = weights[r][c];
       if this_largest_w > best_largest_w {
         ordered_visits[0] = r;
         ordered_visits[1] = c;
@@ -841,27 +1262,26 @@ fn compute_largest_triangle(node_coordinates: &Vec<(CityNum, CityXYCoord, CityXY
   // Given the longest edge, find
   // weight(0, 2) + weight(1, 2) (weights of both edges going to "2")
   let mut current_longest_point_len = weights[ordered_visits[0]][ordered_visits[2]] + weights[ordered_visits[1]][ordered_visits[2]];
-  for r in 0..weights.len() {
+  for (r, (to_0, to_1)) in weights[ordered_visits[0]].iter().zip(weights[ordered_visits[1]].iter()).enumerate() {
     if r == ordered_visits[0] || r == ordered_visits[1] { continue; }
-    let this_len = weights[ordered_visits[0]][r] + weights[ordered_visits[1]][r];
+    let this_len = to_0 + to_1;
     if this_len > current_longest_point_len {
       ordered_visits[2] = r;
       current_longest_point_len = this_len;
     }
   }
 
-  return ordered_visits;
+  ordered_visits
 }
 
-fn compute_smallest_triangle(node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>, weights: &Vec<Vec<fp>>) -> Vec<usize> {
+fn compute_smallest_triangle(node_coordinates: &[(CityNum, CityXYCoord, CityXYCoord)], weights: &[Vec<fp>]) -> Vec<usize> {
   let mut ordered_visits: Vec<usize> = vec![0, 1, 2]; // holds the path as a vector of indexes relating to the city number beginning at 0
 
   // Make the first 2 points the closest away in the entire graph
-  for r in 0..weights.len() {
-    for c in 0..weights.len() {
+  for (r, row) in weights.iter().enumerate() {
+    for (c, &this_smallest_w) in row.iter().enumerate() {
       if r == c { continue; }
       let best_smallest_w = weights[ordered_visits[0]][ordered_visits[1]];
-      let this_smallest_w    = weights[r][c];
       if this_smallest_w < best_smallest_w {
         ordered_visits[0] = r;
         ordered_visits[1] = c;
@@ -877,21 +1297,21 @@ fn compute_smallest_triangle(node_coordinates: &Vec<(CityNum, CityXYCoord, CityX
   // Given the shortest edge, find
   // weight(0, 2) + weight(1, 2) (weights of both edges going to "2")
   let mut current_shortest_point_len = weights[ordered_visits[0]][ordered_visits[2]] + weights[ordered_visits[1]][ordered_visits[2]];
-  for r in 0..weights.len() {
+  for (r, (to_0, to_1)) in weights[ordered_visits[0]].iter().zip(weights[ordered_visits[1]].iter()).enumerate() {
     if r == ordered_visits[0] || r == ordered_visits[1] { continue; }
-    let this_len = weights[ordered_visits[0]][r] + weights[ordered_visits[1]][r];
+    let this_len = to_0 + to_1;
     if this_len < current_shortest_point_len {
       ordered_visits[2] = r;
       current_shortest_point_len = this_len;
     }
   }
 
-  return ordered_visits;
+  ordered_visits
 }
 
 
 // Mutates path between from_i and to_i inclusive, reversing the items between from_i and to_i.
-fn reverse_slice(path: &mut Vec<usize>, from_i: usize, to_i: usize) {
+fn reverse_slice(path: &mut [usize], from_i: usize, to_i: usize) {
   //let p_len = path.len();
   if from_i < to_i {
     // Simple case; reverse w/o overlapping
@@ -944,4 +1364,68 @@ mod tests {
         assert_eq!(data, vec![2,1,3,4,5,6]);
 
     }
+
+    #[test]
+    fn test_next_step_n_deep_matches_unrolled() {
+        for seed in 0..20 {
+            let mut rng = fastrand::Rng::with_seed(seed);
+            let node_coordinates: Vec<(CityNum, CityXYCoord, CityXYCoord)> = (0..10).map(|i| (i, rng.f32() * 15.0, rng.f32() * 15.0) ).collect();
+            let weights = compute_weight_coords(&node_coordinates);
+            let next_fn = next_city_num_first_not_inserted;
+
+            let mut ordered_visits = compute_largest_triangle(&node_coordinates, &weights);
+            while ordered_visits.len() < 8 {
+                assert_eq!(next_step_n_deep(&ordered_visits, &node_coordinates, &weights, 0, &next_fn), next_step(&ordered_visits, &node_coordinates, &weights, &next_fn));
+                assert_eq!(next_step_n_deep(&ordered_visits, &node_coordinates, &weights, 2, &next_fn), next_step_2_deep(&ordered_visits, &node_coordinates, &weights, &next_fn));
+                if ordered_visits.len() > 3 {
+                    assert_eq!(next_step_n_deep(&ordered_visits, &node_coordinates, &weights, 3, &next_fn), next_step_3_deep(&ordered_visits, &node_coordinates, &weights, &next_fn));
+                }
+                if ordered_visits.len() > 4 {
+                    assert_eq!(next_step_n_deep(&ordered_visits, &node_coordinates, &weights, 4, &next_fn), next_step_4_deep(&ordered_visits, &node_coordinates, &weights, &next_fn));
+                }
+                if ordered_visits.len() > 5 {
+                    assert_eq!(next_step_n_deep(&ordered_visits, &node_coordinates, &weights, 5, &next_fn), next_step_5_deep(&ordered_visits, &node_coordinates, &weights, &next_fn));
+                }
+                ordered_visits = next_step_n_deep(&ordered_visits, &node_coordinates, &weights, 1, &next_fn);
+            }
+        }
+    }
+
+    #[test]
+    fn test_solve_fast_visits_every_city() {
+        let mut rng = fastrand::Rng::with_seed(3);
+        let node_coordinates: Vec<(CityNum, CityXYCoord, CityXYCoord)> = (0..2000).map(|i| (i, rng.f32() * 1000.0, rng.f32() * 1000.0) ).collect();
+        for insertion_order in InsertionOrder::all() {
+            let mut tour = solve_fast(&node_coordinates, &SolveOptions { insertion_order, ..Default::default() });
+            tour.sort();
+            assert_eq!(tour, (0..node_coordinates.len()).collect::<Vec<CityNum>>(), "{}", insertion_order.name());
+        }
+    }
+
+    #[test]
+    fn test_solve_fast_visits_every_city_around_a_nan_city() {
+        let node_coordinates: Vec<(CityNum, CityXYCoord, CityXYCoord)> = vec![(0, 0.0, 0.0), (1, 10.0, 0.0), (2, 10.0, 10.0), (3, 0.0, 10.0), (4, fp::NAN, 3.0), (5, 5.0, 5.0)];
+        let mut tour = solve_fast(&node_coordinates, &SolveOptions::default());
+        tour.sort();
+        assert_eq!(tour, (0..node_coordinates.len()).collect::<Vec<CityNum>>());
+    }
+
+    #[test]
+    fn test_next_step_inserts_one_city() {
+        let node_coordinates: Vec<(CityNum, CityXYCoord, CityXYCoord)> = vec![(0, 0.0, 0.0), (1, 10.0, 0.0), (2, 10.0, 10.0), (3, 0.0, 10.0), (4, 5.0, -1.0)];
+        let weights = compute_weight_coords(&node_coordinates);
+        let ordered_visits = vec![0, 1, 2, 3];
+        let next = next_step(&ordered_visits, &node_coordinates, &weights, &next_city_num_first_not_inserted);
+        assert_eq!(next, vec![0, 4, 1, 2, 3]);
+    }
+
+    #[test]
+    fn random_insertion_order_is_seeded() {
+        let node_coordinates: Vec<(CityNum, CityXYCoord, CityXYCoord)> = (0..12).map(|i| (i, i as fp, 0.0)).collect();
+        let order = InsertionOrder::from_name("random:7").unwrap();
+        assert_eq!(order, InsertionOrder::Random(7));
+        assert_eq!(InsertionOrder::from_name(&order.label()), Some(order));
+        assert_eq!(order.fixed_priority(&node_coordinates), order.fixed_priority(&node_coordinates));
+        assert_ne!(order.fixed_priority(&node_coordinates), InsertionOrder::Random(8).fixed_priority(&node_coordinates));
+    }
 }
@@ -0,0 +1,219 @@
+/*
+ *  tsp-sol - an experimental environment for traveling salesman solution analysis
+ *  Copyright (C) 2023  Jeffrey McAteer <jeffrey@jmcateer.com>
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; version 2 of the License ONLY.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License along
+ *  with this program; if not, write to the Free Software Foundation, Inc.,
+ *  51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+ */
+
+use super::*;
+
+use serde::{Serialize, Deserialize};
+
+/// How progress through a keyframe segment is mapped onto movement.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum Easing {
+  #[default]
+  Linear,
+  EaseIn,
+  EaseOut,
+  EaseInOut,
+  /// Hold the previous arrangement for the whole segment and jump on its last frame.
+  Step,
+}
+
+impl Easing {
+  pub fn apply(&self, t: fp) -> fp {
+    let t = t.clamp(0.0, 1.0);
+    match self {
+      Easing::Linear => t,
+      Easing::EaseIn => t * t,
+      Easing::EaseOut => 1.0 - ((1.0 - t) * (1.0 - t)),
+      Easing::EaseInOut => t * t * (3.0 - (2.0 * t)),
+      Easing::Step => if t >= 1.0 { 1.0 } else { 0.0 },
+    }
+  }
+}
+
+/// A non-straight route one city takes on its way to a keyframe.
+/// Both kinds start at the city's previous position and end exactly on its keyframe position.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "path", rename_all = "kebab-case")]
+pub enum CityPath {
+  /// Swing around (center_x, center_y), blending the radius between start and end;
+  /// turns adds whole extra revolutions (negative turns go clockwise).
+  Orbit {
+    city: usize,
+    center_x: fp,
+    center_y: fp,
+    #[serde(default)]
+    turns: i32,
+  },
+  /// Quadratic bezier bending towards the control point (control_x, control_y).
+  Curve {
+    city: usize,
+    control_x: fp,
+    control_y: fp,
+  },
+}
+
+impl CityPath {
+  pub fn city(&self) -> usize {
+    match self {
+      CityPath::Orbit { city, .. } => *city,
+      CityPath::Curve { city, .. } => *city,
+    }
+  }
+
+  fn position(&self, from: (fp, fp), to: (fp, fp), t: fp) -> (fp, fp) {
+    match self {
+      CityPath::Orbit { center_x, center_y, turns, .. } => {
+        let (from_dx, from_dy) = (from.0 - center_x, from.1 - center_y);
+        let (to_dx, to_dy) = (to.0 - center_x, to.1 - center_y);
+        let from_angle = from_dy.atan2(from_dx);
+        let mut to_angle = to_dy.atan2(to_dx);
+        // Take the short way round, then add any requested full revolutions
+        while to_angle - from_angle > std::f32::consts::PI {
+          to_angle -= std::f32::consts::TAU;
+        }
+        while to_angle - from_angle < -std::f32::consts::PI {
+          to_angle += std::f32::consts::TAU;
+        }
+        to_angle += (*turns as fp) * std::f32::consts::TAU;
+
+        let from_radius = (from_dx * from_dx + from_dy * from_dy).sqrt();
+        let to_radius = (to_dx * to_dx + to_dy * to_dy).sqrt();
+        let radius = from_radius + ((to_radius - from_radius) * t);
+        let angle = from_angle + ((to_angle - from_angle) * t);
+        (center_x + (radius * angle.cos()), center_y + (radius * angle.sin()))
+      }
+      CityPath::Curve { control_x, control_y, .. } => {
+        let u = 1.0 - t;
+        (
+          (u * u * from.0) + (2.0 * u * t * control_x) + (t * t * to.0),
+          (u * u * from.1) + (2.0 * u * t * control_y) + (t * t * to.1),
+        )
+      }
+    }
+  }
+}
+
+/// Moves every city at once: scale and rotate about origin (default: the centroid), then translate.
+/// Multi-pattern-scan grows each parameter from identity to its full value across the segment.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct CityTransform {
+  #[serde(default = "default_scale")]
+  pub scale: fp,
+  #[serde(default)]
+  pub rotate_degrees: fp,
+  #[serde(default)]
+  pub translate_x: fp,
+  #[serde(default)]
+  pub translate_y: fp,
+  #[serde(default)]
+  pub origin_x: Option<fp>,
+  #[serde(default)]
+  pub origin_y: Option<fp>,
+}
+
+fn default_scale() -> fp {
+  1.0
+}
+
+impl CityTransform {
+  /// Applies the fraction t of this transform to each city of the given arrangement.
+  pub fn apply(&self, cities: &[(CityNum, CityXYCoord, CityXYCoord)], t: fp) -> Vec<(CityNum, CityXYCoord, CityXYCoord)> {
+    let num = cities.len().max(1) as fp;
+    let centroid_x = cities.iter().map(|c| c.1).sum::<fp>() / num;
+    let centroid_y = cities.iter().map(|c| c.2).sum::<fp>() / num;
+    let origin_x = self.origin_x.unwrap_or(centroid_x);
+    let origin_y = self.origin_y.unwrap_or(centroid_y);
+
+    let scale = 1.0 + ((self.scale - 1.0) * t);
+    let (sin, cos) = (self.rotate_degrees * t).to_radians().sin_cos();
+
+    cities.iter().map(|(i, x, y)| {
+      let (dx, dy) = ((x - origin_x) * scale, (y - origin_y) * scale);
+      (
+        *i,
+        origin_x + (dx * cos) - (dy * sin) + (self.translate_x * t),
+        origin_y + (dx * sin) + (dy * cos) + (self.translate_y * t),
+      )
+    }).collect()
+  }
+}
+
+/// City positions a fraction t (already eased) of the way from `from` to `to`;
+/// cities with an entry in paths follow it, every other city moves in a straight line.
+pub fn interpolate_cities(
+  from: &[(CityNum, CityXYCoord, CityXYCoord)],
+  to: &[(CityNum, CityXYCoord, CityXYCoord)],
+  paths: &[CityPath],
+  t: fp
+) -> Vec<(CityNum, CityXYCoord, CityXYCoord)> {
+  let mut frame = vec![];
+  for i in 0..from.len() {
+    let a = (from[i].1, from[i].2);
+    let b = (to[i].1, to[i].2);
+    let (x, y) = match paths.iter().find(|p| p.city() == i) {
+      Some(path) => path.position(a, b, t),
+      None => (a.0 + ((b.0 - a.0) * t), a.1 + ((b.1 - a.1) * t)),
+    };
+    frame.push((from[i].0, x, y));
+  }
+  frame
+}
+
+/// Rounds interpolated coordinates so that frames land on reproducible positions.
+pub fn round_coordinates(cities: Vec<(CityNum, CityXYCoord, CityXYCoord)>) -> Vec<(CityNum, CityXYCoord, CityXYCoord)> {
+  #[allow(non_upper_case_globals)]
+  const rounded_decimals: fp = 1000.0;
+  cities.into_iter().map(|(i, x, y)| {
+    (i, (x * rounded_decimals).round() / rounded_decimals, (y * rounded_decimals).round() / rounded_decimals)
+  }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn easings_keep_endpoints() {
+    for easing in [Easing::Linear, Easing::EaseIn, Easing::EaseOut, Easing::EaseInOut, Easing::Step] {
+      assert_eq!(easing.apply(0.0), 0.0);
+      assert_eq!(easing.apply(1.0), 1.0);
+    }
+    assert_eq!(Easing::Step.apply(0.99), 0.0);
+  }
+
+  #[test]
+  fn paths_end_on_keyframe() {
+    let from = vec![(0, 4.0, 7.5), (1, 10.0, 7.5)];
+    let to = vec![(0, 7.5, 4.0), (1, 10.0, 10.0)];
+    let paths = vec![
+      CityPath::Orbit { city: 0, center_x: 7.5, center_y: 7.5, turns: 1 },
+      CityPath::Curve { city: 1, control_x: 14.0, control_y: 9.0 },
+    ];
+    let start = interpolate_cities(&from, &to, &paths, 0.0);
+    let end = interpolate_cities(&from, &to, &paths, 1.0);
+    for i in 0..2 {
+      assert!((start[i].1 - from[i].1).abs() < fp_epsilon && (start[i].2 - from[i].2).abs() < fp_epsilon);
+      assert!((end[i].1 - to[i].1).abs() < fp_epsilon && (end[i].2 - to[i].2).abs() < fp_epsilon);
+    }
+    // Half way round the orbit stays on the circle
+    let mid = interpolate_cities(&from, &to, &paths, 0.5);
+    let radius = ((mid[0].1 - 7.5).powi(2) + (mid[0].2 - 7.5).powi(2)).sqrt();
+    assert!((radius - 3.5).abs() < fp_epsilon);
+  }
+}
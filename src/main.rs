@@ -3,7 +3,7 @@
 #![allow(unused_variables)]
 #![allow(dead_code)]
 
-/**
+/*
  *  tsp-sol - an experimental environment for traveling salesman solution analysis
  *  Copyright (C) 2023  Jeffrey McAteer <jeffrey@jmcateer.com>
  *
@@ -21,7 +21,7 @@
  *  51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
  */
 
-use tsplib::{NodeCoord};
+use tsplib::{EdgeWeight, NodeCoord};
 
 use image::{RgbImage, Rgb, GenericImage};
 
@@ -31,10 +31,8 @@ use rusttype::{Font, Scale};
 
 use rand::prelude::*;
 
-use permutohedron;
 
 use threadpool::ThreadPool;
-use num_cpus;
 
 use once_cell::sync::Lazy;
 
@@ -45,6 +43,7 @@ use std::fs::{File,create_dir};
 use std::path::Path;
 use std::io::{BufReader,Write};
 use std::sync::{Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
 use std::env;
@@ -52,8 +51,37 @@ use std::env;
 use std::f32;
 use std::f64;
 
+// brute_algo.rs is the original solver, kept as written rather than restyled for clippy
+#[allow(clippy::needless_return, clippy::ptr_arg, clippy::needless_borrow, clippy::single_match, clippy::explicit_counter_loop, clippy::type_complexity, clippy::mut_from_ref, clippy::absurd_extreme_comparisons, clippy::empty_line_after_doc_comments)]
 mod brute_algo;
 mod jeff_algo;
+mod viewer;
+mod scenario;
+mod keyframes;
+mod animation;
+mod shrink;
+mod geometry;
+mod spatial;
+mod trace;
+mod stepcheck;
+mod generators;
+mod open_path;
+mod vrp;
+mod time_windows;
+mod orienteering;
+mod metric;
+mod importers;
+mod exporters;
+mod report;
+mod cli;
+mod output;
+#[cfg(test)]
+mod property_tests;
+
+use scenario::{Scenario, ScenarioSolver};
+use jeff_algo::{InsertionOrder, InitialTour, SolveOptions};
+use generators::Family;
+use metric::Metric;
 
 #[allow(non_camel_case_types)]
 //pub type fp = f64;
@@ -63,6 +91,8 @@ pub type fp = f32;
 pub type CityNum = usize;
 pub type CityWeight = fp;
 pub type CityXYCoord = fp;
+// One city of node_coordinates: (city number, x, y)
+pub type City = (CityNum, CityXYCoord, CityXYCoord);
 
 // fp numbers within this distance are considered equal
 #[allow(non_upper_case_globals)]
@@ -86,167 +116,45 @@ pub const y_min: fp = 3.0;
 #[allow(non_upper_case_globals)]
 pub const y_max: fp = 12.0;
 
+// .tsp files with more cities than this are solved with jeff_algo::solve_fast;
+// 5000 cities already need a 100MB weights matrix and hours of next_step_3_deep
+const FAST_SOLVE_MIN_CITIES: usize = 5000;
 
-pub const HTML_BEGIN: &'static str = r#"
-<!DOCTYPE html>
-  <head>
-    <style>
-      div > * {
-        display: none;
-        width: 450px;
-        background-color: Canvas ;
-        position: relative;
-        top:-120px;
-        left:10px;
-      }
-      div:hover > * {
-        display: block;
-        pointer-events:none;
-      }
-      html, body {
-        background: #c0c0c0;
-      }
-    </style>
-    <script>
-      /* test w/ draw_path( document.querySelectorAll('div')[5] ) */
-      function draw_path(clicked_elm) {
-        console.log('draw_path', clicked_elm);
-        /*event = event || window.event;
-        var clicked_elm = event.currentTarget || event.target;*/
-        var path_coords_s = clicked_elm.getAttribute("c").split(" ");
-        console.log(path_coords_s);
-        var path_coords = [];
-        for (var i=0; i<path_coords_s.length; i+= 1) {
-          try {
-            var coords_s = path_coords_s[i].split(",");
-            if (coords_s.length < 2) {
-              continue;
-            }
-            path_coords.push(
-              [ parseFloat(coords_s[0]), parseFloat(coords_s[1]) ]
-            );
-          }
-          catch (e) {
-            console.log(e);
-          }
-        }
-        console.log(path_coords);
-        var canvas_elm = document.getElementById("overlay-canvas");
-        var ctx = canvas_elm.getContext("2d");
-        ctx.clearRect(0, 0, canvas_elm.width, canvas_elm.height);
-
-        try {
-          var initial_coords_s = document.getElementById("initial-sol").getAttribute("c").split(" ");
-          var initial_coords = [];
-          for (var i=0; i<initial_coords_s.length; i+= 1) {
-            try {
-              var coords_s = initial_coords_s[i].split(",");
-              if (coords_s.length < 2) {
-                continue;
-              }
-              initial_coords.push(
-                [ parseFloat(coords_s[0]), parseFloat(coords_s[1]) ]
-              );
-            }
-            catch (e) {
-              console.log(e);
-            }
-          }
-          ctx.lineWidth = 1;
-          ctx.strokeStyle = 'white';
-          ctx.setLineDash([]);
-          var last_coords = initial_coords[initial_coords.length-1];
-          for (var i=0; i<initial_coords.length; i+= 1) {
-            var dis_coords = initial_coords[i];
-            ctx.beginPath();
-            ctx.moveTo(last_coords[0], last_coords[1]);
-            ctx.lineTo(dis_coords[0], dis_coords[1]);
-            ctx.stroke();
-            last_coords = dis_coords;
-          }
-        }
-        catch (e) {
-          console.log(e);
-        }
 
-        ctx.lineWidth = 2;
-        ctx.strokeStyle = 'black';
-        ctx.setLineDash([10,10]);
-        var last_coords = path_coords[path_coords.length-1];
-        for (var i=0; i<path_coords.length; i+= 1) {
-          var dis_coords = path_coords[i];
-          ctx.beginPath();
-          ctx.moveTo(last_coords[0], last_coords[1]);
-          ctx.lineTo(dis_coords[0], dis_coords[1]);
-          ctx.stroke();
-          last_coords = dis_coords;
-        }
-      }
-    </script>
-  </head>
-  <body>
-"#;
-pub const HTML_END: &'static str = r#"
-  <canvas id="overlay-canvas" width="1450px" height="1450px" style="position:absolute;top:0;left:0;pointer-events:none;"/>
-</body>
-"#;
-pub const HTML_POINT_SCALE: fp = 100.0 as fp;
-
-fn usage() {
-  println!(r#"Usage: ./tsp-sol path/to/berlin52.tsp|delta|selective|spray
-
-Passing a single file (tsp/berlin52.tsp) will run JeffAlgo on it and print the size and solution path.
-
-delta will cause 1000 runs using both JeffAlgo and BruteAlgo, incorrect JeffAlgo runs will be dumped to the ./views directory.
-
-selective loops throuh increasingly large cities and exits when JeffAlgo does not match BruteAlgo.
-
-spray requires 2 numbers after it, the N-1 size of the city and a resolution to spray over. `spray` also takes
-      the initial N-1 city coordinates from the environment variable TSP_INITIAL_COORDS. An example
-      is TSP_INITIAL_COORDS='2.5,8.5 7.5,8.5 12.5,8.5 7.5,9.0' ./tsp-sol spray 4 0.2.
-      This creates a city with the above coordinates, then for every point 0.2 units apart in
-      the bounding box it attempts to insert it using both JeffAlgo and BruteAlgo. If the tours
-      generated by JeffAlgo and BruteAlgo match a green pixel will be plotted on ./views/spray.png,
-      if they do not match a red pixel is plotted. This may be used to graphically show where
-      JeffAlgo fails to uphold the hamiltonian cycle invariant from city size N to N+1.
-
-pattern-scan N granularity
-  pattern-scan needs the number of cities to consider and the granularity of the grid of
-  N+1 points to lay on top; produces a graph showing all identical tours in the same color.
-
-multi-pattern-scan N pattern-granularity num-steps-to-interop
-  multi-pattern-scan needs the number of cities to consider, the granularity of the grid of
-  N+1 points to lay on top, and a step number.
-  The step number is how many steps we use to move a city from its arrangement
-  defined by TSP_INITIAL_COORDS to TSP_ENDING_COORDS, for example a value of 3
-  would produce 1 pattern-scan result considering TSP_INITIAL_COORDS,
-  1 pattern-scan result for a city w/ points at the midpoint of each of the 2 arrangements,
-  and finally 1 pattern-scan result considering TSP_ENDING_COORDS.
-
-spray-pattern-search N pattern-granularity max-sprays-to-perform
-  spray-pattern-search needs the number of cities to consider, the granularity of the grid of
-  N+1 points to lay on top, and the number of sprays to perform.
-  For each spray, generates a random N-sized city and performs pattern-scan of the given pattern-granularity,
-  recording additional details along the way.
-
-  The goal of this operation is to test conjectures about relationships between edge weights and
-  optimal tour patterns, which get written in spray_pattern_search().
-
-
-
-"#);
+
+// We will read in a problem & compute a weights matrix, the solver must return
+// a vector of the path to take from city index to index.
+// Solver function header:
+//   pub fn solve(node_coordinates: Vec<(usize, fp, fp)>, weights: &Vec<Vec<fp>>) -> Vec<usize>
+
+// Set by --format json, stdout then carries nothing but the solve report
+static JSON_REPORT: AtomicBool = AtomicBool::new(false);
+
+fn json_report() -> bool {
+  JSON_REPORT.load(Ordering::Relaxed)
 }
 
-/// We will read in a problem & compute a weights matrix, the solver must return
-/// a vector of the path to take from city index to index.
-/// Solver function header:
-///   pub fn solve(node_coordinates: Vec<(usize, fp, fp)>, weights: &Vec<Vec<fp>>) -> Vec<usize>
+// Set by report_error, main then exits with status 1
+static RUN_FAILED: AtomicBool = AtomicBool::new(false);
+
+// Errors go to stderr so --format json's stdout stays parseable, and fail the run
+fn report_error(message: &str) {
+  eprintln!("{}", message);
+  RUN_FAILED.store(true, Ordering::Relaxed);
+}
 
 fn main() {
   let begin_time = std::time::Instant::now();
   timed_main();
   let exec_duration = begin_time.elapsed();
-  println!("=== Elapsed time: {:?} ===", exec_duration);
+  if let Some(manifest_path) = output::finish_run(exec_duration) {
+    if !json_report() {
+      println!("Run manifest: {}", manifest_path.display());
+    }
+  }
+  if !json_report() {
+    println!("=== Elapsed time: {:?} ===", exec_duration);
+  }
 
   // Flush any brute algo cache we may have
   if brute_algo::use_brute_cache_env_val() {
@@ -258,13 +166,26 @@ fn main() {
     }
   }
 
+  if RUN_FAILED.load(Ordering::Relaxed) {
+    std::process::exit(1);
+  }
+
 }
 
 fn timed_main() {
-  let args: Vec<_> = env::args().collect();
-  if args.len() < 2 {
-    usage();
-    return;
+  let cli = match cli::Cli::parse_args(env::args().collect()) {
+    Ok(cli) => cli,
+    Err(e) => e.exit(), // prints --help / --version, or the bad argument and usage
+  };
+  if let cli::Command::Solve(args) = &cli.command {
+    JSON_REPORT.store(args.format == cli::OutputFormat::Json, Ordering::Relaxed);
+  }
+  // serve only reads what earlier runs wrote
+  if !matches!(cli.command, cli::Command::Serve(_)) {
+    if let Err(e) = output::start_run(&cli.out_dir, cli.timestamped, cli.command.name(), env::args().collect()) {
+      report_error(&e);
+      return;
+    }
   }
 
   if cfg!(windows) {
@@ -272,160 +193,570 @@ fn timed_main() {
   }
 
   let thread_pool = ThreadPool::new( num_cpus::get_physical() );
-  println!("Brute force algo thread pool size: {}", thread_pool.max_count());
+  if !json_report() {
+    println!("Brute force algo thread pool size: {}", thread_pool.max_count());
+  }
 
   // Initialize GPU
 
   // Grab largest device, report sizes, and pass this to downstream funcs which may either use the
   // thread_pool OR the emu device object to schedule work across
   let mut gpu_adapter = get_best_gpu();
-  if let Some(ref adapter) = gpu_adapter {
-    println!("GPU device = {:?}", adapter.get_info());
-  }
-  else {
-    println!("NO GPU");
+  if !json_report() {
+    match &gpu_adapter {
+      Some(adapter) => println!("GPU device = {:?}", adapter.get_info()),
+      None => println!("NO GPU"),
+    }
   }
 
-  let file_arg = args.get(1).unwrap();
+  match cli.command {
+    cli::Command::Solve(args) => {
+      solve_file(&args, &thread_pool);
+    }
+
+    cli::Command::PatternScan(args) => {
+      // Given a city of points, add one more in a grid and
+      // store a color based on the optimal point arrangement.
+      // Sections w/ the same ideal solution path will be grouped
+      // as the same color.
+      let scenario = match load_scenario(&args.cities, None, args.n, args.granularity, 1, false, &args.jalgo.flags()) {
+        Some(scenario) => scenario,
+        None => return, // error message printed in load_scenario
+      };
+      pattern_scan(
+        &scenario,
+        &output::path("pattern-scan.png"),
+        &thread_pool, &mut gpu_adapter
+      );
+    }
+
+    cli::Command::MultiPatternScan(args) => {
+      // Same as pattern-scan, but take in 2 cities and perform
+      // pattern_scan in steps
+      let mut scenario = match load_scenario(&args.cities, args.ending_coords.as_deref(), args.n, args.granularity, args.steps as usize, true, &args.jalgo.flags()) {
+        Some(scenario) => scenario,
+        None => return, // error message printed in load_scenario
+      };
+      if let Some(fps) = args.fps {
+        scenario.fps = fps as usize;
+      }
+      multi_pattern_scan(
+        &scenario,
+        &thread_pool, &mut gpu_adapter
+      );
+    }
+
+    cli::Command::SprayPatternSearch(args) => {
+      let jalgo_flags = args.jalgo.flags();
+      let scenario = match &args.scenario {
+        Some(scenario_file) => {
+          let cities = cli::ScenarioArgs { scenario: Some(scenario_file.clone()), initial_coords: None };
+          match load_scenario(&cities, None, args.n, args.granularity, 1, false, &jalgo_flags) {
+            Some(scenario) => scenario,
+            None => return, // error message printed in load_scenario
+          }
+        }
+        None => {
+          let mut scenario = Scenario::random(args.n, args.granularity);
+          jalgo_flags.apply(&mut scenario);
+          scenario
+        }
+      };
+      spray_pattern_search(
+        &scenario,
+        args.sprays,
+        &thread_pool, &mut gpu_adapter
+      );
+    }
+
+    cli::Command::Serve(args) => {
+      viewer::serve(args.directory.as_deref().unwrap_or(&cli.out_dir), args.port, &thread_pool);
+    }
 
-  let mut use_jalgo = true;
-  let mut use_brute = false;
-  let mut write_solution_out_to_views = false;
-  for arg in &args {
-    if arg == "jalgo" {
-      println!("Enabling jalgo...");
-      use_jalgo = true;
+    cli::Command::Delta(args) => {
+      let num = args.runs as usize;
+      let jalgo_options = args.jalgo.flags().options();
+      let num_failed = delta(num, 4, 8, args.family, &jalgo_options, args.all_insertion_orders, &thread_pool); // test the algorithm on generated cities, between 4-7 points each.
+      println!("Failed {} out of {} (family {}, insertion order {}, initial tour {}, deep {})", num_failed, num, args.family.name(), jalgo_options.insertion_order.label(), jalgo_options.initial_tour.name(), jalgo_options.deep);
     }
-    if arg == "brute" {
-      println!("Enabling brute...");
-      use_brute = true;
+
+    cli::Command::Shrink(args) => {
+      // Minimize a city where jeff_algo disagrees with brute_algo down to the smallest counterexample
+      let scenario = match load_scenario(&args.cities, None, args.n, 0.25, 1, false, &args.jalgo.flags()) {
+        Some(scenario) => scenario,
+        None => return, // error message printed in load_scenario
+      };
+      shrink::shrink_and_report(&scenario.node_coordinates(), &scenario.jalgo_options(), &output::path("shrunk/"), &thread_pool);
     }
-    if arg == "no-jalgo" {
-      println!("Disabling jalgo...");
-      use_jalgo = false;
+
+    cli::Command::Generate(args) => {
+      // Write a city of some family as a TSPLIB file
+      let family = args.family;
+      let num_points = args.n;
+      let file_path = args.file.unwrap_or_else(|| output::path(&format!("generated/{}-{}.tsp", family.name(), num_points)));
+      let node_coordinates = generators::generate(family, num_points, family.tsplib_bounds());
+      let name = format!("{}{}", family.name(), num_points);
+      match generators::write_tsplib(&file_path, &name, &format!("{} {} cities generated by tsp-sol", num_points, family.name()), &node_coordinates) {
+        Ok(()) => println!("Wrote {}", file_path),
+        Err(e) => report_error(&e),
+      }
     }
-    if arg == "no-brute" {
-      println!("Brute jalgo...");
-      use_brute = false;
+
+    cli::Command::Stepcheck(args) => {
+      // Compare every intermediate jeff_algo tour against brute_algo on the same cities
+      let scenario = match load_scenario(&args.cities, None, args.n, 0.25, 1, false, &args.jalgo.flags()) {
+        Some(scenario) => scenario,
+        None => return, // error message printed in load_scenario
+      };
+      stepcheck::check_and_report(&scenario.node_coordinates(), args.runs, &scenario.jalgo_options(), &output::path("stepcheck/"), &thread_pool);
     }
-    if arg == "view" {
-      write_solution_out_to_views = true;
+
+    cli::Command::Selective(args) => {
+      // generate increasing city size until failure (jeff() != brute()), then go back and map a large range of points
+      let max_cities_to_test = args.max_cities as usize;
+      let num_to_test_before = args.num_to_test_before as usize;
+      let mut min_cities_to_ignore = max_cities_to_test.saturating_sub(num_to_test_before);
+      if min_cities_to_ignore >= max_cities_to_test {
+        println!("Invalid num_to_test_before passed ({}), resetting min_cities_to_ignore from {} to {}", num_to_test_before, min_cities_to_ignore, max_cities_to_test - 1);
+        min_cities_to_ignore = max_cities_to_test - 1;
+      }
+      selective(
+        min_cities_to_ignore,
+        max_cities_to_test,
+        args.family,
+        &args.jalgo.flags().options(),
+        &thread_pool, &mut gpu_adapter
+      );
     }
-    if arg == "no-view" {
-      write_solution_out_to_views = false;
+
+    cli::Command::Spray(args) => {
+      // Generate random N points then add a grid of points and track where insertion
+      // results in a non-optimal path.
+      let scenario = match load_scenario(&args.cities, None, args.n, args.granularity, 1, false, &args.jalgo.flags()) {
+        Some(scenario) => scenario,
+        None => return, // error message printed in load_scenario
+      };
+      spray(
+        &scenario,
+        args.one_step,
+        &thread_pool, &mut gpu_adapter
+      );
     }
   }
+}
 
-  if file_arg == "pattern-scan" {
-    // Given a city of points, add one more in a grid and
-    // store a color based on the optimal point arrangement.
-    // Sections w/ the same ideal solution path will be grouped
-    // as the same color.
-    pattern_scan(
-      args.get(2).unwrap_or(&"5".to_string()).parse().unwrap(), // given number OR 5
-      args.get(3).unwrap_or(&"0.25".to_string()).parse().unwrap(), // given number OR 0.25
-      "views/pattern-scan.png",
-      &thread_pool, &mut gpu_adapter
-    );
+// The solve command: one file, solved the way its options ask.
+fn solve_file(args: &cli::SolveArgs, thread_pool: &ThreadPool) {
+  let file_arg = args.file.as_str();
+  let (use_jalgo, use_brute, write_solution_out_to_views, use_fast) = args.solvers();
+  // Open paths name their ends with the city numbers used in the file
+  let path_end_ids = [args.start, args.end];
+  let open_path = args.open || path_end_ids.iter().any(|id| id.is_some());
+  let num_vehicles_arg = args.vehicles.map(|num_vehicles| num_vehicles as usize);
+  let depot_id_arg = args.depot;
+  let time_windows_arg = args.time_windows.clone();
+  let metric_arg = args.metric;
+  let prizes_arg = args.prizes.clone();
+  let budget = args.budget;
+  let export_files = &args.export;
+  let jalgo_options = args.jalgo.flags().options();
+
+  if vrp::is_cvrp_file(file_arg) {
+    if json_report() {
+      report_error("--format json reports single tours, CVRP routes are only printed as text");
+      return;
+    }
+    let mut problem = match vrp::open_cvrp(file_arg) {
+      Some(problem) => problem,
+      None => return, // error message printed in open_cvrp
+    };
+    if num_vehicles_arg.is_some() {
+      problem.num_vehicles = num_vehicles_arg;
+    }
+    solve_routes(&problem, use_brute, write_solution_out_to_views, &jalgo_options);
     return;
   }
 
-  if file_arg == "multi-pattern-scan" {
-    // Same as pattern-scan, but take in 2 cities and perform
-    // pattern_scan in steps
-    multi_pattern_scan(
-      args.get(2).unwrap_or(&"5".to_string()).parse().unwrap(), // given number OR 5 - number of cities
-      args.get(3).unwrap_or(&"0.25".to_string()).parse().unwrap(), // given number OR 0.25 - resolution to generate a SINGLE multi pattern at
-      args.get(4).unwrap_or(&"10".to_string()).parse().unwrap(), // number of steps to put between 2 cities, aka total number of pattern_scans to run.
-      &thread_pool, &mut gpu_adapter
-    );
+  let (file_coordinates, explicit_weights, file_metric) = match open_tsp_coordinates(file_arg.to_string()) {
+    Some(city) => city,
+    None => {
+      return; // error message printed in open_tsp_coordinates
+    }
+  };
+  if let Some((id, x, y)) = file_coordinates.iter().find(|(_id, x, y)| !x.is_finite() || !y.is_finite()) {
+    report_error(&format!("City {} has coordinates {},{} which are not finite", id, x, y));
+    return;
+  }
+  let metric = metric_arg.unwrap_or(file_metric);
+  if metric != Metric::Euclidean && !json_report() {
+    println!("Measuring distances with the {} metric", metric.name());
+  }
+  // Weights use the file's own coordinates, everything else (images, hulls) their projection
+  let coordinate_weights = explicit_weights.is_none() && metric == Metric::Euclidean;
+  let city_weights = || explicit_weights.clone().unwrap_or_else(|| metric.weights(&file_coordinates) );
+  let node_coordinates = metric.view_coordinates(&file_coordinates);
+
+  let depot = match depot_id_arg {
+    Some(depot_id) => match node_coordinates.iter().position(|(id, _x, _y)| *id == depot_id) {
+      Some(depot) => depot,
+      None => {
+        report_error(&format!("City {} is not in the file", depot_id));
+        return;
+      }
+    },
+    None => 0,
+  };
+
+  let tour_variant = budget.is_some() || time_windows_arg.is_some() || num_vehicles_arg.is_some() || open_path;
+  if json_report() && tour_variant {
+    report_error("--format json reports single closed tours, --budget, --time-windows, --vehicles and open paths are only printed as text");
     return;
   }
+  if !export_files.is_empty() && tour_variant {
+    println!("--export writes single closed tours, it is ignored for --budget, --time-windows, --vehicles and open paths");
+  }
 
-  if file_arg == "spray-pattern-search" {
-    spray_pattern_search(
-      args.get(2).unwrap_or(&"5".to_string()).parse().unwrap(), // given number OR 5 - number of cities
-      args.get(3).unwrap_or(&"0.25".to_string()).parse().unwrap(), // given number OR 0.25 - resolution to generate a SINGLE multi pattern at
-      args.get(4).unwrap_or(&"100".to_string()).parse().unwrap(), // number of sprays to perform
-      &thread_pool, &mut gpu_adapter
-    );
+  let solvers = FileSolvers { use_jalgo, use_brute, write_solution_out_to_views, jalgo_options: &jalgo_options };
+  if let Some(budget) = budget {
+    let prizes = match &prizes_arg {
+      Some(prizes_file) => match orienteering::Prizes::open(prizes_file, &node_coordinates) {
+        Some(prizes) => prizes,
+        None => return, // error message printed in Prizes::open
+      },
+      None => orienteering::Prizes::uniform(node_coordinates.len()),
+    };
+    let weights = city_weights();
+    solve_orienteering(&node_coordinates, &weights, &prizes, depot, budget, &solvers);
     return;
   }
 
-  if file_arg == "delta" {
-    let num = 1000;
-    let num_failed = delta(num, 4, 8, &thread_pool); // test the algorithm on a thousand generated cities, between 4-8 points each.
-    println!("Failed {} out of {}", num_failed, num);
+  if let Some(time_windows_file) = time_windows_arg {
+    let time_windows = match time_windows::TimeWindows::open(&time_windows_file, &node_coordinates) {
+      Some(time_windows) => time_windows,
+      None => return, // error message printed in TimeWindows::open
+    };
+    let weights = city_weights();
+    solve_time_windows(&node_coordinates, &weights, &time_windows, depot, use_jalgo, use_brute, &jalgo_options);
     return;
   }
 
-  if file_arg == "selective" {
-    // generate increasing city size until failure (jeff() != brute()), then go back and map a large range of points
-    let max_cities_to_test: usize = args.get(2).unwrap_or(&"11".to_string()).parse().unwrap();  // arg after "selective" OR 11
-    let num_to_test_before: usize = args.get(3).unwrap_or(&"2".to_string()).parse().unwrap();  // arg after "max_cities_to_test" OR 1
-    let mut min_cities_to_ignore = max_cities_to_test - num_to_test_before;
-    if min_cities_to_ignore >= max_cities_to_test {
-      println!("Invalid num_to_test_before passed ({}), resetting min_cities_to_ignore from {} to {}", num_to_test_before, min_cities_to_ignore, max_cities_to_test - 1);
-      min_cities_to_ignore = max_cities_to_test - 1;
-    }
-    selective(
-      min_cities_to_ignore,
-      max_cities_to_test,
-      &thread_pool, &mut gpu_adapter
-    );
+  if let Some(num_vehicles) = num_vehicles_arg {
+    let weights = city_weights();
+    solve_routes(&vrp::VrpProblem::mtsp(&node_coordinates, &weights, depot, num_vehicles), use_brute, write_solution_out_to_views, &jalgo_options);
     return;
   }
 
-  if file_arg == "spray" {
-    // Generate random N points then add a grid of points and track where insertion
-    // results in a non-optimal path.
-    spray(
-      args.get(2).unwrap_or(&"5".to_string()).parse().unwrap(), // given number OR 5
-      args.get(3).unwrap_or(&"0.25".to_string()).parse().unwrap(), // given number OR 0.25
-      &thread_pool, &mut gpu_adapter
-    );
+  if open_path {
+    let weights = city_weights();
+    solve_open_path(&node_coordinates, &weights, path_end_ids, &solvers, thread_pool);
     return;
   }
 
-  let (node_coordinates, weights) = match open_tsp_problem(file_arg.to_string()) {
-    Some(stuff) => stuff,
-    None => {
-      return; // error message printed in open_tsp_problem
+  let distance_name = if explicit_weights.is_some() { "explicit" } else { metric.name() };
+  if coordinate_weights && (use_fast || node_coordinates.len() > FAST_SOLVE_MIN_CITIES) {
+    // No weights matrix, jeff_algo::solve_fast works from coordinates alone
+    if !json_report() {
+      println!("City has {} points, using jeff_algo::solve_fast", node_coordinates.len());
     }
-  };
+    let solve_begin = std::time::Instant::now();
+    let solution_p = jeff_algo::solve_fast(&node_coordinates, &jalgo_options);
+    let solve_duration = solve_begin.elapsed();
+    if write_solution_out_to_views {
+      save_state_image(output::path("tsp_problem/jalgo-fast.png"), &solution_p, &node_coordinates);
+    }
+    let solution_dist = compute_dist_coords(&node_coordinates, &solution_p);
+    if json_report() {
+      let mut report = report::SolveReport::new(file_arg, node_coordinates.len(), distance_name, true);
+      report.add_run("jeff_algo::solve_fast", &node_coordinates, &solution_p, solution_dist, solve_duration);
+      println!("{}", report.to_json());
+    }
+    else {
+      println!("====== jeff_algo::solve_fast ======");
+      println!("Solution distance: {}", solution_dist);
+      if use_brute {
+        println!("brute is skipped for {} cities", node_coordinates.len());
+      }
+    }
+    export_tour(export_files, file_arg, "jeff_algo", &file_coordinates, metric, &solution_p, solution_dist);
+    return;
+  }
+
+  let weights = city_weights();
+  let symmetric = weights_are_symmetric(&weights);
+  if !json_report() {
+    println!("City has {} points", weights.len());
+    if !symmetric {
+      println!("Weights are asymmetric, tours are compared in the direction they are driven");
+    }
+  }
 
   // First dump the environment variable we'd need to set to scan this city,
-  // useful for going from .tsp file -> research
-  let mut env_s = "TSP_INITIAL_COORDS='".to_string();
-  for (_i, x, y) in node_coordinates.iter() {
-    env_s += format!("{:.2},{:.2} ", x, y).as_str();
+  // useful for going from .tsp file -> research. Other weights cannot be rebuilt from coordinates.
+  if coordinate_weights && !json_report() {
+    let mut env_s = "TSP_INITIAL_COORDS='".to_string();
+    for (_i, x, y) in node_coordinates.iter() {
+      env_s += format!("{:.2},{:.2} ", x, y).as_str();
+    }
+    env_s += "'";
+    println!("{}", env_s);
   }
-  env_s += "'";
-  println!("{}", env_s);
 
+  let mut solved_tours = vec![];
+  let mut report = report::SolveReport::new(file_arg, weights.len(), distance_name, symmetric);
   if use_jalgo {
+    let solve_begin = std::time::Instant::now();
     let solution_p = if write_solution_out_to_views {
-      jeff_algo::solve(&node_coordinates, &weights, Some( "./views/tsp_problem".to_string() ))
+      jeff_algo::solve_with_options(&node_coordinates, &weights, Some( output::path("tsp_problem") ), &jalgo_options)
     }
     else {
-      jeff_algo::solve(&node_coordinates, &weights, None)
+      jeff_algo::solve_with_options(&node_coordinates, &weights, None, &jalgo_options)
     };
-    println!("====== jeff_algo::solve ======");
-    print_path_metadata(&solution_p, &weights);
+    report.add_run("jeff_algo", &node_coordinates, &solution_p, compute_dist(&weights, &solution_p), solve_begin.elapsed());
+    if !json_report() {
+      println!("====== jeff_algo::solve ======");
+      print_path_metadata(&solution_p, &weights);
+    }
+    solved_tours.push(("jeff_algo", solution_p));
   }
 
   if use_brute {
+    let solve_begin = std::time::Instant::now();
     let solution_p = if write_solution_out_to_views {
-      let all_solutions = brute_algo::solve_all(&node_coordinates, &weights, Some( "./views/tsp_problem".to_string() ), &thread_pool);
+      let all_solutions = brute_algo::solve_all(&node_coordinates, &weights, Some( output::path("tsp_problem") ), thread_pool);
       all_solutions[0].clone()
     }
     else {
-      brute_algo::solve(&node_coordinates, &weights, None, &thread_pool)
+      brute_algo::solve(&node_coordinates, &weights, None, thread_pool)
+    };
+    report.add_run("brute_algo", &node_coordinates, &solution_p, compute_dist(&weights, &solution_p), solve_begin.elapsed());
+    if !json_report() {
+      println!("====== brute_algo::solve ======");
+      print_path_metadata(&solution_p, &weights);
+    }
+    solved_tours.push(("brute_algo", solution_p));
+  }
+
+  if json_report() {
+    // the report holds file city numbers; tour identity only needs unique labels, weights give the symmetry
+    report.compare_runs(&|a, b| is_identical_tour(a, b, &weights));
+    println!("{}", report.to_json());
+  }
+
+  // the shortest tour found, brute_algo's on ties
+  let shortest = solved_tours.iter().rev().min_by(|a, b| compute_dist(&weights, &a.1).partial_cmp(&compute_dist(&weights, &b.1)).unwrap_or(std::cmp::Ordering::Equal));
+  if let Some((algo_name, tour)) = shortest {
+    export_tour(export_files, file_arg, algo_name, &file_coordinates, metric, tour, compute_dist(&weights, tour));
+  }
+}
+
+// Writes tour to every --export file, in the format of its extension.
+fn export_tour(export_files: &[String], file_arg: &str, algo_name: &str, file_coordinates: &[(CityNum, CityXYCoord, CityXYCoord)], metric: Metric, tour: &[CityNum], tour_dist: CityWeight) {
+  if export_files.is_empty() {
+    return;
+  }
+  let name = Path::new(file_arg).file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+  let exported = exporters::ExportedTour::new(&name, algo_name, file_coordinates, metric, tour, tour_dist);
+  for export_file in export_files.iter() {
+    match exported.write(export_file) {
+      Ok(()) if json_report() => {}
+      Ok(()) => println!("Wrote {} tour to {}", algo_name, export_file),
+      Err(e) => eprintln!("Error exporting tour: {}", e),
+    }
+  }
+}
+
+// Which solvers a file solve runs, how jeff_algo is configured and whether solutions are saved as images.
+struct FileSolvers<'a> {
+  use_jalgo: bool,
+  use_brute: bool,
+  write_solution_out_to_views: bool,
+  jalgo_options: &'a jeff_algo::SolveOptions,
+}
+
+// File solve for --open/--start/--end: the shortest path through every city instead of a tour.
+fn solve_open_path(node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>, weights: &[Vec<CityWeight>], path_end_ids: [Option<usize>; 2], solvers: &FileSolvers, thread_pool: &ThreadPool) {
+  // file city numbers -> indexes
+  let mut path_ends = [None, None];
+  for (end_id, end_citynum) in path_end_ids.iter().zip(path_ends.iter_mut()) {
+    if let Some(end_id) = end_id {
+      match node_coordinates.iter().position(|(id, _x, _y)| id == end_id) {
+        Some(citynum) => *end_citynum = Some(citynum),
+        None => {
+          report_error(&format!("City {} is not in the file", end_id));
+          return;
+        }
+      }
+    }
+  }
+  let ends = open_path::PathEnds { start: path_ends[0], end: path_ends[1] };
+  if let Err(e) = ends.check(node_coordinates.len()) {
+    report_error(&e);
+    return;
+  }
+  println!("City has {} points, solving an open path (start {:?}, end {:?})", weights.len(), path_end_ids[0], path_end_ids[1]);
+
+  let mut solutions = vec![];
+  if solvers.use_jalgo {
+    solutions.push(("jeff_algo", "jalgo", open_path::solve_jalgo(node_coordinates, weights, &ends, solvers.jalgo_options)));
+  }
+  if solvers.use_brute {
+    solutions.push(("brute_algo", "brute", open_path::solve_brute(node_coordinates, weights, &ends, thread_pool)));
+  }
+  for (algo_name, file_prefix, path) in solutions.iter() {
+    println!("====== {}::solve (open path) ======", algo_name);
+    println!("Path distance: {}", open_path::path_dist(weights, path));
+    let path_ids: Vec<String> = path.iter().map(|citynum| format!("{}", node_coordinates[*citynum].0)).collect();
+    println!("Path order (file city numbers): {}", path_ids.join(" "));
+    if solvers.write_solution_out_to_views {
+      save_path_image(output::path(&format!("tsp_problem/{}-open-path.png", file_prefix)), path, node_coordinates, false);
+    }
+  }
+}
+
+// File solve for --budget: the most prize a tour from the depot can collect within budget.
+fn solve_orienteering(node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>, weights: &[Vec<CityWeight>], prizes: &orienteering::Prizes, depot: CityNum, budget: fp, solvers: &FileSolvers) {
+  println!("City has {} points worth {} in prizes, tour budget {} from depot {}", weights.len(), prizes.prizes.iter().sum::<fp>(), budget, node_coordinates[depot].0);
+  let mut solutions = vec![];
+  if solvers.use_jalgo {
+    solutions.push(("jeff_algo", "jalgo", orienteering::solve_greedy(node_coordinates, weights, prizes, depot, budget, solvers.jalgo_options)));
+  }
+  if solvers.use_brute {
+    match orienteering::solve_exact(weights, prizes, depot, budget) {
+      Some(tour) => solutions.push(("brute_algo", "brute", tour)),
+      None => println!("brute is skipped for {} cities (at most {})", weights.len(), orienteering::MAX_EXACT_CITIES),
+    }
+  }
+  for (algo_name, file_prefix, tour) in solutions.iter() {
+    println!("====== {}::solve (orienteering) ======", algo_name);
+    println!("Prize collected: {}  tour distance: {} of budget {}", prizes.collected(tour), compute_dist(weights, tour), budget);
+    let tour_ids: Vec<String> = tour.iter().map(|citynum| format!("{}", node_coordinates[*citynum].0)).collect();
+    println!("Visits {} of {} cities (file city numbers): {}", tour.len(), weights.len(), tour_ids.join(" "));
+    if solvers.write_solution_out_to_views {
+      save_state_image(output::path(&format!("tsp_problem/{}-orienteering.png", file_prefix)), tour, node_coordinates);
+    }
+  }
+}
+
+// Largest city brute_algo checks every order of under --time-windows; the depot is fixed so this is 10! orders.
+const MAX_BRUTE_CITIES_WITH_TIME_WINDOWS: usize = 11;
+
+// File solve for --time-windows: tours leave the depot at time 0 and are reported with their lateness.
+fn solve_time_windows(node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>, weights: &Vec<Vec<CityWeight>>, time_windows: &time_windows::TimeWindows, depot: CityNum, use_jalgo: bool, use_brute: bool, jalgo_options: &jeff_algo::SolveOptions) {
+  println!("City has {} points, leaving depot {} at time 0", weights.len(), node_coordinates[depot].0);
+  let mut solutions = vec![];
+  if use_jalgo {
+    solutions.push(("jeff_algo", Some(time_windows::solve_jalgo(node_coordinates, weights, time_windows, depot, jalgo_options))));
+  }
+  if use_brute {
+    if weights.len() > MAX_BRUTE_CITIES_WITH_TIME_WINDOWS {
+      println!("brute is skipped for {} cities with time windows (at most {})", weights.len(), MAX_BRUTE_CITIES_WITH_TIME_WINDOWS);
+    }
+    else {
+      solutions.push(("brute_algo", time_windows::solve_brute(weights, time_windows, depot)));
+    }
+  }
+
+  for (algo_name, tour) in solutions.iter() {
+    println!("====== {}::solve (time windows) ======", algo_name);
+    let tour = match tour {
+      Some(tour) => tour,
+      None => {
+        println!("No tour keeps every time window");
+        continue;
+      }
     };
-    println!("====== brute_algo::solve ======");
-    print_path_metadata(&solution_p, &weights);
+    let schedule = time_windows::schedule(tour, weights, time_windows);
+    println!("Solution distance: {}", compute_dist(weights, tour));
+    println!("Feasible: {}  total lateness: {}  max lateness: {}", if schedule.is_feasible() { "yes" } else { "no" }, schedule.total_lateness(), schedule.max_lateness());
+    for i in 0..=tour.len() {
+      let citynum = tour[i % tour.len()];
+      let (earliest, latest) = time_windows.windows[citynum];
+      let late_s = if schedule.lateness[i] > fp_epsilon { format!("  LATE by {}", schedule.lateness[i]) } else { String::new() };
+      println!("  city {:>4} arrives {:>10.2}  window [{}, {}]{}", node_coordinates[citynum].0, schedule.arrivals[i], earliest, latest, late_s);
+    }
+  }
+}
+
+// File solve for CVRPLIB files and --vehicles: one route per vehicle from a shared depot.
+fn solve_routes(problem: &vrp::VrpProblem, use_brute: bool, write_solution_out_to_views: bool, jalgo_options: &jeff_algo::SolveOptions) {
+  let fleet_s = match problem.num_vehicles {
+    Some(num_vehicles) => format!("{}", num_vehicles),
+    None => "as many as needed".to_string(),
+  };
+  let capacity_s = match problem.capacity {
+    Some(capacity) => format!("{}", capacity),
+    None => "unlimited".to_string(),
+  };
+  println!("City has {} points, routing from depot {} with {} vehicles of capacity {}",
+    problem.weights.len(), problem.node_coordinates[problem.depot].0, fleet_s, capacity_s);
+  if use_brute {
+    println!("brute is skipped for routing problems");
+  }
+
+  let routes = match vrp::solve(problem, jalgo_options) {
+    Some(routes) => routes,
+    None => return, // reason reported in vrp::solve
+  };
+  println!("====== jeff_algo routes ======");
+  for (route_i, route) in routes.iter().enumerate() {
+    let route_ids: Vec<String> = route.iter().map(|citynum| format!("{}", problem.node_coordinates[*citynum].0)).collect();
+    println!("Route {}: load {} distance {} order (file city numbers): {}",
+      route_i + 1, problem.route_load(route), compute_dist(&problem.weights, route), route_ids.join(" "));
+  }
+  println!("Solution distance: {}", vrp::routes_dist(&problem.weights, &routes));
+  if write_solution_out_to_views {
+    save_routes_image(output::path("tsp_problem/jalgo-routes.png"), &routes, &problem.node_coordinates);
+  }
+}
+
+// --insertion-order / --initial-tour / --deep as given on the command line, None when not passed
+// so a scenario file's own choice is kept.
+#[derive(Debug, Default)]
+struct JalgoFlags {
+  insertion_order: Option<InsertionOrder>,
+  initial_tour: Option<InitialTour>,
+  deep: Option<usize>,
+}
+
+impl JalgoFlags {
+  fn options(&self) -> SolveOptions {
+    SolveOptions {
+      insertion_order: self.insertion_order.unwrap_or_default(),
+      initial_tour: self.initial_tour.unwrap_or_default(),
+      deep: self.deep.unwrap_or(jeff_algo::DEFAULT_DEEP),
+    }
+  }
+
+  fn apply(&self, scenario: &mut Scenario) {
+    if let Some(insertion_order) = self.insertion_order {
+      scenario.insertion_order = insertion_order;
+    }
+    if let Some(initial_tour) = self.initial_tour {
+      scenario.initial_tour = initial_tour;
+    }
+    if let Some(deep) = self.deep {
+      scenario.deep = deep;
+    }
   }
+}
 
+// Research commands read their cities from --scenario when given, falling back to
+// --initial-coords / --ending-coords (or their TSP_* variables) and the positional arguments.
+fn load_scenario(cities: &cli::ScenarioArgs, ending_coords: Option<&str>, n: usize, bound_granularity: fp, num_steps: usize, with_ending: bool, jalgo_flags: &JalgoFlags) -> Option<Scenario> {
+  let scenario = match &cities.scenario {
+    Some(scenario_file) => Scenario::load(scenario_file),
+    None => Scenario::from_coords(cities.initial_coords.as_deref(), ending_coords, n, bound_granularity, num_steps, with_ending),
+  };
+  match scenario {
+    Ok(mut scenario) => {
+      jalgo_flags.apply(&mut scenario);
+      Some(scenario)
+    }
+    Err(e) => {
+      report_error(&e);
+      None
+    }
+  }
 }
 
 fn get_best_gpu() -> Option<wgpu::Adapter> {
@@ -510,12 +841,8 @@ fn get_best_gpu() -> Option<wgpu::Adapter> {
     }
   }
 
-  // Just grab the first one
-  for adapter in adapters.enumerate_adapters(wgpu::Backends::VULKAN) {
-    return Some(adapter);
-  }
-  // If no devices,
-  return None;
+  // Just grab the first one, None if there are no devices
+  adapters.enumerate_adapters(wgpu::Backends::VULKAN).into_iter().next()
 }
 
 fn attempt_to_raise_priority() {
@@ -531,7 +858,7 @@ fn attempt_to_raise_priority() {
     //format!("import psutil ; pid={our_pid} ; p=psutil.Process(pid) ; p.cpu_affinity([0]) ; p.nice(-5)", our_pid=our_pid)
     format!("import psutil ; pid={our_pid} ; p=psutil.Process(pid) ;  p.nice(-5)", our_pid=our_pid)
   };
-  let res = Command::new("python").args(&[
+  let res = Command::new("python").args([
     "-c", &psutil_script
   ])
     .stdout(Stdio::null())
@@ -540,57 +867,94 @@ fn attempt_to_raise_priority() {
     .spawn();
 }
 
-fn delta(num_tests: usize, lower_city_size: usize, upper_city_size: usize, thread_pool: &ThreadPool) -> usize {
+fn delta(num_tests: usize, lower_city_size: usize, upper_city_size: usize, family: Family, jalgo_options: &SolveOptions, all_insertion_orders: bool, thread_pool: &ThreadPool) -> usize {
   let mut rng = thread_rng();
   let mut total_failed: usize = 0;
+  let insertion_orders = delta_insertion_orders(jalgo_options, all_insertion_orders);
+  let mut failed_per_order: Vec<usize> = vec![0; insertion_orders.len()];
   for i in 0..num_tests {
     let city_size = rng.gen_range(lower_city_size, upper_city_size);
     println!("Delta testing {}/{}", i, num_tests);
-    if ! delta_test(city_size, thread_pool) {
+    if ! delta_test(city_size, family, jalgo_options, &insertion_orders, &mut failed_per_order, thread_pool) {
       total_failed += 1;
     }
   }
-  return total_failed;
+
+  if insertion_orders.len() > 1 {
+    println!("Mismatch rate per insertion order over {} cities:", num_tests);
+    for (order, num_failed) in insertion_orders.iter().zip(failed_per_order.iter()) {
+      println!("  {:>14} {:>5} / {} = {:.2}%", order.label(), num_failed, num_tests, 100.0 * (*num_failed as fp) / (num_tests.max(1) as fp));
+    }
+  }
+  total_failed
 }
 
-fn delta_test(city_size: usize, thread_pool: &ThreadPool) -> bool {
-  let (node_coordinates, weights) = gen_tsp_problem(city_size, 0.0, 10.0, 0.0, 10.0);
+// Just jalgo_options' order, or with all_insertion_orders InsertionOrder::all(), random seeded like
+// jalgo_options when that is the order under test
+fn delta_insertion_orders(jalgo_options: &SolveOptions, all_insertion_orders: bool) -> Vec<InsertionOrder> {
+  if !all_insertion_orders {
+    return vec![jalgo_options.insertion_order];
+  }
+  InsertionOrder::all().into_iter().map(|order| match (order, jalgo_options.insertion_order) {
+    (InsertionOrder::Random(_), InsertionOrder::Random(seed)) => InsertionOrder::Random(seed),
+    _ => order,
+  }).collect()
+}
 
-  let jeff_sol = jeff_algo::solve(&node_coordinates, &weights, None);
-  let brute_sol = brute_algo::solve(&node_coordinates, &weights, None, thread_pool);
+// Returns false if jalgo_options' tour differs from brute_algo; every one of insertion_orders is
+// tried on the same city and tallied in failed_per_order (indexed like insertion_orders).
+fn delta_test(city_size: usize, family: Family, jalgo_options: &SolveOptions, insertion_orders: &[InsertionOrder], failed_per_order: &mut [usize], thread_pool: &ThreadPool) -> bool {
+  let node_coordinates = generators::generate(family, city_size, (0.0, 10.0, 0.0, 10.0));
+  let weights = compute_weight_coords(&node_coordinates);
 
-  let jeff_sol_len = compute_dist(&weights, &jeff_sol);
+  let brute_sol = brute_algo::solve(&node_coordinates, &weights, None, thread_pool);
   let brute_sol_len = compute_dist(&weights, &brute_sol);
 
-  let distance_diff = jeff_sol_len - brute_sol_len;
+  let mut passed = true;
+  for (order_i, order) in insertion_orders.iter().enumerate() {
+    let order_options = SolveOptions { insertion_order: *order, ..*jalgo_options };
+    let jeff_sol = jeff_algo::solve_with_options(&node_coordinates, &weights, None, &order_options);
+    let jeff_sol_len = compute_dist(&weights, &jeff_sol);
+
+    let distance_diff = jeff_sol_len - brute_sol_len;
+
+    if distance_diff.abs() > fp_epsilon && !is_identical_path(&jeff_sol, &brute_sol) { // account for floating point errors
+      failed_per_order[order_i] += 1;
+      if *order == jalgo_options.insertion_order {
+        passed = false;
+      }
+    }
+  }
 
-  if distance_diff.abs() > fp_epsilon && !is_identical_path(&jeff_sol, &brute_sol) { // account for floating point errors
+  if ! passed {
     // re-do test, saving results
     let r_test_num: usize = rand::thread_rng().gen_range(0, 10000000);
 
-    let prefix_dir = format!("./views/{:02}-{}/", weights.len(), r_test_num);
-    jeff_algo::solve(&node_coordinates, &weights, Some(prefix_dir.clone()));
+    let prefix_dir = output::path(&format!("{:02}-{}/", weights.len(), r_test_num));
+    jeff_algo::solve_with_options(&node_coordinates, &weights, Some(prefix_dir.clone()), jalgo_options);
     brute_algo::solve_all(&node_coordinates, &weights, Some(prefix_dir.clone()), thread_pool);
-    return false;
+    if shrink::shrink_failures_env_val() {
+      shrink::shrink_and_report(&node_coordinates, jalgo_options, &format!("{}shrunk/", prefix_dir), thread_pool);
+    }
   }
-  return true;
+  passed
 }
 
-fn print_path_metadata(path: &Vec<usize>, weights: &Vec<Vec<fp>>) {
-  println!("Solution distance: {}", compute_dist(weights, &path));
+fn print_path_metadata(path: &Vec<usize>, weights: &[Vec<fp>]) {
+  println!("Solution distance: {}", compute_dist(weights, path));
   print!("Solution order: ");
   for p in path {
     print!("{} ", *p);
   }
-  println!("");
+  println!();
 }
 
 // Bounds some number i within len, used heavily in index calculations
 fn b(i: usize, len: usize) -> usize {
-  return (i + len) % len;
+  (i + len) % len
 }
 
-fn compute_dist(weights: &Vec<Vec<fp>>, path: &[usize]) -> fp {
+fn compute_dist(weights: &[Vec<fp>], path: &[usize]) -> fp {
   let mut total: fp = 0.0;
   for p_i in 0..path.len() {
     unsafe {
@@ -599,49 +963,28 @@ fn compute_dist(weights: &Vec<Vec<fp>>, path: &[usize]) -> fp {
       total += weights.get_unchecked( *p ).get_unchecked( *p2 );
     }
   }
-  return total;
+  total
 }
 
-fn gen_tsp_problem(num_points: usize, min_x: fp, max_x: fp, min_y: fp, max_y: fp) -> (Vec<(usize, fp, fp)>, Vec<Vec<fp>>) {
-  let mut rng = rand::thread_rng();
-  let mut node_coordinates: Vec<(usize, fp, fp)> = vec![];
-
-  for i in 0..num_points {
-    node_coordinates.push(
-      (i, rng.gen_range(min_x, max_x), rng.gen_range(min_y, max_y))
-    );
-  }
-
-  // Compute 2x matrix of edge weights (assumes 2d euclidian geometry)
-  let mut weights: Vec<Vec<fp>> = Vec::with_capacity(node_coordinates.len());
-  {
-    for row_r in &node_coordinates {
-      let mut row_weight_v: Vec<fp> = Vec::with_capacity(node_coordinates.len());
-      for col_r in &node_coordinates {
-        let weight: fp = (
-          (row_r.1 - col_r.1).powf(2.0) + // x1 + x2 squared
-          (row_r.2 - col_r.2).powf(2.0)   // y1 + y2 squared
-        ).sqrt();
-
-        row_weight_v.push(weight);
-      }
-      weights.push(row_weight_v);
-    }
-  }
-
-  return (node_coordinates, weights);
-}
+// Explicit weights of the file if it has an EDGE_WEIGHT_SECTION (.atsp files), None when weights come from coordinates.
+type ExplicitWeights = Option<Vec<Vec<CityWeight>>>;
 
-fn open_tsp_problem(file_arg: String) -> Option<(Vec<(usize, fp, fp)>, Vec<Vec<fp>>)> {
+fn open_tsp_coordinates(file_arg: String) -> Option<(Vec<City>, ExplicitWeights, Metric)> {
   if ! Path::new(&file_arg).exists() {
-    println!("File does not exist: {}", file_arg);
+    report_error(&format!("File does not exist: {}", file_arg));
     return None;
   }
 
+  // .csv and .geojson cities, by extension
+  if importers::format_of(&file_arg).is_some() {
+    let (node_coordinates, metric) = importers::open(&file_arg)?;
+    return Some((node_coordinates, None, metric));
+  }
+
   let file = match File::open(file_arg.clone()) {
     Ok(f) => f,
     Err(e) => {
-      println!("Cannot open {}: {}", file_arg, e);
+      report_error(&format!("Cannot open {}: {}", file_arg, e));
       return None;
     }
   };
@@ -650,7 +993,34 @@ fn open_tsp_problem(file_arg: String) -> Option<(Vec<(usize, fp, fp)>, Vec<Vec<f
   let instance = match tsplib::parse( BufReader::new(file) ) {
     Ok(i) => i,
     Err(e) => {
-      println!("Error parsing tsplib file {}: {}", file_arg, e);
+      report_error(&format!("Error parsing tsplib file {}: {}", file_arg, e));
+      return None;
+    }
+  };
+
+  tsp_instance_coordinates(&file_arg, instance)
+}
+
+// Coordinates (and explicit weights, if any) of an already parsed instance plus the metric its EDGE_WEIGHT_TYPE
+// names; file_arg is only used in error messages.
+fn tsp_instance_coordinates(file_arg: &str, instance: tsplib::Instance) -> Option<(Vec<City>, ExplicitWeights, Metric)> {
+  let metric = match instance.edge_weight_type {
+    Some(tsplib::EdgeWeightType::Geo) => Metric::Geo,
+    _ => Metric::Euclidean,
+  };
+
+  let explicit_weights: ExplicitWeights = match &instance.edge_weight {
+    None | Some(EdgeWeight::Function) => None,
+    Some(EdgeWeight::FullMatrix(matrix)) => {
+      let n = instance.dimension;
+      if matrix.len() != n * n {
+        report_error(&format!("Err: {} has {} weights, a FULL_MATRIX of dimension {} needs {}", file_arg, matrix.len(), n, n * n));
+        return None;
+      }
+      Some( matrix.chunks(n).map(|row| row.iter().map(|w| *w as CityWeight).collect() ).collect() )
+    }
+    Some(_triangular) => {
+      report_error(&format!("Err: only EDGE_WEIGHT_FORMAT: FULL_MATRIX explicit weights are supported, {} uses a triangular format", file_arg));
       return None;
     }
   };
@@ -659,30 +1029,29 @@ fn open_tsp_problem(file_arg: String) -> Option<(Vec<(usize, fp, fp)>, Vec<Vec<f
     Some(node_c) => match node_c {
       NodeCoord::Two(vec_count_loc_loc) => vec_count_loc_loc,
       NodeCoord::Three(_we_dont_care) => {
-        println!("3D TSP problems currently unsupported.");
+        report_error("3D TSP problems currently unsupported.");
         return None;
       }
     },
+    // matrix-only files may still carry DISPLAY_DATA, otherwise they are drawn on a circle
+    None if explicit_weights.is_some() => match instance.display_data {
+      Some(display_data) => display_data.iter().map(|(i, x, y)| (*i, *x as f32, *y as f32) ).collect(),
+      None => geometry::circle_layout(instance.dimension),
+    },
     None => {
-      println!("Err: no coordinates found in {}", file_arg);
+      report_error(&format!("Err: no coordinates found in {}", file_arg));
       return None;
     }
   };
 
   let node_coordinates: Vec<(usize, fp, fp)> = node_coordinates.iter().map(|(a, b, c)| (*a, *b as fp, *c as fp) ).collect();
 
-  // Compute 2x matrix of edge weights (assumes 2d euclidian geometry)
-  let weights = compute_weight_coords(&node_coordinates);
-
-  println!("City has {} points", weights.len());
-  // remember weights is 2d square matrix (could be triangle, meh.)
-
-  return Some( (node_coordinates, weights) );
+  Some((node_coordinates, explicit_weights, metric))
 }
 
 // Meh used in imagery
 
-fn compute_center(path: &Vec<usize>, locations: &Vec<(usize, fp, fp)>) -> (fp, fp) {
+fn compute_center(path: &Vec<usize>, locations: &[(usize, fp, fp)]) -> (fp, fp) {
   let mut x_tot: fp = 0.0;
   let mut y_tot: fp = 0.0;
 
@@ -693,12 +1062,44 @@ fn compute_center(path: &Vec<usize>, locations: &Vec<(usize, fp, fp)>) -> (fp, f
 
   x_tot /= path.len() as fp;
   y_tot /= path.len() as fp;
-  return (x_tot, y_tot);
+  (x_tot, y_tot)
 }
 
 // Shared imagery functions
 
-fn save_state_image<I: Into<String>>(file_path: I, path: &Vec<usize>, locations: &Vec<(usize, fp, fp)>) {
+fn save_state_image<I: Into<String>>(file_path: I, path: &[usize], locations: &Vec<(usize, fp, fp)>) {
+  save_path_image(file_path, path, locations, true);
+}
+
+// closed=false leaves out the edge from the last city back to the first and marks the
+// path's start in green and its end in blue.
+fn save_path_image<I: Into<String>>(file_path: I, path: &[usize], locations: &Vec<(usize, fp, fp)>, closed: bool) {
+  save_paths_image(file_path, &[(path.to_vec(), Rgb([200, 200, 200]))], locations, closed);
+}
+
+// One closed route per vehicle, each in its own color, with the depot (every route's first city) circled in white.
+fn save_routes_image<I: Into<String>>(file_path: I, routes: &[Vec<usize>], locations: &Vec<(usize, fp, fp)>) {
+  let colored_routes: Vec<(Vec<usize>, Rgb<u8>)> = routes.iter().enumerate().map(|(i, route)| (route.clone(), route_color(i)) ).collect();
+  save_paths_image(file_path, &colored_routes, locations, true);
+}
+
+// Evenly spread hues (golden angle steps) so neighbouring route numbers never share a color.
+fn route_color(route_i: usize) -> Rgb<u8> {
+  let hue = ((route_i as fp) * 137.508) % 360.0;
+  let x = 1.0 - (((hue / 60.0) % 2.0) - 1.0).abs();
+  let (r, g, b) = match (hue / 60.0) as usize {
+    0 => (1.0, x, 0.0),
+    1 => (x, 1.0, 0.0),
+    2 => (0.0, 1.0, x),
+    3 => (0.0, x, 1.0),
+    4 => (x, 0.0, 1.0),
+    _ => (1.0, 0.0, x),
+  };
+  // keep every channel between 60 and 240 so lines stand out against black
+  Rgb([(60.0 + (180.0 * r)) as u8, (60.0 + (180.0 * g)) as u8, (60.0 + (180.0 * b)) as u8])
+}
+
+fn save_paths_image<I: Into<String>>(file_path: I, paths: &[(Vec<usize>, Rgb<u8>)], locations: &Vec<(usize, fp, fp)>, closed: bool) {
   let file_path = file_path.into();
   let (width, height) = (900, 900);
   let mut image = RgbImage::new(width + 15, height + 15); // width, height
@@ -730,9 +1131,8 @@ fn save_state_image<I: Into<String>>(file_path: I, path: &Vec<usize>, locations:
 
   let font = Font::try_from_bytes(include_bytes!("../resources/NotoSans-Bold.ttf")).unwrap();
 
-  for i in 0..locations.len() {
-    let loc = locations[i];
-    let (loc_x,loc_y) = scale_xy(width, height, x_range as u32, y_range as u32, smallest_x, smallest_y, loc.1, loc.2);
+  for (i, &loc) in locations.iter().enumerate() {
+    let (loc_x,loc_y) = scale_xy(width, height, x_range as u32, y_range as u32, smallest_x, smallest_y, (loc.1, loc.2));
 
     // Set all location pixels to be red // r,g,b
     //image.get_pixel_mut(loc_x, loc_y).data = [255, 0, 0];
@@ -742,37 +1142,58 @@ fn save_state_image<I: Into<String>>(file_path: I, path: &Vec<usize>, locations:
     // Also draw an index number
     let font_height = 18.0;
     let font_scale = Scale { x: font_height, y: font_height };
-    draw_text_mut(&mut image, Rgb([225, 225, 255]), loc_x as u32, loc_y as u32, font_scale, &font, format!("{}", i).as_str());
+    draw_text_mut(&mut image, Rgb([225, 225, 255]), loc_x, loc_y, font_scale, &font, format!("{}", i).as_str());
   }
 
-  for i in 0..path.len() {
-    let pt_from = path[i];
-    let pt_to =   path[(i+1) % path.len()];
-    //println!("pt_from = {}, pt_to = {}", pt_from, pt_to);
+  for (path, path_color) in paths.iter() {
+    let num_edges = if closed { path.len() } else { path.len().saturating_sub(1) };
+    for i in 0..num_edges {
+      let pt_from = path[i];
+      let pt_to =   path[(i+1) % path.len()];
+      //println!("pt_from = {}, pt_to = {}", pt_from, pt_to);
 
-    let from_loc = locations[pt_from];
-    let (from_loc_x,from_loc_y) = scale_xy(width, height, x_range as u32, y_range as u32, smallest_x, smallest_y, from_loc.1, from_loc.2);
+      let from_loc = locations[pt_from];
+      let (from_loc_x,from_loc_y) = scale_xy(width, height, x_range as u32, y_range as u32, smallest_x, smallest_y, (from_loc.1, from_loc.2));
 
-    let to_loc = locations[pt_to];
-    let (pt_to_x,pt_to_y) = scale_xy(width, height, x_range as u32, y_range as u32, smallest_x, smallest_y, to_loc.1, to_loc.2);
-    //println!("Going from {} to {}", pt_from, pt_to);
+      let to_loc = locations[pt_to];
+      let (pt_to_x,pt_to_y) = scale_xy(width, height, x_range as u32, y_range as u32, smallest_x, smallest_y, (to_loc.1, to_loc.2));
+      //println!("Going from {} to {}", pt_from, pt_to);
 
-    draw_line_segment_mut(&mut image,
-      (pt_to_x as f32,pt_to_y as f32), // start
-      (from_loc_x as f32,from_loc_y as f32), // end
-      Rgb([200, 200, 200])
-    );
+      draw_line_segment_mut(&mut image,
+        (pt_to_x as f32,pt_to_y as f32), // start
+        (from_loc_x as f32,from_loc_y as f32), // end
+        *path_color
+      );
+    }
+
+    if path.is_empty() {
+      continue;
+    }
+    let markers = if !closed {
+      vec![(path[0], Rgb([0, 220, 0])), (path[path.len() - 1], Rgb([60, 120, 255]))]
+    }
+    else if paths.len() > 1 {
+      vec![(path[0], Rgb([255, 255, 255]))]
+    }
+    else {
+      vec![]
+    };
+    for (citynum, color) in markers {
+      let loc = locations[citynum];
+      let (loc_x,loc_y) = scale_xy(width, height, x_range as u32, y_range as u32, smallest_x, smallest_y, (loc.1, loc.2));
+      draw_hollow_circle_mut(&mut image, (loc_x as i32, loc_y as i32), 14 /*radius*/, color);
+    }
   }
 
   // does the folder exist?
   let file_parent_dir = std::path::PathBuf::from(file_path.clone());
   let file_parent_dir = file_parent_dir.parent().expect("All image paths should have a parent");
-  std::fs::create_dir_all(&file_parent_dir).unwrap_or(());
+  std::fs::create_dir_all(file_parent_dir).unwrap_or(());
 
   image.save(file_path).unwrap();
 }
 
-fn save_state_image_center<I: Into<String>>(file_path: I, path: &Vec<usize>, locations: &Vec<(usize, fp, fp)>, center: &(fp, fp)) {
+fn save_state_image_center<I: Into<String>>(file_path: I, path: &[usize], locations: &Vec<(usize, fp, fp)>, center: &(fp, fp)) {
   let file_path = file_path.into();
   let (width, height) = (600, 600);
   let mut image = RgbImage::new(width + 5, height + 5); // width, height
@@ -783,9 +1204,8 @@ fn save_state_image_center<I: Into<String>>(file_path: I, path: &Vec<usize>, loc
 
   let font = Font::try_from_bytes(include_bytes!("../resources/NotoSans-Bold.ttf")).unwrap();
 
-  for i in 0..locations.len() {
-    let loc = locations[i];
-    let (loc_x,loc_y) = scale_xy(width, height, x_range as u32, y_range as u32, smallest_x, smallest_y, loc.1, loc.2);
+  for (i, &loc) in locations.iter().enumerate() {
+    let (loc_x,loc_y) = scale_xy(width, height, x_range as u32, y_range as u32, smallest_x, smallest_y, (loc.1, loc.2));
 
     // Set all location pixels to be red // r,g,b
     //image.get_pixel_mut(loc_x, loc_y).data = [255, 0, 0];
@@ -795,7 +1215,7 @@ fn save_state_image_center<I: Into<String>>(file_path: I, path: &Vec<usize>, loc
     // Also draw an index number
     let font_height = 14.0;
     let font_scale = Scale { x: font_height, y: font_height };
-    draw_text_mut(&mut image, Rgb([225, 225, 255]), loc_x as u32, loc_y as u32, font_scale, &font, format!("{}", i).as_str());
+    draw_text_mut(&mut image, Rgb([225, 225, 255]), loc_x, loc_y, font_scale, &font, format!("{}", i).as_str());
   }
 
   for i in 0..path.len() {
@@ -804,10 +1224,10 @@ fn save_state_image_center<I: Into<String>>(file_path: I, path: &Vec<usize>, loc
     //println!("pt_from = {}, pt_to = {}", pt_from, pt_to);
 
     let from_loc = locations[pt_from];
-    let (from_loc_x,from_loc_y) = scale_xy(width, height, x_range as u32, y_range as u32, smallest_x, smallest_y, from_loc.1, from_loc.2);
+    let (from_loc_x,from_loc_y) = scale_xy(width, height, x_range as u32, y_range as u32, smallest_x, smallest_y, (from_loc.1, from_loc.2));
 
     let to_loc = locations[pt_to];
-    let (pt_to_x,pt_to_y) = scale_xy(width, height, x_range as u32, y_range as u32, smallest_x, smallest_y, to_loc.1, to_loc.2);
+    let (pt_to_x,pt_to_y) = scale_xy(width, height, x_range as u32, y_range as u32, smallest_x, smallest_y, (to_loc.1, to_loc.2));
     //println!("Going from {} to {}", pt_from, pt_to);
 
     draw_line_segment_mut(&mut image,
@@ -818,13 +1238,13 @@ fn save_state_image_center<I: Into<String>>(file_path: I, path: &Vec<usize>, loc
   }
 
   // center is green cross
-  let (center_img_x, center_img_y) = scale_xy(width, height, x_range as u32, y_range as u32, smallest_x, smallest_y, center.0, center.1);
+  let (center_img_x, center_img_y) = scale_xy(width, height, x_range as u32, y_range as u32, smallest_x, smallest_y, (center.0, center.1));
   draw_cross_mut(&mut image, Rgb([0, 255, 0]), center_img_x as i32, center_img_y as i32);
 
   image.save(file_path).unwrap();
 }
 
-fn scale_xy(img_w: u32, img_h: u32, path_w: u32, path_h: u32, path_x_smallest: fp, path_y_smallest: fp, given_x: fp, given_y: fp) -> (u32, u32) {
+fn scale_xy(img_w: u32, img_h: u32, path_w: u32, path_h: u32, path_x_smallest: fp, path_y_smallest: fp, (given_x, given_y): (fp, fp)) -> (u32, u32) {
   let mut img_x = (given_x - path_x_smallest) * ((img_w as fp / path_w as fp) as fp);
   let mut img_y = (given_y - path_y_smallest) * ((img_h as fp / path_h as fp) as fp);
   if img_x < 5.0 {
@@ -839,7 +1259,7 @@ fn scale_xy(img_w: u32, img_h: u32, path_w: u32, path_h: u32, path_x_smallest: f
   if img_y > (img_h-5) as fp {
     img_y = (img_h-5) as fp;
   }
-  return (img_x as u32, img_y as u32);
+  (img_x as u32, img_y as u32)
 }
 
 // returns smallestX, largestY, largestX, smallestY
@@ -864,7 +1284,21 @@ fn get_point_extents(locations: &Vec<(usize, fp, fp)>) -> (fp, fp, fp, fp) {
       largest_y = y;
     }
   }
-  return (smallest_x, largest_y, largest_x, smallest_y);
+  (smallest_x, largest_y, largest_x, smallest_y)
+}
+
+// Tour length computed from coordinates, for cities too large for a weights matrix
+fn compute_dist_coords(node_coordinates: &[(usize, fp, fp)], path: &[usize]) -> fp {
+  let mut total: fp = 0.0;
+  for p_i in 0..path.len() {
+    total += coord_dist(&node_coordinates[path[p_i]], &node_coordinates[path[(p_i+1) % path.len()]]);
+  }
+  total
+}
+
+// Euclidean distance between 2 cities, for code that cannot afford a weights matrix
+fn coord_dist(a: &(usize, fp, fp), b: &(usize, fp, fp)) -> fp {
+  ( (a.1 - b.1).powf(2.0) + (a.2 - b.2).powf(2.0) ).sqrt()
 }
 
 fn compute_weight_coords(node_coordinates: &Vec<(usize, fp, fp)>) -> Vec<Vec<fp>> {
@@ -884,58 +1318,45 @@ fn compute_weight_coords(node_coordinates: &Vec<(usize, fp, fp)>) -> Vec<Vec<fp>
       weights.push(row_weight_v);
     }
   }
-  return weights;
+  weights
 }
 
-fn selective(min_cities_to_ignore: usize, max_cities_to_test: usize, thread_pool: &ThreadPool, gpu_adapter: &mut Option<wgpu::Adapter>,) {
-  println!("Performing selective failure from {} points to {} points...", min_cities_to_ignore, max_cities_to_test);
+fn selective(min_cities_to_ignore: usize, max_cities_to_test: usize, family: Family, jalgo_options: &SolveOptions, thread_pool: &ThreadPool, _gpu_adapter: &mut Option<wgpu::Adapter>,) {
+  println!("Performing selective failure from {} points to {} points ({} cities)...", min_cities_to_ignore, max_cities_to_test, family.name());
   // Bounding box for all points
 
   let mut rng = rand::thread_rng();
+  // Every city we may add, shuffled so the first few of a structured family are spread over its shape
+  let mut family_cities = generators::generate(family, max_cities_to_test, (x_min, x_max, y_min, y_max));
+  rng.shuffle(&mut family_cities);
   let mut node_coordinates: Vec<(usize, fp, fp)> = vec![];
 
   // Just add 3 to begin with
-  for i in 0..3 {
-    let new_r_city = (
-      i,
-      rng.gen_range(x_min, x_max),
-      rng.gen_range(y_min, y_max),
-    );
-    node_coordinates.push(new_r_city);
-  }
-
   // Don't bother solving short tours which are highly likely to be correctly solved by jeff_algo
   // We can always manually configure this parameter down if this discoveres a horrible failure and
   // we want to find the first city where stuff blows up.
-  for city_num in 3..min_cities_to_ignore {
-    let new_r_city = (
-      city_num,
-      rng.gen_range(x_min, x_max),
-      rng.gen_range(y_min, y_max),
-    );
-    node_coordinates.push(new_r_city);
+  for (city_num, &(_, x, y)) in family_cities.iter().enumerate().take(min_cities_to_ignore.max(3)) {
+    node_coordinates.push( (city_num, x, y) );
   }
 
   // If we hit 11 cities without a failure we'll recurse and start from min_cities_to_ignore again.
-  for city_num in min_cities_to_ignore..max_cities_to_test {
-    let new_r_city = (
-      city_num,
-      rng.gen_range(x_min, x_max),
-      rng.gen_range(y_min, y_max),
-    );
-    node_coordinates.push(new_r_city); // we can pop() if we fail
+  for (city_num, &(_, x, y)) in family_cities.iter().enumerate().skip(min_cities_to_ignore.max(3)) {
+    node_coordinates.push( (city_num, x, y) ); // we can pop() if we fail
 
     let city_weights = compute_weight_coords(&node_coordinates);
 
-    let jeff_sol = jeff_algo::solve(&node_coordinates, &city_weights, None);
+    let jeff_sol = jeff_algo::solve_with_options(&node_coordinates, &city_weights, None, jalgo_options);
     let brute_sol = brute_algo::solve(&node_coordinates, &city_weights, None, thread_pool);
 
     let jeff_sol_len = compute_dist(&city_weights, &jeff_sol);
     let brute_sol_len = compute_dist(&city_weights, &brute_sol);
     let distance_diff = jeff_sol_len - brute_sol_len;
 
-    if distance_diff.abs() > fp_epsilon && !is_identical_path(&jeff_sol, &brute_sol) { // account for floating point errors
+    if distance_diff.abs() > fp_epsilon && !is_identical_tour(&jeff_sol, &brute_sol, &city_weights) { // account for floating point errors
       println!("We have broken jeff_algo at {} points!", city_num+1);
+      if shrink::shrink_failures_env_val() {
+        shrink::shrink_and_report(&node_coordinates, jalgo_options, &output::path("selective/shrunk/"), thread_pool);
+      }
       // we have added a city which breaks things!
       node_coordinates.pop();
       let city_weights = compute_weight_coords(&node_coordinates);
@@ -943,8 +1364,8 @@ fn selective(min_cities_to_ignore: usize, max_cities_to_test: usize, thread_pool
       // Now we have a city right before our failure.
 
       // Save the correct solution
-      brute_algo::solve_all(&node_coordinates, &city_weights, Some("./views/selective/".to_string()), thread_pool);
-      jeff_algo::solve(&node_coordinates, &city_weights, Some("./views/selective/".to_string()));
+      brute_algo::solve_all(&node_coordinates, &city_weights, Some(output::path("selective/")), thread_pool);
+      jeff_algo::solve_with_options(&node_coordinates, &city_weights, Some(output::path("selective/")), jalgo_options);
 
       // compute a 2d matrix of points and plot blue if they result in correct, red if they do not.
       // perform_matrix_image_gen("./views/selective-map.png", node_coordinates, city_weights, );
@@ -956,27 +1377,73 @@ fn selective(min_cities_to_ignore: usize, max_cities_to_test: usize, thread_pool
   }
 
   println!("Failed to break after {}, resetting...", max_cities_to_test);
-  selective(min_cities_to_ignore, max_cities_to_test, thread_pool, gpu_adapter);
+  selective(min_cities_to_ignore, max_cities_to_test, family, jalgo_options, thread_pool, _gpu_adapter);
 
 }
 
+// true when both tours use the same edges, in either direction
 fn is_identical_path(path_a: &[usize], path_b: &[usize]) -> bool {
+  identical_path_walk(path_a, path_b, true)
+}
+
+// true when both tours visit the cities in the same order, only the starting city may differ;
+// asymmetric tours walked backwards cost something else so they are different tours
+fn is_identical_directed_path(path_a: &[usize], path_b: &[usize]) -> bool {
+  identical_path_walk(path_a, path_b, false)
+}
+
+// Tour identity for these weights, reversals only count as the same tour when weights are symmetric
+fn is_identical_tour(path_a: &[usize], path_b: &[usize], weights: &[Vec<fp>]) -> bool {
+  if weights_are_symmetric(weights) {
+    return is_identical_path(path_a, path_b);
+  }
+  is_identical_directed_path(path_a, path_b)
+}
+
+// True when weights are exactly what compute_weight_coords makes of node_coordinates, the only
+// case where a result can be looked up by coordinates alone.
+fn weights_match_coordinates(node_coordinates: &[(CityNum, CityXYCoord, CityXYCoord)], weights: &[Vec<CityWeight>]) -> bool {
+  if weights.len() != node_coordinates.len() {
+    return false;
+  }
+  for a in 0..weights.len() {
+    for b in 0..weights.len() {
+      if (weights[a][b] - coord_dist(&node_coordinates[a], &node_coordinates[b])).abs() > fp_epsilon {
+        return false;
+      }
+    }
+  }
+  true
+}
+
+fn weights_are_symmetric(weights: &[Vec<fp>]) -> bool {
+  for (a, row_a) in weights.iter().enumerate() {
+    for (b, row_b) in weights.iter().enumerate().skip(a+1) {
+      if (row_a[b] - row_b[a]).abs() > fp_epsilon {
+        return false;
+      }
+    }
+  }
+  true
+}
+
+fn identical_path_walk(path_a: &[usize], path_b: &[usize], walk_backwards: bool) -> bool {
   if path_a.len() != path_b.len() {
     return false; // duh
   }
 
   let mut smallest_usize_in_a = usize::MAX;
   let mut smallest_usize_idx_in_a = 0;
-  for i in 0..path_a.len() {
-    if path_a[i] < smallest_usize_in_a {
-      smallest_usize_in_a = path_a[i];
+  for (i, &city) in path_a.iter().enumerate() {
+    if city < smallest_usize_in_a {
+      smallest_usize_in_a = city;
       smallest_usize_idx_in_a = i;
     }
   }
 
   let mut smallest_usize_idx_in_b = 0;
-  for i in 0..path_b.len() {
-    if path_b[i] == smallest_usize_in_a {
+  for (i, &city) in path_b.iter().enumerate() {
+    if city == smallest_usize_in_a {
       smallest_usize_idx_in_b = i;
       break;
     }
@@ -985,50 +1452,41 @@ fn is_identical_path(path_a: &[usize], path_b: &[usize]) -> bool {
   // Both lists now have a begin index at their smallest value (assume 0)
   // we walk them & compare values; if any are not equal then these have
   // different orders!
-  for i in 0..path_a.len() {
-    if path_a[(i+smallest_usize_idx_in_a) % path_a.len()] != path_b[(i+smallest_usize_idx_in_b) % path_b.len()] {
-      return false; // Not identical b/c values differ!
+  let len = path_a.len();
+  let mut identical_forwards = true;
+  for i in 0..len {
+    if path_a[(i+smallest_usize_idx_in_a) % len] != path_b[(i+smallest_usize_idx_in_b) % len] {
+      identical_forwards = false; // Not identical b/c values differ!
+      break;
     }
   }
+  if identical_forwards {
+    return true; // identical b/c all path_a[i+] == path_b[i+]
+  }
+  if !walk_backwards {
+    return false;
+  }
 
-  return true; // identical b/c all path_a[i+] == path_b[i+]
-}
-
-fn get_env_or_random_node_coordinates(n: usize, env_var_name: &str, _x_min: fp, _x_max: fp, _y_min: fp, _y_max: fp) -> Vec<(usize, fp, fp)> {
-  let mut rng = rand::thread_rng();
-  let mut node_coordinates: Vec<(usize, fp, fp)> = vec![];
-  // Create random set of points OR parse from env var
-  match env::var(env_var_name) {
-    Ok(initial_coords_s) => {
-      // initial_coords_s == "5.12,6.8 4.8,4.9, 1.2,1.3"
-      let pairs: Vec<&str> = initial_coords_s.split(" ").collect();
-      for i in 0..n {
-        let x_and_y_s: Vec<&str> = pairs[i].split(",").collect();
-        let x: fp = x_and_y_s[0].parse().expect("TSP_INITIAL_COORDS did not contain a number");
-        let y: fp = x_and_y_s[1].parse().expect("TSP_INITIAL_COORDS did not contain a number");
-        let new_r_city = (
-          i, x, y
-        );
-        node_coordinates.push(new_r_city);
-      }
-    }
-    Err(_) => {
-      for i in 0..n {
-        let new_r_city = (
-          i,
-          rng.gen_range(_x_min, _x_max),
-          rng.gen_range(_y_min, _y_max),
-        );
-        node_coordinates.push(new_r_city);
-      }
+  // A tour walked backwards visits the same edges, so walk path_b the other way from the same start
+  for i in 0..len {
+    if path_a[(i+smallest_usize_idx_in_a) % len] != path_b[(smallest_usize_idx_in_b + len - i) % len] {
+      return false;
     }
   }
-  return node_coordinates;
+
+  true // identical b/c all path_a[i+] == path_b[i-]
 }
 
-fn spray(n: usize, mut bound_granularity: fp, thread_pool: &ThreadPool, gpu_adapter: &mut Option<wgpu::Adapter>,) {
-  println!("Spraying {} cities...", n);
+fn spray(scenario: &Scenario, one_step: bool, thread_pool: &ThreadPool, gpu_adapter: &mut Option<wgpu::Adapter>,) {
+  let n = scenario.cities.len();
+  if one_step {
+    println!("Spraying {} cities, inserting each point with a single jeff_algo::next_step...", n);
+  }
+  else {
+    println!("Spraying {} cities...", n);
+  }
 
+  let mut bound_granularity = scenario.granularity;
   if bound_granularity < 0.025 {
     println!("Resetting {} to 0.025 because that's the size of a single pixel...", bound_granularity);
     bound_granularity = 0.025;
@@ -1036,28 +1494,29 @@ fn spray(n: usize, mut bound_granularity: fp, thread_pool: &ThreadPool, gpu_adap
   let bound_granularity = bound_granularity;
 
 
-  let node_coordinates: Vec<(usize, fp, fp)> = get_env_or_random_node_coordinates(n, "TSP_INITIAL_COORDS", x_min, x_max, y_min, y_max);
+  let node_coordinates: Vec<(usize, fp, fp)> = scenario.node_coordinates();
   println!("Initial node_coordinates={:?}", &node_coordinates);
 
   // Generate partial image
-  let file_path = "views/spray.png";
+  let file_path = output::path("spray.png");
+  scenario.save(output::path("spray.scenario.json"));
   let (width, height) = (900, 900);
   let mut image = RgbImage::new(width + 15, height + 15); // width, height
 
-  let (smallest_x, largest_y, largest_x, smallest_y) = (x_min_bound, y_max_bound, x_max_bound, y_min_bound);
+  let (smallest_x, largest_y, largest_x, smallest_y) = (scenario.bounds.x_min, scenario.bounds.y_max, scenario.bounds.x_max, scenario.bounds.y_min);
   let x_range: fp = largest_x - smallest_x;
   let y_range: fp = largest_y - smallest_y;
 
   // Use jalgo to compute the first N-1 insertions...
   let city_weights = compute_weight_coords(&node_coordinates);
-  let first_ordered_visits = jeff_algo::solve(&node_coordinates, &city_weights, None);
+  let first_ordered_visits = jeff_algo::solve_with_options(&node_coordinates, &city_weights, None, &scenario.jalgo_options());
 
   let brute_sol = brute_algo::solve(&node_coordinates, &city_weights, None, thread_pool);
   // If jeff disagrees w/ brute, the rest of the loop does not make sense!
   let first_ordered_visits_len = compute_dist(&city_weights, &first_ordered_visits);
   let brute_sol_len = compute_dist(&city_weights, &brute_sol);
   let distance_diff = first_ordered_visits_len - brute_sol_len;
-  if distance_diff.abs() > fp_epsilon && !is_identical_path(&first_ordered_visits, &brute_sol) {
+  if distance_diff.abs() > fp_epsilon && !is_identical_tour(&first_ordered_visits, &brute_sol, &city_weights) {
     println!("Refusing to spray; jeff_sol={:?} ({}) and brute_sol={:?} ({}) are already broken!",
       first_ordered_visits, first_ordered_visits_len, brute_sol, brute_sol_len
     );
@@ -1070,15 +1529,15 @@ fn spray(n: usize, mut bound_granularity: fp, thread_pool: &ThreadPool, gpu_adap
 
   let mut num_failures = 0;
 
-  let mut point_y = y_min_bound;
+  let mut point_y = smallest_y;
   loop {
-    if point_y > y_max_bound {
+    if point_y > largest_y {
       break;
     }
 
-    let mut point_x = x_min_bound;
+    let mut point_x = smallest_x;
     loop {
-      if point_x > x_max_bound {
+      if point_x > largest_x {
         break;
       }
 
@@ -1092,8 +1551,14 @@ fn spray(n: usize, mut bound_granularity: fp, thread_pool: &ThreadPool, gpu_adap
 
       //let jeff_sol = jeff_algo::solve(&node_coordinates, &city_weights, None);
       //println!("=============");
-      //let jeff_sol = jeff_algo::next_step(&first_ordered_visits, &node_coordinates, &city_weights, &None);
-      let jeff_sol = jeff_algo::solve(&node_coordinates, &city_weights, None);
+      let jeff_sol = if one_step {
+        // Only the sprayed point moves, the N-1 tour above is kept as-is
+        let sprayed_citynum = node_coordinates.len() - 1;
+        jeff_algo::next_step(&first_ordered_visits, &node_coordinates, &city_weights, &|_ordered_visits, _weights| sprayed_citynum)
+      }
+      else {
+        jeff_algo::solve_with_options(&node_coordinates, &city_weights, None, &scenario.jalgo_options())
+      };
       //println!("jeff_sol={:?}", &jeff_sol);
 
       let brute_sol = brute_algo::solve(&node_coordinates, &city_weights, None, thread_pool);
@@ -1104,9 +1569,9 @@ fn spray(n: usize, mut bound_granularity: fp, thread_pool: &ThreadPool, gpu_adap
       //println!("jeff_sol_len={}   brute_sol_len={}  distance_diff={}", jeff_sol_len, brute_sol_len, distance_diff);
 
       let loc = (point_x, point_y);
-      let (loc_x,loc_y) = scale_xy(width, height, x_range as u32, y_range as u32, smallest_x, smallest_y, loc.0, loc.1);
+      let (loc_x,loc_y) = scale_xy(width, height, x_range as u32, y_range as u32, smallest_x, smallest_y, (loc.0, loc.1));
 
-      if distance_diff.abs() > fp_epsilon && !is_identical_path(&jeff_sol, &brute_sol) {
+      if distance_diff.abs() > fp_epsilon && !is_identical_tour(&jeff_sol, &brute_sol, &city_weights) {
         // jalgo broke, paint red pixel
         *image.get_pixel_mut(loc_x, loc_y) = Rgb([255, 0, 0]);
         {
@@ -1115,10 +1580,10 @@ fn spray(n: usize, mut bound_granularity: fp, thread_pool: &ThreadPool, gpu_adap
           *image.get_pixel_mut(loc_x, loc_y+1) = Rgb([255, 0, 0]);
         }
         num_failures += 1;
-        // Also save a copy of the state in views/spray-jalgo*
+        // Also save a copy of the state in OUT_DIR/spray-jalgo*
         // BUT only if bound_granularity > 0.1 as a performance improvement to high-res sprays
         if bound_granularity >= 0.2 {
-          let prefix_dir = format!("./views/spray-jalgo-f{:03}", num_failures);
+          let prefix_dir = output::path(&format!("spray-jalgo-f{:03}", num_failures));
 
           // Debugging jeff_algo::next_step(&first_ordered_visits, &node_coordinates, &city_weights, &Some(format!("{}-jeff-next_step", prefix_dir.clone() ) ));
           //jeff_algo::solve(&node_coordinates, &city_weights, Some(prefix_dir.clone()));
@@ -1127,12 +1592,9 @@ fn spray(n: usize, mut bound_granularity: fp, thread_pool: &ThreadPool, gpu_adap
           // Also dump brute_algo solutions for node_coordinates N-1, n-2, etc... until 3
 
           for i in 3..(node_coordinates.len()+1) {
-            let mut delta_node_coords = vec![];
-            for j in 0..i {
-              delta_node_coords.push( node_coordinates[j] );
-            }
+            let delta_node_coords = node_coordinates[..i].to_vec();
             let city_weights = compute_weight_coords(&delta_node_coords);
-            jeff_algo::solve(&delta_node_coords, &city_weights, Some(prefix_dir.clone()));
+            jeff_algo::solve_with_options(&delta_node_coords, &city_weights, Some(prefix_dir.clone()), &scenario.jalgo_options());
             brute_algo::solve_all(&delta_node_coords, &city_weights, Some(prefix_dir.clone()), thread_pool);
           }
 
@@ -1151,9 +1613,8 @@ fn spray(n: usize, mut bound_granularity: fp, thread_pool: &ThreadPool, gpu_adap
 
   let font = Font::try_from_bytes(include_bytes!("../resources/NotoSans-Bold.ttf")).unwrap();
 
-  for i in 0..node_coordinates.len() {
-    let loc = node_coordinates[i];
-    let (loc_x,loc_y) = scale_xy(width, height, x_range as u32, y_range as u32, smallest_x, smallest_y, loc.1, loc.2);
+  for (i, &loc) in node_coordinates.iter().enumerate() {
+    let (loc_x,loc_y) = scale_xy(width, height, x_range as u32, y_range as u32, smallest_x, smallest_y, (loc.1, loc.2));
 
     // Set all location pixels to be red // r,g,b
     //image.get_pixel_mut(loc_x, loc_y).data = [255, 0, 0];
@@ -1164,39 +1625,40 @@ fn spray(n: usize, mut bound_granularity: fp, thread_pool: &ThreadPool, gpu_adap
 
     let font_height = 18.0;
     let font_scale = Scale { x: font_height, y: font_height };
-    draw_text_mut(&mut image, Rgb([225, 225, 255]), loc_x as u32, loc_y as u32, font_scale, &font, format!("{}", i).as_str());
+    draw_text_mut(&mut image, Rgb([225, 225, 255]), loc_x, loc_y, font_scale, &font, format!("{}", i).as_str());
   }
 
-  // Finally write image to views/spray.png
-  if let Err(e) = image.save(file_path) {
-    println!("Please create the directory ./views/ before running tests!");
+  // Finally write image to OUT_DIR/spray.png
+  if let Err(e) = image.save(&file_path) {
+    println!("Error writing {}: {}", file_path, e);
   }
 
   println!("{} failures", num_failures);
 
 }
 
-fn pattern_scan(n: usize, bound_granularity: fp, file_path: &str, thread_pool: &ThreadPool, gpu_adapter: &mut Option<wgpu::Adapter>) {
-  let node_coordinates: Vec<(usize, fp, fp)> = get_env_or_random_node_coordinates(n, "TSP_INITIAL_COORDS", x_min, x_max, y_min, y_max);
-  pattern_scan_coords(n, bound_granularity, file_path, node_coordinates, thread_pool, gpu_adapter, nop_closure);
+fn pattern_scan(scenario: &Scenario, file_path: &str, thread_pool: &ThreadPool, gpu_adapter: &mut Option<wgpu::Adapter>) {
+  scenario.save(Path::new(file_path).with_extension("scenario.json"));
+  pattern_scan_coords(scenario.cities.len(), scenario, file_path, scenario.node_coordinates(), thread_pool, gpu_adapter, nop_closure);
 }
 
 
 //fn nop_closure() { } // apparenty Option<<Fn() -> ()>>::None is annoying to construct as a type
-fn nop_closure(a: &Vec<Vec<CityWeight>>, b: &Vec<CityNum>, c: &(fp, fp), d: &(u8, u8, u8)) { }
+fn nop_closure(a: &[Vec<CityWeight>], b: &[CityNum], c: &(fp, fp), d: &(u8, u8, u8)) { }
 
 fn pattern_scan_coords<F>(
   n: usize,
-  mut bound_granularity: fp,
+  scenario: &Scenario,
   file_path: &str,
   node_coordinates: Vec<(usize, fp, fp)>,
   thread_pool: &ThreadPool,
   gpu_adapter: &mut Option<wgpu::Adapter>,
   mut addtl_logging_fn: F,
-) -> ()
-  where F: std::ops::FnMut(&Vec<Vec<CityWeight>>, &Vec<CityNum>, &(fp, fp), &(u8, u8, u8)) -> (),
+)
+  where F: std::ops::FnMut(&[Vec<CityWeight>], &[CityNum], &(fp, fp), &(u8, u8, u8)) -> (),
 {
   println!("Pattern scanning {} cities...", n);
+  let mut bound_granularity = scenario.granularity;
   if bound_granularity < 0.010 {
     println!("Resetting {} to 0.010 because that's the size of a single pixel...", bound_granularity);
     bound_granularity = 0.010;
@@ -1210,7 +1672,7 @@ fn pattern_scan_coords<F>(
   let (width, height) = (900, 900);
   let mut image = RgbImage::new(width + 15, height + 15); // width, height
 
-  let (smallest_x, largest_y, largest_x, smallest_y) = (x_min_bound, y_max_bound, x_max_bound, y_min_bound);
+  let (smallest_x, largest_y, largest_x, smallest_y) = (scenario.bounds.x_min, scenario.bounds.y_max, scenario.bounds.x_max, scenario.bounds.y_min);
   let x_range: fp = largest_x - smallest_x;
   let y_range: fp = largest_y - smallest_y;
 
@@ -1218,6 +1680,12 @@ fn pattern_scan_coords<F>(
 
   let mut unique_solution_spaces_points: HashMap<(u8, u8, u8), Vec<(fp, fp)>> = HashMap::new();
 
+  // Everything the `serve` viewer needs to redraw this scan, written next to the image
+  let scan_data_path = Path::new(file_path).with_extension("json");
+  let scan_name = scan_data_path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+  let mut scan_data = viewer::ScanData::new(&scan_name, &node_coordinates, scenario.bounds_tuple(), bound_granularity);
+  scan_data.initial_solution = brute_algo::solve(&node_coordinates, &city_weights, None, thread_pool);
+
   // If we get >1 brute solutions, pick next in line % all.
   // This is more deterministic than picking at random and produces a noticable checker pattern
   // on areas with 2+ solutions due to weight symmetry
@@ -1226,9 +1694,9 @@ fn pattern_scan_coords<F>(
   // If you get stripes instead of checkers, toggle the value of INCREMENT_NONCE_ON_ROW env variable to get the other pattern.
   let increment_nonce_on_row = env::var("INCREMENT_NONCE_ON_ROW").unwrap_or("f".to_string()).contains("t");
 
-  let mut point_y = y_min_bound;
+  let mut point_y = smallest_y;
   loop {
-    if point_y > y_max_bound {
+    if point_y > largest_y {
       break;
     }
 
@@ -1236,9 +1704,9 @@ fn pattern_scan_coords<F>(
       brute_sol_nonce += 1; // bump so exactly-two are staggered at each row
     }
 
-    let mut point_x = x_min_bound;
+    let mut point_x = smallest_x;
     loop {
-      if point_x > x_max_bound {
+      if point_x > largest_x {
         break;
       }
 
@@ -1250,7 +1718,10 @@ fn pattern_scan_coords<F>(
 
       let city_weights = compute_weight_coords(&node_coordinates);
 
-      let brute_solutions = brute_algo::solve_all(&node_coordinates, &city_weights, None, thread_pool);
+      let brute_solutions = match scenario.solver {
+        ScenarioSolver::Brute => brute_algo::solve_all(&node_coordinates, &city_weights, None, thread_pool),
+        ScenarioSolver::Jalgo => vec![ jeff_algo::solve_with_options(&node_coordinates, &city_weights, None, &scenario.jalgo_options()) ],
+      };
       let num_sols: i32 = brute_solutions.len() as i32;
       //let rand_idx: i32 = rand::thread_rng().gen_range(0, num_sols);
       //let brute_sol: Vec<CityNum> = brute_solutions[ rand_idx as usize ].clone(); // Vec<CityNum>
@@ -1259,16 +1730,14 @@ fn pattern_scan_coords<F>(
       brute_sol_nonce += 1;
 
       let loc = (point_x, point_y);
-      let (loc_x,loc_y) = scale_xy(width, height, x_range as u32, y_range as u32, smallest_x, smallest_y, loc.0, loc.1);
+      let (loc_x,loc_y) = scale_xy(width, height, x_range as u32, y_range as u32, smallest_x, smallest_y, (loc.0, loc.1));
 
       // Paint according to brute_sol order
       let (r, g, b) = path_to_rgb(&brute_sol, &city_weights);
 
       let rgb_key = (r, g, b);
-      if !unique_solution_spaces_points.contains_key(&rgb_key) {
-        unique_solution_spaces_points.insert(rgb_key, vec![]);
-      }
-      unique_solution_spaces_points.get_mut(&rgb_key).map(|key_vec| { key_vec.push( (point_x, point_y) ); });
+      unique_solution_spaces_points.entry(rgb_key).or_default().push( (point_x, point_y) );
+      scan_data.add_sample(point_x, point_y, &brute_sol, &rgb_key, compute_dist(&city_weights, &brute_sol));
 
       addtl_logging_fn(
         &city_weights,
@@ -1297,9 +1766,8 @@ fn pattern_scan_coords<F>(
 
   let font = Font::try_from_bytes(include_bytes!("../resources/NotoSans-Bold.ttf")).unwrap();
 
-  for i in 0..node_coordinates.len() {
-    let loc = node_coordinates[i];
-    let (loc_x,loc_y) = scale_xy(width, height, x_range as u32, y_range as u32, smallest_x, smallest_y, loc.1, loc.2);
+  for (i, &loc) in node_coordinates.iter().enumerate() {
+    let (loc_x,loc_y) = scale_xy(width, height, x_range as u32, y_range as u32, smallest_x, smallest_y, (loc.1, loc.2));
 
     // Set all location pixels to be red // r,g,b
     //image.get_pixel_mut(loc_x, loc_y).data = [255, 0, 0];
@@ -1310,7 +1778,7 @@ fn pattern_scan_coords<F>(
 
     let font_height = 18.0;
     let font_scale = Scale { x: font_height, y: font_height };
-    draw_text_mut(&mut image, Rgb([225, 225, 255]), loc_x as u32, loc_y as u32, font_scale, &font, format!("{}", i).as_str());
+    draw_text_mut(&mut image, Rgb([225, 225, 255]), loc_x, loc_y, font_scale, &font, format!("{}", i).as_str());
   }
 
   // Get average of all points over unique_solution_spaces_points
@@ -1332,7 +1800,7 @@ fn pattern_scan_coords<F>(
     let avg_y = sum_y / (inserted_points.len() as fp);
 
     // Draw text
-    let (loc_x,loc_y) = scale_xy(width, height, x_range as u32, y_range as u32, smallest_x, smallest_y, avg_x, avg_y);
+    let (loc_x,loc_y) = scale_xy(width, height, x_range as u32, y_range as u32, smallest_x, smallest_y, (avg_x, avg_y));
     let rgb_text = format!("{:02x}{:02x}{:02x}", rgb_key.0, rgb_key.1, rgb_key.2 );
     let font_height = 18.0;
     let font_scale = Scale { x: font_height, y: font_height };
@@ -1362,25 +1830,22 @@ fn pattern_scan_coords<F>(
       }
     }
 
-    space_label_y_coords.push(loc_y as u32);
-    draw_text_mut(&mut image, Rgb([225, 225, 255]), loc_x as u32, loc_y as u32, font_scale, &font, rgb_text.as_str());
+    space_label_y_coords.push(loc_y);
+    draw_text_mut(&mut image, Rgb([225, 225, 255]), loc_x, loc_y, font_scale, &font, rgb_text.as_str());
 
     let mut node_coordinates = node_coordinates.clone(); // Prevent us from mutating the initial set of points
     node_coordinates.push(
       (node_coordinates.len(), avg_x, avg_y)
     );
 
-    // Also compute brute force for avg_x, avg_y and store under views/pattern-scan-{rgb_text}-center/
+    // Also compute brute force for avg_x, avg_y and store under pattern-scan/{rgb_text}-center/ next to the image
 
-    let parent_prefix_dir = format!("views/{}", file_path_name);
+    let parent_prefix_dir = Path::new(file_path).with_file_name(&file_path_name);
     std::fs::create_dir_all(&parent_prefix_dir).unwrap_or(());
 
-    let prefix_dir = format!("views/{}/{}-center", file_path_name, rgb_text);
+    let prefix_dir = format!("{}/{}-center", parent_prefix_dir.display(), rgb_text);
     for i in 3..(node_coordinates.len()+1) {
-      let mut delta_node_coords = vec![];
-      for j in 0..i {
-        delta_node_coords.push( node_coordinates[j] );
-      }
+      let delta_node_coords = node_coordinates[..i].to_vec();
       let city_weights = compute_weight_coords(&delta_node_coords);
       brute_algo::solve_all(&delta_node_coords, &city_weights, Some(prefix_dir.clone()), thread_pool);
     }
@@ -1389,70 +1854,45 @@ fn pattern_scan_coords<F>(
 
 
 
-  // Finally write image to views/pattern_scan.png
+  scan_data.save(&scan_data_path);
+
+  // Finally write image to OUT_DIR/pattern-scan.png
   if let Err(e) = image.save(file_path) {
-    println!("Please create the directory ./views/ before running tests!");
+    println!("Error writing {}: {}", file_path, e);
   }
 
   println!("{} unique solutions found + plotted!", unique_solution_spaces_points.len());
 
 }
 
-fn multi_pattern_scan(n: usize, bound_granularity: fp, num_multi_steps_to_scan: usize, thread_pool: &ThreadPool, gpu_adapter: &mut Option<wgpu::Adapter>,) {
+fn multi_pattern_scan(scenario: &Scenario, thread_pool: &ThreadPool, gpu_adapter: &mut Option<wgpu::Adapter>,) {
+  let n = scenario.cities.len();
+  let num_multi_steps_to_scan = scenario.steps;
   println!("Muti-pattern scanning {} cities...", n);
 
-  let node_coordinates_a: Vec<(usize, fp, fp)> = get_env_or_random_node_coordinates(n, "TSP_INITIAL_COORDS", x_min, x_max, y_min, y_max);
-  println!("Initial node_coordinates_a={:?}", &node_coordinates_a);
-
-  let node_coordinates_b: Vec<(usize, fp, fp)> = get_env_or_random_node_coordinates(n, "TSP_ENDING_COORDS", x_min, x_max, y_min, y_max);
-  println!("Initial node_coordinates_b={:?}", &node_coordinates_b);
+  for (i, keyframe) in scenario.keyframe_coordinates().iter().enumerate() {
+    println!("Keyframe {} node_coordinates={:?}", i, keyframe);
+  }
+  scenario.save(output::path("multi-pattern-scan.scenario.json"));
 
   let mut output_scan_files = vec![];
 
   for multi_step_i in 0..=num_multi_steps_to_scan {
-    let converged_cities = converge_coordinates(&node_coordinates_a, &node_coordinates_b, multi_step_i, num_multi_steps_to_scan);
-    let converged_cities_weights = compute_weight_coords(&converged_cities);
-    let initial_solution = brute_algo::solve(&converged_cities, &converged_cities_weights, None, thread_pool);
-    let output_multiscan_file_path = format!("views/multi-pattern-scan-{:03}.png", multi_step_i);
-    let html_path = format!("views/multi-pattern-scan-{:03}.html", multi_step_i);
-    let mut html_content = HTML_BEGIN.to_string();
+    let converged_cities = scenario.frame_coordinates(multi_step_i, num_multi_steps_to_scan);
+    let output_multiscan_file_path = output::path(&format!("multi-pattern-scan-{:03}.png", multi_step_i));
 
     // [(x, y, rgb_usize, ) ... ]
     let mut tsp_point_colors: Vec<(fp, fp, usize)> = vec![];
 
-    pattern_scan_coords(n, bound_granularity, &output_multiscan_file_path, converged_cities.clone(), thread_pool, gpu_adapter, |city_weights, brute_sol, (tsp_point_x, tsp_point_y), rgb_key| {
-      let point_x: isize = (tsp_point_x * HTML_POINT_SCALE) as isize;
-      let point_y: isize = (tsp_point_y * HTML_POINT_SCALE) as isize;
-
-      let mut c = "".to_string();
-      for city_num in brute_sol.iter() {
-        if *city_num < converged_cities.len() {
-          c += format!("{},{} ", (converged_cities[*city_num].1 * HTML_POINT_SCALE) as isize, (converged_cities[*city_num].2 * HTML_POINT_SCALE) as isize).as_str();
-        }
-        else { // it's the new one
-          c += format!("{},{} ", point_x, point_y).as_str();
-        }
-      }
-
-      let city_weights = normalize_weights(city_weights);
-      html_content += format!(
-        "<div style=\"width:5px;height:5px;position:absolute;left:{}px;top:{}px;background-color:#{:02x}{:02x}{:02x}\" onclick=\"draw_path(this);\" c=\"{}\">{}</div>",
-        point_x,point_y,
-        rgb_key.0, rgb_key.1, rgb_key.2,
-        c, // coordinated mapped to display coords, ought to match everything else
-        html_format_tour_details(&city_weights, brute_sol).as_str()
-      ).as_str();
-
+    pattern_scan_coords(n, scenario, &output_multiscan_file_path, converged_cities.clone(), thread_pool, gpu_adapter, |city_weights, brute_sol, (tsp_point_x, tsp_point_y), rgb_key| {
       let rgb_key: usize = ((rgb_key.0 as usize) << 16) + ((rgb_key.1 as usize) << 8) + (rgb_key.2 as usize);
       tsp_point_colors.push(
         (*tsp_point_x, *tsp_point_y, rgb_key)
       );
-
-
     });
 
     // We know tsp_point_colors contains a square, so compute width & height so we can index into neighbors for edge detection
-    let tsp_square_size: isize = (f64::sqrt(tsp_point_colors.len() as f64) as isize /*+ 1*/) as isize;
+    let tsp_square_size: isize = f64::sqrt(tsp_point_colors.len() as f64) as isize;
 
     for y in 0..tsp_square_size {
       for x in 0..tsp_square_size {
@@ -1491,35 +1931,14 @@ fn multi_pattern_scan(n: usize, bound_granularity: fp, num_multi_steps_to_scan:
 
 
 
-    // Overlap tested N+1 points w/ beginning coordinates
-    for (city_num, city_x, city_y) in converged_cities.iter() {
-      let city_x: isize = (city_x * HTML_POINT_SCALE) as isize;
-      let city_y: isize = (city_y * HTML_POINT_SCALE) as isize;
-      html_content += format!(
-        "<div style=\"width:24px;height:24px;position:absolute;left:{}px;top:{}px;background:transparent;border: 3px solid red;border-radius:99px;font-weight:bold;pointer-events:none;\">{}</div>",
-        city_x - 12, city_y - 12, city_num
-      ).as_str();
-    }
-
-    let mut c = "".to_string();
-    for city_num in initial_solution.iter() {
-      c += format!("{},{} ", (converged_cities[*city_num].1 * HTML_POINT_SCALE) as isize, (converged_cities[*city_num].2 * HTML_POINT_SCALE) as isize).as_str();
-    }
-
-    html_content += format!("<pre style=\"position:absolute;top:1600px;height:350px;\" id=\"initial-sol\" c=\"{}\">initial_solution = {:?}</pre>", c, &initial_solution).as_str();
-    html_content += HTML_END;
-
-    let mut f = File::create(&html_path).expect("Unable to create file");
-    f.write_all(html_content.as_bytes()).expect("Unable to write data");
-
     output_scan_files.push(output_multiscan_file_path);
   }
 
-  let gif_output_file = "views/multi-pattern-scan.gif";
+  let gif_output_file = output::path("multi-pattern-scan.gif");
 
   let images = engiffen::load_images(&output_scan_files);
-  if let Ok(gif_data) = engiffen::engiffen(&images, 5 /*fps*/, engiffen::Quantizer::Naive ) {
-    if let Ok(mut output_f) = File::create(gif_output_file) {
+  if let Ok(gif_data) = engiffen::engiffen(&images, scenario.fps, engiffen::Quantizer::Naive ) {
+    if let Ok(mut output_f) = File::create(&gif_output_file) {
       if let Err(e) = gif_data.write(&mut output_f) {
         eprintln!("Error writing to {}: {:?}", gif_output_file, e);
       }
@@ -1527,46 +1946,39 @@ fn multi_pattern_scan(n: usize, bound_granularity: fp, num_multi_steps_to_scan:
   }
   println!("See {}", gif_output_file);
 
-
-}
-
-fn converge_coordinates(a: &Vec<(usize, fp, fp)>, b: &Vec<(usize, fp, fp)>, step_num: usize, total_steps: usize) -> Vec<(usize, fp, fp)> {
-  if a.len() != b.len() {
-    panic!("a.len() = {} and b.len() = {}", a.len(), b.len());
+  // The gif palette merges similar region colors, the apng + frame sequence keep every pixel
+  let apng_output_file = output::path("multi-pattern-scan.apng");
+  match animation::write_apng(&output_scan_files, &apng_output_file, scenario.fps) {
+    Ok(()) => println!("See {}", apng_output_file),
+    Err(e) => println!("{}", e),
   }
-  let mut converged = vec![];
-  for i in 0..a.len() {
-    converged.push(
-      converge_coords(a[i], b[i], step_num, total_steps)
-    );
+
+  let frame_dir = output::path("multi-pattern-scan-frames");
+  match animation::write_frame_sequence(&output_scan_files, &frame_dir, scenario.fps) {
+    Ok(sequence) => {
+      println!("Wrote {} frames to {}, make a video with:", sequence.frames.len(), frame_dir);
+      println!("  {}", sequence.ffmpeg);
+    }
+    Err(e) => println!("{}", e),
   }
-  converged
-}
+  println!("Browse the frames with: tsp-sol serve {}", output::run_dir().display());
 
-fn converge_coords(a: (usize, fp, fp), b: (usize, fp, fp), step_num: usize, total_steps: usize) -> (usize, fp, fp) {
-  let a_weight = (total_steps - step_num) as fp / total_steps as fp;
-  let b_weight = step_num as fp / total_steps as fp;
-  #[allow(non_upper_case_globals)]
-  const rounded_decimals: fp = 1000.0;
-  (
-    a.0,
-    (((a.1 * a_weight) + (b.1 * b_weight)) * rounded_decimals).round() / rounded_decimals,
-    (((a.2 * a_weight) + (b.2 * b_weight)) * rounded_decimals).round() / rounded_decimals,
-  )
 }
 
+// path_to_rgb's colors, keyed by the path's hash
+type PathColors = HashMap<usize, (u8, u8, u8)>;
 
-static PATH_TO_RGB_CACHE: Lazy<Mutex<HashMap<usize, (u8, u8, u8) >>> = Lazy::new(|| {
+static PATH_TO_RGB_CACHE: Lazy<Mutex<PathColors>> = Lazy::new(|| {
   Mutex::new( HashMap::new() )
 });
 
-pub fn path_to_rgb(path: &[usize], city_weights: &Vec<Vec<fp>>) -> (u8, u8, u8) {
+pub fn path_to_rgb(path: &[usize], city_weights: &[Vec<fp>]) -> (u8, u8, u8) {
 
   // Iterate city from zero_i to end_i, calculating a hash in both directions.
 
   let mut zero_i = 0;
-  for i in 0..path.len() {
-    if path[i] == 0 {
+  for (i, &city) in path.iter().enumerate() {
+    if city == 0 {
       zero_i = i;
     }
   }
@@ -1599,17 +2011,22 @@ pub fn path_to_rgb(path: &[usize], city_weights: &Vec<Vec<fp>>) -> (u8, u8, u8)
     }
   }
   let left_hash_u64 = left_hash.finish() as usize;
-  let right_hash_u64 = right_hash.finish() as usize;
+  let right_hash_u64 = if weights_are_symmetric(city_weights) {
+    right_hash.finish() as usize
+  }
+  else {
+    left_hash_u64 // a reversed directed tour is a different tour, it must not share the color
+  };
 
   // If hash_u64 is in cache, re-use same color.
   // Else generate something random but "nice" and store in cache.
 
   let mut path_to_rgb_cache_ref = PATH_TO_RGB_CACHE.lock().unwrap();
   if let Some(colors) = path_to_rgb_cache_ref.get(&left_hash_u64) {
-    return *colors;
+    *colors
   }
   else if let Some(colors) = path_to_rgb_cache_ref.get(&right_hash_u64) {
-    return *colors;
+    *colors
   }
   else {
     let r = rand::thread_rng().gen_range(40, 220) as u8;
@@ -1624,13 +2041,14 @@ pub fn path_to_rgb(path: &[usize], city_weights: &Vec<Vec<fp>>) -> (u8, u8, u8)
     // Debugging
     //println!("Unique color ({:02x}{:02x}{:02x}) allocated for path = {:?} hashes=({}, {})", r, g, b, path, left_hash_u64, right_hash_u64);
 
-    return (r, g, b);
+    (r, g, b)
   }
 }
 
 
 
-fn spray_pattern_search(n: usize, bound_granularity: fp, num_sprays_to_perform: usize, thread_pool: &ThreadPool, gpu_adapter: &mut Option<wgpu::Adapter>,) {
+fn spray_pattern_search(scenario: &Scenario, num_sprays_to_perform: usize, thread_pool: &ThreadPool, gpu_adapter: &mut Option<wgpu::Adapter>,) {
+  let n = scenario.cities.len();
   println!("Spray pattern searching {} cities for {} sprays...", n, num_sprays_to_perform);
 
   if brute_algo::use_brute_cache_env_val() {
@@ -1640,106 +2058,37 @@ fn spray_pattern_search(n: usize, bound_granularity: fp, num_sprays_to_perform:
 
   for spray_i in 0..num_sprays_to_perform {
     // Generate random N-city
-    let node_coordinates: Vec<(usize, fp, fp)> = get_env_or_random_node_coordinates(n, "SHOULD_NEVER_BE_COORDS_HERE!!__$%@!#@#!#&(*#@_INVALID_CHARS", x_min, x_max, y_min, y_max);
+    // Only the bounds, granularity and solver are taken from the given scenario
+    let spray_scenario = Scenario {
+      name: format!("spray-pattern-search-{:03}", spray_i),
+      cities: Scenario::random(n, scenario.granularity).cities,
+      ..scenario.clone()
+    };
+    let node_coordinates: Vec<(usize, fp, fp)> = spray_scenario.node_coordinates();
 
-    println!("");
+    println!();
     println!("spray_i={:03} node_coordinates={:?}", spray_i, node_coordinates);
 
-    let file_path = format!("views/spray-pattern-search-{:03}.png", spray_i);
-    let html_path = format!("views/spray-pattern-search-{:03}.html", spray_i);
-    let mut html_content = HTML_BEGIN.to_string();
-
-    pattern_scan_coords(n, bound_granularity, &file_path, node_coordinates.clone(), thread_pool, gpu_adapter, |city_weights, brute_sol, (point_x, point_y), rgb_key| {
-      let point_x: isize = (point_x * HTML_POINT_SCALE) as isize;
-      let point_y: isize = (point_y * HTML_POINT_SCALE) as isize;
+    let file_path = output::path(&format!("spray-pattern-search-{:03}.png", spray_i));
+    spray_scenario.save(output::path(&format!("spray-pattern-search-{:03}.scenario.json", spray_i)));
 
-      let city_weights = normalize_weights(city_weights);
-      html_content += format!(
-        "<div style=\"width:5px;height:5px;position:absolute;left:{}px;top:{}px;background-color:#{:02x}{:02x}{:02x}\" onclick=\"draw_path(this);\">{}</div>",
-        point_x,point_y,
-        rgb_key.0, rgb_key.1, rgb_key.2,
-        html_format_tour_details(&city_weights, brute_sol).as_str()
-      ).as_str();
-
-    });
+    pattern_scan_coords(n, &spray_scenario, &file_path, node_coordinates.clone(), thread_pool, gpu_adapter, nop_closure);
 
-    // Overlap tested N+1 points w/ beginning coordinates
-    for (city_num, city_x, city_y) in node_coordinates.iter() {
-      let city_x: isize = (city_x * HTML_POINT_SCALE) as isize;
-      let city_y: isize = (city_y * HTML_POINT_SCALE) as isize;
-      html_content += format!(
-        "<div style=\"width:24px;height:24px;position:absolute;left:{}px;top:{}px;background:transparent;border: 3px solid red;border-radius:99px;font-weight:bold;pointer-events:none;\">{}</div>",
-        city_x - 12, city_y - 12, city_num
-      ).as_str();
-    }
-
-    html_content += HTML_END;
-
-    let mut f = File::create(&html_path).expect("Unable to create file");
-    f.write_all(html_content.as_bytes()).expect("Unable to write data");
-
-  }
-
-}
-
-// transforms matrix of weights from 0.0 -> N to 0.0 -> 1.0 no matter how large the heaviest weight is.
-fn normalize_weights(weights: &Vec<Vec<fp>>) -> Vec<Vec<fp>> {
-  let mut heaviest_weight: fp = 0.0 as fp;
-  for row in weights.iter() {
-    for num in row.iter() {
-      if *num > heaviest_weight {
-        heaviest_weight = *num;
-      }
-    }
-  }
-
-  let corrective_ratio = 1.0 as fp / heaviest_weight;
-
-  let mut normalized_weights = vec![];
-  for row in weights.iter() {
-    let mut normalized_row = vec![];
-    for num in row.iter() {
-      normalized_row.push(
-        *num * corrective_ratio
-      );
-    }
-    normalized_weights.push( normalized_row );
   }
-  normalized_weights
-}
 
-fn html_format_tour_details(weights: &Vec<Vec<fp>>, brute_tour: &Vec<CityNum>) -> String {
-  let mut s = "<pre>".to_string();
-  let n = weights.len();
-  for row_i in 0..n {
-    s += "    ";
-    for col_i in 0..n {
-        if row_i == col_i {
-          s += "x.x         ";
-          continue;
-        }
-        s += format!("{:0.8}  ", weights[row_i][col_i] ).as_str();
-    }
-    s += "<br/>";
-  }
-  s += format!("tour = {:?}", brute_tour).as_str();
-  s += "</pre>";
-  s
 }
 
-
-fn print_square_matrix(weights: &Vec<Vec<fp>>) {
-  let n = weights.len();
-  for row_i in 0..n {
+fn print_square_matrix(weights: &[Vec<fp>]) {
+  for (row_i, row) in weights.iter().enumerate() {
     print!("    ");
-    for col_i in 0..n {
+    for (col_i, weight) in row.iter().enumerate() {
         if row_i == col_i {
           print!("x.x         ");
           continue;
         }
-        print!("{:0.8}  ", weights[row_i][col_i] );
+        print!("{:0.8}  ", weight );
     }
-    println!("");
+    println!();
   }
 }
 
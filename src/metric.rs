@@ -0,0 +1,141 @@
+/*
+ *  tsp-sol - an experimental environment for traveling salesman solution analysis
+ *  Copyright (C) 2023  Jeffrey McAteer <jeffrey@jmcateer.com>
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; version 2 of the License ONLY.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License along
+ *  with this program; if not, write to the Free Software Foundation, Inc.,
+ *  51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+ */
+
+// How the distance between 2 cities is measured. The solvers only see the weights matrix, so a
+// metric is chosen once when the weights are built; images draw geographic cities through an
+// equirectangular projection.
+
+use super::*;
+
+/// Mean earth radius in km, used by Metric::Haversine
+const EARTH_RADIUS_KM: f64 = 6371.0088;
+/// The sphere TSPLIB's GEO distances are defined on
+const TSPLIB_GEO_RADIUS_KM: f64 = 6378.388;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Metric {
+  /// Straight lines on the plane, what compute_weight_coords has always done
+  #[default]
+  Euclidean,
+  /// Great-circle km between (x = longitude, y = latitude) in decimal degrees, e.g. GPS fixes
+  Haversine,
+  /// TSPLIB's GEO convention: x = latitude, y = longitude as DDD.MM (degrees.minutes), integer km
+  Geo,
+}
+
+impl Metric {
+  pub fn all() -> Vec<Metric> {
+    vec![Metric::Euclidean, Metric::Haversine, Metric::Geo]
+  }
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      Metric::Euclidean => "euclidean",
+      Metric::Haversine => "haversine",
+      Metric::Geo => "geo",
+    }
+  }
+
+  pub fn from_name(name: &str) -> Option<Metric> {
+    Metric::all().into_iter().find(|m| m.name() == name)
+  }
+
+  /// (longitude, latitude) in radians of a city's coordinates, None for the plane.
+  pub fn lon_lat_radians(&self, x: CityXYCoord, y: CityXYCoord) -> Option<(f64, f64)> {
+    match self {
+      Metric::Euclidean => None,
+      Metric::Haversine => Some(((x as f64).to_radians(), (y as f64).to_radians())),
+      Metric::Geo => Some((tsplib_geo_radians(y as f64), tsplib_geo_radians(x as f64))),
+    }
+  }
+
+  pub fn dist(&self, a: &(CityNum, CityXYCoord, CityXYCoord), b: &(CityNum, CityXYCoord, CityXYCoord)) -> CityWeight {
+    match self {
+      Metric::Euclidean => coord_dist(a, b),
+      Metric::Haversine => {
+        let ((lon_a, lat_a), (lon_b, lat_b)) = (self.lon_lat_radians(a.1, a.2).unwrap(), self.lon_lat_radians(b.1, b.2).unwrap());
+        let h = ((lat_b - lat_a) / 2.0).sin().powi(2) + (lat_a.cos() * lat_b.cos() * ((lon_b - lon_a) / 2.0).sin().powi(2));
+        (2.0 * EARTH_RADIUS_KM * h.sqrt().min(1.0).asin()) as CityWeight
+      }
+      Metric::Geo => {
+        if a.1 == b.1 && a.2 == b.2 {
+          return 0.0;
+        }
+        // TSPLIB 95 section 2.4, truncated like the reference implementations
+        let ((lon_a, lat_a), (lon_b, lat_b)) = (self.lon_lat_radians(a.1, a.2).unwrap(), self.lon_lat_radians(b.1, b.2).unwrap());
+        let q1 = (lon_a - lon_b).cos();
+        let q2 = (lat_a - lat_b).cos();
+        let q3 = (lat_a + lat_b).cos();
+        let arc = (0.5 * (((1.0 + q1) * q2) - ((1.0 - q1) * q3))).clamp(-1.0, 1.0).acos();
+        ((TSPLIB_GEO_RADIUS_KM * arc) + 1.0).trunc() as CityWeight
+      }
+    }
+  }
+
+  pub fn weights(&self, node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>) -> Vec<Vec<CityWeight>> {
+    if *self == Metric::Euclidean {
+      return compute_weight_coords(node_coordinates);
+    }
+    node_coordinates.iter().map(|a| node_coordinates.iter().map(|b| self.dist(a, b)).collect() ).collect()
+  }
+
+  /// Cities to draw and to feed coordinate heuristics (hulls, spatial orders) with: geographic cities are
+  /// projected equirectangularly (north up) and fitted into the 0-15 view box; planar cities are returned as-is.
+  pub fn view_coordinates(&self, node_coordinates: &[(CityNum, CityXYCoord, CityXYCoord)]) -> Vec<(CityNum, CityXYCoord, CityXYCoord)> {
+    if *self == Metric::Euclidean || node_coordinates.is_empty() {
+      return node_coordinates.to_vec();
+    }
+    let lon_lats: Vec<(f64, f64)> = node_coordinates.iter().map(|(_i, x, y)| self.lon_lat_radians(*x, *y).unwrap()).collect();
+    let mean_lat = lon_lats.iter().map(|(_lon, lat)| lat).sum::<f64>() / (lon_lats.len() as f64);
+    let projected: Vec<(CityNum, CityXYCoord, CityXYCoord)> = node_coordinates.iter().zip(lon_lats.iter())
+      .map(|((i, _x, _y), (lon, lat))| (*i, (lon * mean_lat.cos()) as fp, (-lat) as fp))
+      .collect();
+
+    let (smallest_x, largest_y, largest_x, smallest_y) = get_point_extents(&projected);
+    let margin: fp = 0.5;
+    let view_size = (x_max_bound - x_min_bound).min(y_max_bound - y_min_bound) - (2.0 * margin);
+    let scale = view_size / (largest_x - smallest_x).max(largest_y - smallest_y).max(fp_epsilon);
+    projected.iter().map(|(i, x, y)| (*i, x_min_bound + margin + ((x - smallest_x) * scale), y_min_bound + margin + ((y - smallest_y) * scale)) ).collect()
+  }
+}
+
+// DDD.MM -> radians
+fn tsplib_geo_radians(ddd_mm: f64) -> f64 {
+  let degrees = ddd_mm.trunc();
+  let minutes = ddd_mm - degrees;
+  std::f64::consts::PI * (degrees + (5.0 * minutes / 3.0)) / 180.0
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn known_distances() {
+    // Paris -> London is about 344 km
+    let paris = (0, 2.3522, 48.8566);
+    let london = (1, -0.1276, 51.5072);
+    let km = Metric::Haversine.dist(&paris, &london);
+    assert!((km - 344.0).abs() < 2.0, "{}", km);
+
+    // burma14 cities 1 and 2 are 153 km apart in TSPLIB's GEO
+    let burma_1 = (0, 16.47, 96.10);
+    let burma_2 = (1, 16.47, 94.44);
+    assert_eq!(Metric::Geo.dist(&burma_1, &burma_2), 153.0);
+  }
+}
@@ -0,0 +1,128 @@
+/*
+ *  tsp-sol - an experimental environment for traveling salesman solution analysis
+ *  Copyright (C) 2023  Jeffrey McAteer <jeffrey@jmcateer.com>
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; version 2 of the License ONLY.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License along
+ *  with this program; if not, write to the Free Software Foundation, Inc.,
+ *  51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+ */
+
+// Open (Hamiltonian) paths, optionally pinned to a start and/or end city, solved by the cycle solvers.
+// A dummy city is appended whose edges cost nothing where the path may begin or finish and more than
+// any whole path everywhere else; the optimal cycle then runs end -> dummy -> start, and cutting
+// it at the dummy leaves the optimal path. Pinned ends make the weights asymmetric, which both
+// solvers honor.
+
+use super::*;
+
+use crate::jeff_algo::SolveOptions;
+
+/// Which ends of an open path are fixed; None lets the path begin or finish at any city.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PathEnds {
+  pub start: Option<CityNum>,
+  pub end: Option<CityNum>,
+}
+
+impl PathEnds {
+  pub fn check(&self, num_cities: usize) -> Result<(), String> {
+    for citynum in [self.start, self.end].iter().flatten() {
+      if *citynum >= num_cities {
+        return Err(format!("City {} does not exist, the city has {} cities", citynum, num_cities));
+      }
+    }
+    if self.start.is_some() && self.start == self.end {
+      return Err("The start and end city must differ, a path that returns to its start is a tour".to_string());
+    }
+    if num_cities < 2 {
+      return Err("An open path needs at least 2 cities".to_string());
+    }
+    Ok(())
+  }
+}
+
+/// Length of path without the edge from its last city back to the first.
+pub fn path_dist(weights: &[Vec<CityWeight>], path: &[CityNum]) -> fp {
+  path.windows(2).map(|edge| weights[edge[0]][edge[1]]).sum()
+}
+
+/// node_coordinates and weights with the dummy city appended as index len().
+fn with_dummy(node_coordinates: &[City], weights: &[Vec<CityWeight>], ends: &PathEnds) -> (Vec<City>, Vec<Vec<CityWeight>>) {
+  let dummy = weights.len();
+  // longer than any path: every path edge leaves a different city
+  let forbidden: CityWeight = 1.0 + weights.iter().map(|row| row.iter().cloned().fold(0.0, fp::max)).sum::<fp>();
+
+  let mut dummy_weights = weights.to_vec();
+  for (citynum, row) in dummy_weights.iter_mut().enumerate() {
+    // citynum -> dummy is where the path finishes
+    row.push(if ends.end.is_none() || ends.end == Some(citynum) { 0.0 } else { forbidden });
+  }
+  // dummy -> citynum is where the path begins
+  let mut dummy_row: Vec<CityWeight> = (0..dummy).map(|citynum| if ends.start.is_none() || ends.start == Some(citynum) { 0.0 } else { forbidden }).collect();
+  dummy_row.push(0.0);
+  dummy_weights.push(dummy_row);
+
+  // the centroid keeps the dummy off the convex hull jeff_algo may start from
+  let mut dummy_coordinates = node_coordinates.to_vec();
+  let (center_x, center_y) = compute_center(&(0..dummy).collect(), node_coordinates);
+  dummy_coordinates.push((dummy, center_x, center_y));
+
+  (dummy_coordinates, dummy_weights)
+}
+
+/// The path that follows the dummy city around cycle.
+fn cut_at_dummy(cycle: &[CityNum], dummy: CityNum) -> Vec<CityNum> {
+  let dummy_i = cycle.iter().position(|c| *c == dummy).expect("the solved cycle visits the dummy city");
+  cycle[dummy_i + 1..].iter().chain(cycle[..dummy_i].iter()).cloned().collect()
+}
+
+/// Shortest open path jeff_algo finds, beginning at ends.start and finishing at ends.end when they are set.
+pub fn solve_jalgo(node_coordinates: &[(CityNum, CityXYCoord, CityXYCoord)], weights: &[Vec<CityWeight>], ends: &PathEnds, options: &SolveOptions) -> Vec<CityNum> {
+  let (dummy_coordinates, dummy_weights) = with_dummy(node_coordinates, weights, ends);
+  let cycle = jeff_algo::solve_with_options(&dummy_coordinates, &dummy_weights, None, options);
+  cut_at_dummy(&cycle, weights.len())
+}
+
+/// Optimal open path by brute force. The coordinate-keyed cache cannot tell the dummy's weights apart, so it is skipped.
+pub fn solve_brute(node_coordinates: &[(CityNum, CityXYCoord, CityXYCoord)], weights: &[Vec<CityWeight>], ends: &PathEnds, thread_pool: &ThreadPool) -> Vec<CityNum> {
+  let (dummy_coordinates, dummy_weights) = with_dummy(node_coordinates, weights, ends);
+  let cycle = brute_algo::solve_uncached(&dummy_coordinates, &dummy_weights, thread_pool);
+  cut_at_dummy(&cycle, weights.len())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn pinned_paths_match_brute() {
+    let thread_pool = ThreadPool::new(1);
+    let mut rng = fastrand::Rng::with_seed(41);
+    for _ in 0..20 {
+      let n = rng.usize(4..8);
+      let node_coordinates: Vec<(CityNum, CityXYCoord, CityXYCoord)> = (0..n).map(|i| (i, 3.0 + rng.f32() * 9.0, 3.0 + rng.f32() * 9.0) ).collect();
+      let weights = compute_weight_coords(&node_coordinates);
+      for ends in [PathEnds::default(), PathEnds { start: Some(0), end: None }, PathEnds { start: None, end: Some(1) }, PathEnds { start: Some(0), end: Some(1) }] {
+        let brute = solve_brute(&node_coordinates, &weights, &ends, &thread_pool);
+        let jeff = solve_jalgo(&node_coordinates, &weights, &ends, &SolveOptions::default());
+        for path in [&brute, &jeff] {
+          let mut sorted = path.clone();
+          sorted.sort();
+          assert_eq!(sorted, (0..n).collect::<Vec<CityNum>>(), "{:?} {:?}", ends, path);
+          assert!(ends.start.is_none() || ends.start == Some(path[0]), "{:?} {:?}", ends, path);
+          assert!(ends.end.is_none() || ends.end == Some(path[n - 1]), "{:?} {:?}", ends, path);
+        }
+        assert!(path_dist(&weights, &brute) <= path_dist(&weights, &jeff) + fp_epsilon, "{:?} brute {:?} jeff {:?}", ends, brute, jeff);
+      }
+    }
+  }
+}
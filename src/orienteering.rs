@@ -0,0 +1,202 @@
+/*
+ *  tsp-sol - an experimental environment for traveling salesman solution analysis
+ *  Copyright (C) 2023  Jeffrey McAteer <jeffrey@jmcateer.com>
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; version 2 of the License ONLY.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License along
+ *  with this program; if not, write to the Free Software Foundation, Inc.,
+ *  51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+ */
+
+// Orienteering: every city has a prize and the tour from the depot may be at most `budget` long,
+// so only some cities are visited. The goal is the largest prize total that fits, ties going to the shorter tour.
+
+use super::*;
+
+use crate::jeff_algo::SolveOptions;
+
+/// Prize of every city, indexed like node_coordinates; the depot's prize is always collected.
+pub struct Prizes {
+  pub prizes: Vec<fp>,
+}
+
+impl Prizes {
+  /// Every city is worth 1, so the best tour is the one visiting the most cities.
+  pub fn uniform(num_cities: usize) -> Prizes {
+    Prizes { prizes: vec![1.0; num_cities] }
+  }
+
+  /// Reads a sidecar file of "CITY PRIZE" lines, CITY being the number used in the .tsp file.
+  /// Cities not listed are worth 0. Empty lines and lines starting with # are skipped.
+  pub fn open(file_arg: &str, node_coordinates: &[(CityNum, CityXYCoord, CityXYCoord)]) -> Option<Prizes> {
+    let text = match fs::read_to_string(file_arg) {
+      Ok(text) => text,
+      Err(e) => {
+        report_error(&format!("Cannot open {}: {}", file_arg, e));
+        return None;
+      }
+    };
+
+    let mut prizes = vec![0.0; node_coordinates.len()];
+    for (line_i, line) in text.lines().enumerate() {
+      let line = line.trim();
+      if line.is_empty() || line.starts_with('#') {
+        continue;
+      }
+      let fields: Vec<&str> = line.split_whitespace().collect();
+      let citynum = fields.first()
+        .and_then(|id_s| id_s.parse::<usize>().ok())
+        .and_then(|id| node_coordinates.iter().position(|(file_id, _x, _y)| *file_id == id));
+      match (citynum, fields.get(1).and_then(|p| p.parse::<fp>().ok()), fields.len()) {
+        (Some(citynum), Some(prize), 2) if prize >= 0.0 => prizes[citynum] = prize,
+        _ => {
+          report_error(&format!("Err: {} line {}: expected \"CITY PRIZE\" for a city in the file, got {:?}", file_arg, line_i + 1, line));
+          return None;
+        }
+      }
+    }
+    Some(Prizes { prizes })
+  }
+
+  pub fn collected(&self, tour: &[CityNum]) -> fp {
+    tour.iter().map(|c| self.prizes[*c]).sum()
+  }
+}
+
+// tour re-ordered by jeff_algo on its own cities, starting at tour[0]; kept only if shorter.
+fn reordered(node_coordinates: &[(CityNum, CityXYCoord, CityXYCoord)], weights: &[Vec<CityWeight>], tour: &[CityNum], options: &SolveOptions) -> Vec<CityNum> {
+  if tour.len() <= 3 {
+    return tour.to_vec();
+  }
+  let sub_coordinates: Vec<(CityNum, CityXYCoord, CityXYCoord)> = tour.iter().enumerate().map(|(i, c)| (i, node_coordinates[*c].1, node_coordinates[*c].2) ).collect();
+  let sub_weights: Vec<Vec<CityWeight>> = tour.iter().map(|a| tour.iter().map(|b| weights[*a][*b]).collect() ).collect();
+  let mut sub_tour = jeff_algo::solve_with_options(&sub_coordinates, &sub_weights, None, options);
+  let first_i = sub_tour.iter().position(|c| *c == 0).unwrap_or(0);
+  sub_tour.rotate_left(first_i);
+  let resolved: Vec<CityNum> = sub_tour.iter().map(|i| tour[*i]).collect();
+  if compute_dist(weights, &resolved) < compute_dist(weights, tour) - fp_epsilon {
+    return resolved;
+  }
+  tour.to_vec()
+}
+
+/// Greedy insertion: the city with the most prize per unit of added length (its cheapest insertion
+/// delta, as insert_point_step computes it) is inserted while it fits the budget. When nothing fits
+/// any more the tour is re-ordered by jeff_algo, which can free budget for another round.
+pub fn solve_greedy(node_coordinates: &[(CityNum, CityXYCoord, CityXYCoord)], weights: &[Vec<CityWeight>], prizes: &Prizes, depot: CityNum, budget: fp, options: &SolveOptions) -> Vec<CityNum> {
+  let mut tour = vec![depot];
+  loop {
+    let mut inserted_any = false;
+    loop {
+      let remaining_budget = budget - compute_dist(weights, &tour);
+      // (prize per length, citynum, insert position)
+      let mut best: Option<(fp, CityNum, usize)> = None;
+      for citynum in 0..weights.len() {
+        if prizes.prizes[citynum] <= 0.0 || tour.contains(&citynum) {
+          continue;
+        }
+        let cheapest = jeff_algo::insertion_deltas(&tour, weights, citynum).into_iter().enumerate()
+          .min_by(|a, b| (a.1).2.partial_cmp(&(b.1).2).unwrap_or(std::cmp::Ordering::Equal));
+        if let Some((from_i, (_from, _to, delta))) = cheapest {
+          if delta > remaining_budget + fp_epsilon {
+            continue;
+          }
+          let ratio = prizes.prizes[citynum] / delta.max(fp_epsilon);
+          if best.map(|b| ratio > b.0).unwrap_or(true) {
+            best = Some((ratio, citynum, from_i + 1));
+          }
+        }
+      }
+      match best {
+        Some((_ratio, citynum, insert_i)) => {
+          tour.insert(insert_i, citynum);
+          inserted_any = true;
+        }
+        None => break,
+      }
+    }
+
+    let shorter = reordered(node_coordinates, weights, &tour, options);
+    let freed_budget = compute_dist(weights, &tour) - compute_dist(weights, &shorter) > fp_epsilon;
+    tour = shorter;
+    if !inserted_any || !freed_budget {
+      break;
+    }
+  }
+  tour
+}
+
+/// Largest city solve_exact accepts: every subset with the depot is a candidate and each is brute forced.
+pub const MAX_EXACT_CITIES: usize = 10;
+
+/// Best tour by exhaustive enumeration: subsets of cities are taken in decreasing prize order and the
+/// shortest tour of each is brute forced; the first subset whose tour fits the budget has the best prize,
+/// later subsets with the same prize may still be shorter. None when num cities > MAX_EXACT_CITIES.
+pub fn solve_exact(weights: &[Vec<CityWeight>], prizes: &Prizes, depot: CityNum, budget: fp) -> Option<Vec<CityNum>> {
+  if weights.len() > MAX_EXACT_CITIES {
+    return None;
+  }
+  let others: Vec<CityNum> = (0..weights.len()).filter(|c| *c != depot).collect();
+  let mut subsets: Vec<(fp, Vec<CityNum>)> = (0..(1usize << others.len())).map(|mask| {
+    let mut subset = vec![depot];
+    subset.extend(others.iter().enumerate().filter(|(bit, _c)| mask & (1 << bit) != 0).map(|(_bit, c)| *c));
+    (prizes.collected(&subset), subset)
+  }).collect();
+  subsets.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+
+  let mut best: Option<(fp, fp, Vec<CityNum>)> = None; // prize, length, tour
+  for (prize, subset) in subsets {
+    if let Some((best_prize, _len, _tour)) = &best {
+      if prize < best_prize - fp_epsilon {
+        break;
+      }
+    }
+    let sub_weights: Vec<Vec<CityWeight>> = subset.iter().map(|a| subset.iter().map(|b| weights[*a][*b]).collect() ).collect();
+    let sub_tour = match brute_algo::solve_st_filtered(&sub_weights, 0, &|_tour| true) {
+      Some(sub_tour) => sub_tour,
+      None => continue,
+    };
+    let tour: Vec<CityNum> = sub_tour.iter().map(|i| subset[*i]).collect();
+    let len = compute_dist(weights, &tour);
+    if len > budget + fp_epsilon {
+      continue;
+    }
+    if best.as_ref().map(|(_prize, best_len, _tour)| len < *best_len).unwrap_or(true) {
+      best = Some((prize, len, tour));
+    }
+  }
+  best.map(|(_prize, _len, tour)| tour)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn greedy_fits_the_budget_and_exact_is_never_beaten() {
+    let mut rng = fastrand::Rng::with_seed(44);
+    for _ in 0..10 {
+      let n = rng.usize(4..9);
+      let node_coordinates: Vec<(CityNum, CityXYCoord, CityXYCoord)> = (0..n).map(|i| (i, 3.0 + rng.f32() * 9.0, 3.0 + rng.f32() * 9.0) ).collect();
+      let weights = compute_weight_coords(&node_coordinates);
+      let prizes = Prizes { prizes: (0..n).map(|_| rng.usize(1..10) as fp).collect() };
+      let budget = 5.0 + rng.f32() * 20.0;
+
+      let greedy = solve_greedy(&node_coordinates, &weights, &prizes, 0, budget, &SolveOptions::default());
+      let exact = solve_exact(&weights, &prizes, 0, budget).expect("the depot alone always fits");
+      for tour in [&greedy, &exact] {
+        assert_eq!(tour[0], 0);
+        assert!(compute_dist(&weights, tour) <= budget + fp_epsilon, "{:?} is longer than {}", tour, budget);
+      }
+      assert!(prizes.collected(&exact) + fp_epsilon >= prizes.collected(&greedy), "greedy {:?} beat exact {:?}", greedy, exact);
+    }
+  }
+}
@@ -0,0 +1,257 @@
+/*
+ *  tsp-sol - an experimental environment for traveling salesman solution analysis
+ *  Copyright (C) 2023  Jeffrey McAteer <jeffrey@jmcateer.com>
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; version 2 of the License ONLY.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License along
+ *  with this program; if not, write to the Free Software Foundation, Inc.,
+ *  51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+ */
+
+// Where a run writes its images, traces and scan data: --out-dir (./views by default), or with
+// --timestamped a fresh OUT_DIR/YYYYmmdd-HHMMSS-COMMAND/ per run, closed by a manifest.json
+// listing every file the run produced through path().
+
+use super::*;
+
+use serde::Serialize;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+struct Run {
+  dir: PathBuf,
+  command_line: Vec<String>,
+  started: SystemTime,
+  // start_run was called, so finish_run writes a manifest
+  active: bool,
+  // the run directory did not exist before this run
+  created: bool,
+  // every relative path handed out by path(), files and directory prefixes alike
+  recorded: Vec<String>,
+}
+
+static RUN: Lazy<Mutex<Run>> = Lazy::new(|| {
+  Mutex::new(Run {
+    dir: PathBuf::from("views"),
+    command_line: vec![],
+    started: SystemTime::now(),
+    active: false,
+    created: false,
+    recorded: vec![],
+  })
+});
+
+#[derive(Serialize, Debug)]
+struct Artifact {
+  /// Relative to the run directory
+  path: String,
+  bytes: u64,
+}
+
+#[derive(Serialize, Debug)]
+struct Manifest {
+  command_line: Vec<String>,
+  run_dir: String,
+  /// UTC, e.g. "2026-10-19T14:25:01Z"
+  started: String,
+  elapsed_ms: f64,
+  artifacts: Vec<Artifact>,
+}
+
+/// Picks and creates the directory this run writes to. command_name names timestamped subdirectories.
+pub fn start_run(out_dir: &str, timestamped: bool, command_name: &str, command_line: Vec<String>) -> Result<PathBuf, String> {
+  let started = SystemTime::now();
+  let mut dir = PathBuf::from(out_dir);
+  // whether our own create_dir made the run directory, not whether it existed a moment ago
+  let mut created = match fs::create_dir(&dir) {
+    Ok(()) => true,
+    Err(e) if e.kind() == ErrorKind::AlreadyExists => false,
+    Err(_) => {
+      fs::create_dir_all(&dir).map_err(|e| format!("Cannot create output directory {}: {}", dir.display(), e))?;
+      true
+    }
+  };
+  if timestamped {
+    let stem = format!("{}-{}", utc_timestamp(started).replace(['-', ':'], "").replace('T', "-").trim_end_matches('Z'), command_name);
+    dir = Path::new(out_dir).join(&stem);
+    // runs started within the same second get -2, -3, ...; create_dir fails for all but one of them,
+    // so two processes never share a directory
+    let mut suffix = 2;
+    loop {
+      match fs::create_dir(&dir) {
+        Ok(()) => break,
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+          dir = Path::new(out_dir).join(format!("{}-{}", stem, suffix));
+          suffix += 1;
+        }
+        Err(e) => return Err(format!("Cannot create output directory {}: {}", dir.display(), e)),
+      }
+    }
+    created = true;
+  }
+
+  let mut run = RUN.lock().unwrap();
+  *run = Run { dir: dir.clone(), command_line, started, active: true, created, recorded: vec![] };
+  Ok(dir)
+}
+
+/// The run directory, e.g. "views" or "views/20261019-142501-spray".
+pub fn run_dir() -> PathBuf {
+  return RUN.lock().unwrap().dir.clone();
+}
+
+/// relative (e.g. "spray.png" or "selective/") inside the run directory; a trailing '/' is kept
+/// because several writers treat their argument as a directory prefix. finish_run lists whatever
+/// ends up at the paths handed out here.
+pub fn path(relative: &str) -> String {
+  let mut run = RUN.lock().unwrap();
+  if !run.recorded.iter().any(|r| r == relative) {
+    run.recorded.push(relative.to_string());
+  }
+  join(&run.dir, relative)
+}
+
+fn join(dir: &Path, relative: &str) -> String {
+  let mut joined = dir.join(relative.trim_end_matches('/')).to_string_lossy().to_string();
+  if relative.ends_with('/') {
+    joined.push('/');
+  }
+  joined
+}
+
+/// Writes manifest.json listing every file written through path(): recorded files, and every file
+/// under recorded directories, modified since the run started; files left in a reused directory by
+/// earlier runs are not listed. A run that produced nothing leaves no manifest, and removes the run
+/// directory if it made it.
+pub fn finish_run(elapsed: Duration) -> Option<PathBuf> {
+  let run = RUN.lock().unwrap();
+  if !run.active {
+    return None;
+  }
+  // whole seconds, filesystems with coarse modification times may round a fresh file down
+  let since = UNIX_EPOCH + Duration::from_secs(run.started.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs());
+  let mut artifacts = vec![];
+  for relative in run.recorded.iter() {
+    collect_artifacts(&run.dir, &run.dir.join(relative.trim_end_matches('/')), since, &mut artifacts);
+  }
+  artifacts.sort_by(|a, b| a.path.cmp(&b.path));
+  artifacts.dedup_by(|a, b| a.path == b.path);
+  if artifacts.is_empty() {
+    if run.created {
+      let _ = fs::remove_dir(&run.dir);
+    }
+    return None;
+  }
+
+  let manifest = Manifest {
+    command_line: run.command_line.clone(),
+    run_dir: run.dir.to_string_lossy().to_string(),
+    started: utc_timestamp(run.started),
+    elapsed_ms: elapsed.as_secs_f64() * 1000.0,
+    artifacts,
+  };
+  let manifest_path = run.dir.join("manifest.json");
+  let json = serde_json::to_string_pretty(&manifest).unwrap_or_default() + "\n";
+  if let Err(e) = fs::write(&manifest_path, json) {
+    report_error(&format!("Error writing {}: {}", manifest_path.display(), e));
+    return None;
+  }
+  Some(manifest_path)
+}
+
+// entry_path itself if it is a file, every file below it if it is a directory, nothing if it was never written;
+// files last modified before since are skipped
+fn collect_artifacts(run_dir: &Path, entry_path: &Path, since: SystemTime, artifacts: &mut Vec<Artifact>) {
+  let metadata = match fs::metadata(entry_path) {
+    Ok(metadata) => metadata,
+    Err(_) => return,
+  };
+  if metadata.is_dir() {
+    let entries = match fs::read_dir(entry_path) {
+      Ok(entries) => entries,
+      Err(_) => return,
+    };
+    for entry in entries.flatten() {
+      collect_artifacts(run_dir, &entry.path(), since, artifacts);
+    }
+    return;
+  }
+  if metadata.modified().is_ok_and(|modified| modified < since) {
+    return;
+  }
+  let relative = entry_path.strip_prefix(run_dir).unwrap_or(entry_path).to_string_lossy().to_string();
+  artifacts.push(Artifact { path: relative, bytes: metadata.len() });
+}
+
+/// "YYYY-mm-ddTHH:MM:SSZ" without pulling in a date crate.
+fn utc_timestamp(t: SystemTime) -> String {
+  let secs = t.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as i64;
+  let (days, secs_of_day) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+  // civil_from_days, Howard Hinnant's date algorithms
+  let z = days + 719468;
+  let era = z.div_euclid(146097);
+  let doe = z - era * 146097;
+  let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+  let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+  let mp = (5 * doy + 2) / 153;
+  let day = doy - (153 * mp + 2) / 5 + 1;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 };
+  let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+  format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, secs_of_day / 3600, secs_of_day % 3600 / 60, secs_of_day % 60)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn timestamps_and_paths() {
+    assert_eq!(utc_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+    assert_eq!(utc_timestamp(UNIX_EPOCH + Duration::from_secs(1_709_210_096)), "2024-02-29T12:34:56Z");
+    assert_eq!(join(Path::new("views"), "spray.png"), "views/spray.png");
+    assert_eq!(join(Path::new("/tmp/runs/20240229-123456-selective"), "selective/"), "/tmp/runs/20240229-123456-selective/selective/");
+  }
+
+  #[test]
+  fn runs_get_their_own_directory_and_list_what_they_wrote() {
+    let out_dir = env::temp_dir().join(format!("tsp-sol-output-{}", std::process::id()));
+    let out_dir_s = out_dir.to_string_lossy().to_string();
+    let first = start_run(&out_dir_s, true, "spray", vec![]).unwrap();
+    let second = start_run(&out_dir_s, true, "spray", vec!["tsp-sol".to_string()]).unwrap();
+    assert_ne!(first, second);
+
+    fs::write(path("spray.png"), b"png").unwrap();
+    fs::create_dir_all(path("selective/")).unwrap();
+    fs::write(path("selective/") + "brute-005.png", b"brute").unwrap();
+    path("never-written.png");
+    fs::write(second.join("stray.txt"), b"not ours").unwrap();
+
+    let manifest_path = finish_run(Duration::from_millis(5)).unwrap();
+    let manifest: serde_json::Value = serde_json::from_str(&fs::read_to_string(&manifest_path).unwrap()).unwrap();
+    let listed: Vec<&str> = manifest["artifacts"].as_array().unwrap().iter().map(|a| a["path"].as_str().unwrap()).collect();
+    assert_eq!(listed, vec!["selective/brute-005.png", "spray.png"]);
+
+    // a reused directory keeps files from earlier runs, only the ones this run wrote are listed
+    let reused = out_dir.join("reused");
+    fs::create_dir_all(reused.join("selective")).unwrap();
+    let stale = reused.join("selective").join("brute-004.png");
+    fs::write(&stale, b"earlier run").unwrap();
+    fs::File::options().write(true).open(&stale).unwrap().set_modified(SystemTime::now() - Duration::from_secs(3600)).unwrap();
+    start_run(&reused.to_string_lossy(), false, "selective", vec![]).unwrap();
+    fs::write(path("selective/") + "brute-005.png", b"brute").unwrap();
+    let manifest_path = finish_run(Duration::from_millis(5)).unwrap();
+    let manifest: serde_json::Value = serde_json::from_str(&fs::read_to_string(&manifest_path).unwrap()).unwrap();
+    let listed: Vec<&str> = manifest["artifacts"].as_array().unwrap().iter().map(|a| a["path"].as_str().unwrap()).collect();
+    assert_eq!(listed, vec!["selective/brute-005.png"]);
+    let _ = fs::remove_dir_all(&out_dir);
+  }
+}
@@ -0,0 +1,206 @@
+/*
+ *  tsp-sol - an experimental environment for traveling salesman solution analysis
+ *  Copyright (C) 2023  Jeffrey McAteer <jeffrey@jmcateer.com>
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; version 2 of the License ONLY.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License along
+ *  with this program; if not, write to the Free Software Foundation, Inc.,
+ *  51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+ */
+
+// Property tests: jeff_algo and brute_algo against random + adversarial cities.
+// Cities come from seeded generators so a failure names the seed that reproduces it,
+// and brute_algo::solve_st is used directly so no test touches the pickle caches under target/.
+
+use super::*;
+
+/// Random cities per property; PROPERTY_TEST_CASES overrides it for longer local runs.
+const DEFAULT_CASES: usize = 60;
+
+/// jeff_algo must match brute_algo on at least this fraction of random 4-8 city problems.
+const MIN_JALGO_OPTIMAL_RATE: fp = 0.95;
+
+fn num_cases() -> usize {
+  match env::var("PROPERTY_TEST_CASES") {
+    Ok(val) => val.parse().unwrap_or(DEFAULT_CASES),
+    Err(_) => DEFAULT_CASES,
+  }
+}
+
+fn random_city(seed: u64) -> Vec<(CityNum, CityXYCoord, CityXYCoord)> {
+  let mut rng = fastrand::Rng::with_seed(seed);
+  let n = rng.usize(4..=8);
+  (0..n).map(|i| (i, x_min + (rng.f32() * (x_max - x_min)), y_min + (rng.f32() * (y_max - y_min))) ).collect()
+}
+
+/// Layouts that stress the tie-breaking and triangle-picking code paths.
+fn adversarial_cities() -> Vec<(&'static str, Vec<City>)> {
+  let mut cities = vec![];
+
+  let circle: Vec<(CityNum, fp, fp)> = (0..8).map(|i| {
+    let angle = (i as fp) * std::f32::consts::TAU / 8.0;
+    (i, 7.5 + (4.0 * angle.cos()), 7.5 + (4.0 * angle.sin()))
+  }).collect();
+  cities.push(("circle", circle.clone()));
+
+  let mut circle_and_center = circle[..7].to_vec();
+  circle_and_center.push((7, 7.5, 7.5));
+  cities.push(("circle-and-center", circle_and_center));
+
+  cities.push(("grid", (0..8).map(|i| (i, 4.0 + (2.0 * (i % 4) as fp), 6.0 + (2.0 * (i / 4) as fp)) ).collect()));
+  cities.push(("collinear", (0..6).map(|i| (i, 3.0 + (1.5 * i as fp), 7.5) ).collect()));
+  cities.push(("nearly-collinear", (0..6).map(|i| (i, 3.0 + (1.5 * i as fp), 7.5 + (0.01 * (i % 2) as fp)) ).collect()));
+  cities.push(("two-clusters", vec![
+    (0, 3.0, 3.0), (1, 3.2, 3.1), (2, 3.1, 3.3),
+    (3, 12.0, 12.0), (4, 12.2, 11.9), (5, 11.9, 12.2), (6, 12.1, 12.1),
+  ]));
+  cities.push(("near-duplicates", vec![
+    (0, 5.0, 5.0), (1, 5.0001, 5.0), (2, 10.0, 5.0), (3, 10.0, 10.0), (4, 5.0, 10.0),
+  ]));
+  cities.push(("long-thin", vec![
+    (0, 1.0, 7.0), (1, 14.0, 7.0), (2, 5.0, 7.2), (3, 9.0, 6.8), (4, 12.0, 7.1), (5, 3.0, 6.9),
+  ]));
+
+  cities
+}
+
+fn brute_solve(node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>, weights: &Vec<Vec<fp>>) -> Vec<CityNum> {
+  brute_algo::solve_st(node_coordinates, weights, 0, brute_algo::factorial(weights.len()))
+}
+
+fn assert_hamiltonian_cycle(label: &str, path: &Vec<CityNum>, n: usize) {
+  assert_eq!(path.len(), n, "{}: tour {:?} does not visit {} cities", label, path, n);
+  let mut seen = vec![false; n];
+  for city in path.iter() {
+    assert!(*city < n, "{}: tour {:?} has unknown city {}", label, path, city);
+    assert!(!seen[*city], "{}: tour {:?} visits city {} twice", label, path, city);
+    seen[*city] = true;
+  }
+}
+
+fn check_both_solvers(label: &str, node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>) -> bool {
+  let weights = compute_weight_coords(node_coordinates);
+  let jeff_sol = jeff_algo::solve(node_coordinates, &weights, None);
+  let brute_sol = brute_solve(node_coordinates, &weights);
+
+  assert_hamiltonian_cycle(&format!("{} jeff_algo", label), &jeff_sol, node_coordinates.len());
+  assert_hamiltonian_cycle(&format!("{} brute_algo", label), &brute_sol, node_coordinates.len());
+
+  let jeff_len = compute_dist(&weights, &jeff_sol);
+  let brute_len = compute_dist(&weights, &brute_sol);
+  assert!(brute_len <= jeff_len + fp_epsilon,
+    "{}: jeff_algo {:?} ({}) beat brute_algo {:?} ({}) for {:?}", label, jeff_sol, jeff_len, brute_sol, brute_len, node_coordinates);
+
+  (jeff_len - brute_len).abs() <= fp_epsilon || is_identical_path(&jeff_sol, &brute_sol)
+}
+
+#[test]
+fn solvers_return_hamiltonian_cycles_and_brute_is_never_beaten() {
+  for seed in 0..num_cases() as u64 {
+    check_both_solvers(&format!("seed {}", seed), &random_city(seed));
+  }
+}
+
+#[test]
+fn adversarial_cities_are_solved() {
+  for (label, node_coordinates) in adversarial_cities() {
+    let jeff_optimal = check_both_solvers(label, &node_coordinates);
+    if label == "circle" {
+      // Cities in convex position have the hull as their only optimal tour
+      assert!(jeff_optimal, "jeff_algo missed the hull tour of {:?}", node_coordinates);
+    }
+  }
+}
+
+#[test]
+fn jalgo_optimality_rate_stays_above_threshold() {
+  let cases = num_cases();
+  let mut num_optimal = 0;
+  for seed in 0..cases as u64 {
+    if check_both_solvers(&format!("seed {}", seed), &random_city(1_000_000 + seed)) {
+      num_optimal += 1;
+    }
+  }
+  let rate = num_optimal as fp / cases as fp;
+  assert!(rate >= MIN_JALGO_OPTIMAL_RATE, "jeff_algo optimal on {}/{} cities ({:.3}), below {}", num_optimal, cases, rate, MIN_JALGO_OPTIMAL_RATE);
+}
+
+#[test]
+fn identical_path_ignores_rotation_and_direction() {
+  for seed in 0..num_cases() as u64 {
+    let mut rng = fastrand::Rng::with_seed(seed);
+    let n = rng.usize(3..=10);
+    let mut tour: Vec<CityNum> = (0..n).collect();
+    rng.shuffle(&mut tour);
+
+    for rotation in 0..n {
+      let mut rotated = tour.clone();
+      rotated.rotate_left(rotation);
+      assert!(is_identical_path(&tour, &rotated), "{:?} vs rotated {:?}", tour, rotated);
+
+      let mut reversed = rotated.clone();
+      reversed.reverse();
+      assert!(is_identical_path(&tour, &reversed), "{:?} vs reversed {:?}", tour, reversed);
+    }
+
+    if n >= 4 {
+      // Swapping 2 neighbours changes which edges the tour uses
+      let swap_i = rng.usize(0..n);
+      let mut different = tour.clone();
+      different.swap(swap_i, (swap_i + 1) % n);
+      assert!(!is_identical_path(&tour, &different), "{:?} should differ from {:?}", tour, different);
+    }
+
+    let shorter = tour[..n - 1].to_vec();
+    assert!(!is_identical_path(&tour, &shorter));
+  }
+}
+
+#[test]
+fn every_insertion_order_and_initial_tour_returns_hamiltonian_cycles() {
+  for seed in 0..num_cases() as u64 {
+    let node_coordinates = random_city(2_000_000 + seed);
+    let weights = compute_weight_coords(&node_coordinates);
+    let brute_len = compute_dist(&weights, &brute_solve(&node_coordinates, &weights));
+    for insertion_order in jeff_algo::InsertionOrder::all() {
+      for initial_tour in jeff_algo::InitialTour::all() {
+        let options = jeff_algo::SolveOptions { insertion_order, initial_tour, ..Default::default() };
+        let jeff_sol = jeff_algo::solve_with_options(&node_coordinates, &weights, None, &options);
+        assert_hamiltonian_cycle(&format!("seed {} {} {}", seed, insertion_order.name(), initial_tour.name()), &jeff_sol, node_coordinates.len());
+        assert!(brute_len <= compute_dist(&weights, &jeff_sol) + fp_epsilon);
+      }
+    }
+  }
+}
+
+#[test]
+fn asymmetric_weights_keep_their_direction() {
+  // one-way ring: going around 0 -> 1 -> ... costs 1 per edge, every other edge costs 10 or more
+  for n in 4..8 {
+    let node_coordinates = geometry::circle_layout(n);
+    let weights: Vec<Vec<fp>> = (0..n).map(|a| (0..n).map(|b| {
+      if a == b { 0.0 } else if b == (a + 1) % n { 1.0 } else { 10.0 + (((a * 7) + b) % 5) as fp }
+    }).collect() ).collect();
+    assert!(!weights_are_symmetric(&weights));
+
+    let forward: Vec<CityNum> = (0..n).collect();
+    let mut backward = forward.clone();
+    backward.reverse();
+    assert!(is_identical_tour(&forward, &forward, &weights));
+    assert!(!is_identical_tour(&forward, &backward, &weights), "a reversed one-way tour is a different tour");
+    assert_ne!(path_to_rgb(&forward, &weights), path_to_rgb(&backward, &weights));
+
+    let brute_sol = brute_solve(&node_coordinates, &weights);
+    let jeff_sol = jeff_algo::solve(&node_coordinates, &weights, None);
+    assert!(is_identical_tour(&brute_sol, &forward, &weights), "brute {:?}", brute_sol);
+    assert!(is_identical_tour(&jeff_sol, &forward, &weights), "jeff {:?}", jeff_sol);
+  }
+}
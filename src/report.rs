@@ -0,0 +1,114 @@
+/*
+ *  tsp-sol - an experimental environment for traveling salesman solution analysis
+ *  Copyright (C) 2023  Jeffrey McAteer <jeffrey@jmcateer.com>
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; version 2 of the License ONLY.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License along
+ *  with this program; if not, write to the Free Software Foundation, Inc.,
+ *  51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+ */
+
+// The --format json solve report: one object describing a file solve, printed instead of the
+// free text so scripts and CI do not have to scrape "Solution distance" lines.
+
+use super::*;
+
+use serde::Serialize;
+
+/// One solver's result.
+#[derive(Serialize, Debug)]
+pub struct SolverRun {
+  /// "jeff_algo", "brute_algo" or "jeff_algo::solve_fast"
+  pub solver: String,
+  /// Visiting order as the file's city numbers
+  pub tour: Vec<CityNum>,
+  pub length: CityWeight,
+  pub runtime_ms: f64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SolveReport {
+  /// File stem, e.g. "berlin52"
+  pub instance: String,
+  pub file: String,
+  pub num_cities: usize,
+  /// "euclidean", "haversine", "geo", or "explicit" for files carrying their own weights
+  pub distance: String,
+  pub symmetric: bool,
+  pub solvers: Vec<SolverRun>,
+  /// Whether every solver found the same tour (reversals count when weights are symmetric),
+  /// null with fewer than 2 solvers
+  pub tours_agree: Option<bool>,
+  /// Whether every solver's tour has the same length, true for different tours tied at the optimum
+  pub lengths_agree: Option<bool>,
+}
+
+impl SolveReport {
+  pub fn new(file_arg: &str, num_cities: usize, distance: &str, symmetric: bool) -> SolveReport {
+    SolveReport {
+      instance: Path::new(file_arg).file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default(),
+      file: file_arg.to_string(),
+      num_cities,
+      distance: distance.to_string(),
+      symmetric,
+      solvers: vec![],
+      tours_agree: None,
+      lengths_agree: None,
+    }
+  }
+
+  /// Records tour (indexes into node_coordinates) under the file's own city numbers.
+  pub fn add_run(&mut self, solver: &str, node_coordinates: &[(CityNum, CityXYCoord, CityXYCoord)], tour: &[CityNum], length: CityWeight, runtime: std::time::Duration) {
+    self.solvers.push(SolverRun {
+      solver: solver.to_string(),
+      tour: tour.iter().map(|citynum| node_coordinates[*citynum].0).collect(),
+      length,
+      runtime_ms: runtime.as_secs_f64() * 1000.0,
+    });
+  }
+
+  /// Fills in tours_agree and lengths_agree once every solver ran; is_same_tour compares 2 tours.
+  pub fn compare_runs(&mut self, is_same_tour: &dyn Fn(&Vec<CityNum>, &Vec<CityNum>) -> bool) {
+    if self.solvers.len() < 2 {
+      return;
+    }
+    self.tours_agree = Some(self.solvers.windows(2).all(|pair| is_same_tour(&pair[0].tour, &pair[1].tour)));
+    self.lengths_agree = Some(self.solvers.windows(2).all(|pair| (pair[0].length - pair[1].length).abs() <= fp_epsilon * pair[0].length.max(1.0)));
+  }
+
+  pub fn to_json(&self) -> String {
+    serde_json::to_string_pretty(self).unwrap_or_default()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn report_uses_file_city_numbers() {
+    let node_coordinates: Vec<(CityNum, CityXYCoord, CityXYCoord)> = vec![(1, 2.0, 2.0), (2, 6.0, 2.0), (3, 6.0, 5.0)];
+    let mut report = SolveReport::new("tsp/tri3.tsp", 3, "euclidean", true);
+    report.add_run("jeff_algo", &node_coordinates, &[2, 0, 1], 12.0, std::time::Duration::from_millis(3));
+    report.compare_runs(&|a, b| a == b);
+    assert_eq!(report.tours_agree, None);
+    report.add_run("brute_algo", &node_coordinates, &[0, 1, 2], 12.0, std::time::Duration::from_millis(9));
+    report.compare_runs(&|a, b| is_identical_path(a, b));
+
+    let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+    assert_eq!(json["instance"], "tri3");
+    assert_eq!(json["num_cities"], 3);
+    assert_eq!(json["solvers"][0]["tour"], serde_json::json!([3, 1, 2]));
+    assert_eq!(json["solvers"][0]["runtime_ms"], 3.0);
+    assert_eq!(json["tours_agree"], true);
+    assert_eq!(json["lengths_agree"], true);
+  }
+}
@@ -0,0 +1,331 @@
+/*
+ *  tsp-sol - an experimental environment for traveling salesman solution analysis
+ *  Copyright (C) 2023  Jeffrey McAteer <jeffrey@jmcateer.com>
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; version 2 of the License ONLY.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License along
+ *  with this program; if not, write to the Free Software Foundation, Inc.,
+ *  51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+ */
+
+use super::*;
+
+use serde::{Serialize, Deserialize};
+
+use crate::keyframes::{Easing, CityPath, CityTransform, interpolate_cities, round_coordinates};
+use crate::jeff_algo::{InsertionOrder, InitialTour, SolveOptions};
+
+/// Which solver a scan colors its regions by.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScenarioSolver {
+  Brute,
+  Jalgo,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ScenarioCity {
+  #[serde(default)]
+  pub name: String,
+  pub x: fp,
+  pub y: fp,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct ScenarioBounds {
+  pub x_min: fp,
+  pub x_max: fp,
+  pub y_min: fp,
+  pub y_max: fp,
+}
+
+/// An arrangement of the scenario's cities; multi-pattern-scan moves between these in order.
+/// A keyframe either lists every city or gives a transform of the previous keyframe's arrangement.
+/// easing and paths describe how the cities travel from the previous keyframe to this one.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ScenarioKeyframe {
+  #[serde(default)]
+  pub cities: Vec<ScenarioCity>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub transform: Option<CityTransform>,
+  #[serde(default)]
+  pub easing: Easing,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub paths: Vec<CityPath>,
+}
+
+impl ScenarioKeyframe {
+  pub fn from_cities(cities: Vec<ScenarioCity>) -> ScenarioKeyframe {
+    ScenarioKeyframe { cities, transform: None, easing: Easing::Linear, paths: vec![] }
+  }
+}
+
+/// Everything a research command needs to reproduce a run, read from `--scenario file.json|file.toml`
+/// instead of TSP_INITIAL_COORDS / TSP_ENDING_COORDS + positional arguments.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Scenario {
+  #[serde(default)]
+  pub name: String,
+  pub cities: Vec<ScenarioCity>,
+  #[serde(default = "default_bounds")]
+  pub bounds: ScenarioBounds,
+  #[serde(default = "default_granularity")]
+  pub granularity: fp,
+  #[serde(default = "default_steps")]
+  pub steps: usize,
+  #[serde(default)]
+  pub keyframes: Vec<ScenarioKeyframe>,
+  #[serde(default = "default_solver")]
+  pub solver: ScenarioSolver,
+  /// Frames per second of the multi-pattern-scan animations.
+  #[serde(default = "default_fps")]
+  pub fps: usize,
+  /// Order jeff_algo inserts cities in, for the jalgo side of every comparison.
+  #[serde(default)]
+  pub insertion_order: InsertionOrder,
+  /// Sub-tour jeff_algo starts inserting into.
+  #[serde(default)]
+  pub initial_tour: InitialTour,
+  /// Cities jeff_algo removes and re-inserts around every insertion.
+  #[serde(default = "default_deep")]
+  pub deep: usize,
+}
+
+fn default_bounds() -> ScenarioBounds {
+  ScenarioBounds { x_min: x_min_bound, x_max: x_max_bound, y_min: y_min_bound, y_max: y_max_bound }
+}
+
+fn default_granularity() -> fp {
+  0.25
+}
+
+fn default_deep() -> usize {
+  jeff_algo::DEFAULT_DEEP
+}
+
+fn default_steps() -> usize {
+  10
+}
+
+fn default_solver() -> ScenarioSolver {
+  ScenarioSolver::Brute
+}
+
+fn default_fps() -> usize {
+  5
+}
+
+impl Scenario {
+  /// Reads a scenario, picking TOML for *.toml files and JSON for everything else.
+  pub fn load<P: AsRef<Path>>(file_path: P) -> Result<Scenario, String> {
+    let file_path = file_path.as_ref();
+    let contents = fs::read_to_string(file_path).map_err(|e| format!("Cannot read scenario {}: {}", file_path.display(), e))?;
+
+    let is_toml = file_path.extension().map(|ext| ext.eq_ignore_ascii_case("toml")).unwrap_or(false);
+    let scenario: Scenario = if is_toml {
+      toml::from_str(&contents).map_err(|e| format!("Error parsing scenario {}: {}", file_path.display(), e))?
+    }
+    else {
+      serde_json::from_str(&contents).map_err(|e| format!("Error parsing scenario {}: {}", file_path.display(), e))?
+    };
+
+    scenario.validate()?;
+    Ok(scenario)
+  }
+
+  /// Builds the scenario the coordinate-string workflow describes: --initial-coords / TSP_INITIAL_COORDS (or n random
+  /// cities) and, for multi-pattern-scan, --ending-coords / TSP_ENDING_COORDS (or another n random cities) as the last keyframe.
+  pub fn from_coords(initial_coords: Option<&str>, ending_coords: Option<&str>, n: usize, granularity: fp, steps: usize, with_ending: bool) -> Result<Scenario, String> {
+    let cities = given_or_random_cities(n, initial_coords, "--initial-coords")?;
+    let mut keyframes = vec![];
+    if with_ending {
+      keyframes.push(ScenarioKeyframe::from_cities(cities.clone()));
+      keyframes.push(ScenarioKeyframe::from_cities(given_or_random_cities(n, ending_coords, "--ending-coords")?));
+    }
+    let scenario = Scenario {
+      name: String::new(),
+      cities,
+      bounds: default_bounds(),
+      granularity,
+      steps,
+      keyframes,
+      solver: default_solver(),
+      fps: default_fps(),
+      insertion_order: InsertionOrder::default(),
+      initial_tour: InitialTour::default(),
+      deep: default_deep(),
+    };
+    scenario.validate()?;
+    Ok(scenario)
+  }
+
+  pub fn random(n: usize, granularity: fp) -> Scenario {
+    Scenario {
+      name: String::new(),
+      cities: random_cities(n),
+      bounds: default_bounds(),
+      granularity,
+      steps: default_steps(),
+      keyframes: vec![],
+      solver: default_solver(),
+      fps: default_fps(),
+      insertion_order: InsertionOrder::default(),
+      initial_tour: InitialTour::default(),
+      deep: default_deep(),
+    }
+  }
+
+  pub fn validate(&self) -> Result<(), String> {
+    if self.cities.is_empty() {
+      return Err("Scenario must contain at least 1 city".to_string());
+    }
+    if self.bounds.x_min >= self.bounds.x_max || self.bounds.y_min >= self.bounds.y_max {
+      return Err(format!("Scenario bounds are empty: {:?}", self.bounds));
+    }
+    if self.granularity <= 0.0 {
+      return Err(format!("Scenario granularity must be positive, got {}", self.granularity));
+    }
+    if self.steps < 1 {
+      return Err("Scenario steps must be at least 1".to_string());
+    }
+    if self.fps < 1 || self.fps > u16::MAX as usize {
+      return Err(format!("Scenario fps must be between 1 and {}, got {}", u16::MAX, self.fps));
+    }
+    for (i, keyframe) in self.keyframes.iter().enumerate() {
+      match (keyframe.cities.is_empty(), keyframe.transform.is_some()) {
+        (true, false) => {
+          return Err(format!("Keyframe {} needs either cities or a transform", i));
+        }
+        (false, true) => {
+          return Err(format!("Keyframe {} has both cities and a transform, pick one", i));
+        }
+        (false, false) if keyframe.cities.len() != self.cities.len() => {
+          return Err(format!("Keyframe {} has {} cities but the scenario has {}", i, keyframe.cities.len(), self.cities.len()));
+        }
+        _ => { }
+      }
+      for path in keyframe.paths.iter() {
+        if path.city() >= self.cities.len() {
+          return Err(format!("Keyframe {} has a path for city {} but the scenario has {} cities", i, path.city(), self.cities.len()));
+        }
+      }
+    }
+    Ok(())
+  }
+
+  pub fn save<P: AsRef<Path>>(&self, file_path: P) {
+    let file_path = file_path.as_ref();
+    match serde_json::to_string_pretty(self) {
+      Ok(json_s) => {
+        if let Err(e) = fs::write(file_path, json_s) {
+          println!("Error writing {}: {:?}", file_path.display(), e);
+        }
+      }
+      Err(e) => {
+        println!("Error serializing scenario for {}: {:?}", file_path.display(), e);
+      }
+    }
+  }
+
+  pub fn node_coordinates(&self) -> Vec<(CityNum, CityXYCoord, CityXYCoord)> {
+    cities_to_node_coordinates(&self.cities)
+  }
+
+  /// Absolute city positions at each keyframe, applying transform keyframes to the arrangement before them.
+  pub fn keyframe_coordinates(&self) -> Vec<Vec<(CityNum, CityXYCoord, CityXYCoord)>> {
+    if self.keyframes.is_empty() {
+      return vec![ self.node_coordinates() ];
+    }
+    let mut resolved: Vec<Vec<(CityNum, CityXYCoord, CityXYCoord)>> = vec![];
+    for keyframe in self.keyframes.iter() {
+      let coords = match &keyframe.transform {
+        Some(transform) => transform.apply(resolved.last().unwrap_or(&self.node_coordinates()), 1.0),
+        None => cities_to_node_coordinates(&keyframe.cities),
+      };
+      resolved.push(coords);
+    }
+    resolved
+  }
+
+  pub fn jalgo_options(&self) -> SolveOptions {
+    SolveOptions {
+      insertion_order: self.insertion_order,
+      initial_tour: self.initial_tour,
+      deep: self.deep,
+    }
+  }
+
+  pub fn bounds_tuple(&self) -> (fp, fp, fp, fp) {
+    (self.bounds.x_min, self.bounds.x_max, self.bounds.y_min, self.bounds.y_max)
+  }
+
+  /// City positions for frame step_num of total_steps. Each keyframe segment gets an equal share of the steps;
+  /// within a segment the cities follow the target keyframe's easing, paths or transform.
+  pub fn frame_coordinates(&self, step_num: usize, total_steps: usize) -> Vec<(CityNum, CityXYCoord, CityXYCoord)> {
+    let keyframes = self.keyframe_coordinates();
+    if keyframes.len() < 2 || total_steps < 1 {
+      return keyframes[0].clone();
+    }
+    let num_segments = keyframes.len() - 1;
+    let progress = (step_num as fp / total_steps as fp) * num_segments as fp;
+    let segment_i = (progress.floor() as usize).min(num_segments - 1);
+    let segment_t = progress - segment_i as fp;
+
+    let target = &self.keyframes[segment_i + 1];
+    let t = target.easing.apply(segment_t);
+    let frame = match &target.transform {
+      Some(transform) => transform.apply(&keyframes[segment_i], t),
+      None => interpolate_cities(&keyframes[segment_i], &keyframes[segment_i + 1], &target.paths, t),
+    };
+    round_coordinates(frame)
+  }
+}
+
+fn cities_to_node_coordinates(cities: &[ScenarioCity]) -> Vec<(CityNum, CityXYCoord, CityXYCoord)> {
+  cities.iter().enumerate().map(|(i, c)| (i, c.x, c.y) ).collect()
+}
+
+fn random_cities(n: usize) -> Vec<ScenarioCity> {
+  let mut rng = rand::thread_rng();
+  (0..n).map(|i| ScenarioCity {
+    name: format!("{}", i),
+    x: rng.gen_range(x_min, x_max),
+    y: rng.gen_range(y_min, y_max),
+  }).collect()
+}
+
+fn given_or_random_cities(n: usize, coords_s: Option<&str>, option_name: &str) -> Result<Vec<ScenarioCity>, String> {
+  match coords_s {
+    Some(coords_s) => {
+      let cities = parse_coordinate_pairs(coords_s).map_err(|e| format!("{}: {}", option_name, e))?;
+      if cities.len() < n {
+        return Err(format!("{} has {} cities but {} were requested", option_name, cities.len(), n));
+      }
+      Ok(cities.into_iter().take(n).collect())
+    }
+    None => Ok(random_cities(n)),
+  }
+}
+
+/// Parses the "x,y x,y ..." strings used by TSP_INITIAL_COORDS, reporting bad pairs instead of panicking.
+pub fn parse_coordinate_pairs(coords_s: &str) -> Result<Vec<ScenarioCity>, String> {
+  let mut cities = vec![];
+  for pair in coords_s.split_whitespace() {
+    let x_and_y_s: Vec<&str> = pair.split(',').collect();
+    if x_and_y_s.len() != 2 {
+      return Err(format!("expected x,y but found {:?}", pair));
+    }
+    let x: fp = x_and_y_s[0].trim().parse().map_err(|e| format!("bad x in {:?}: {}", pair, e))?;
+    let y: fp = x_and_y_s[1].trim().parse().map_err(|e| format!("bad y in {:?}: {}", pair, e))?;
+    cities.push(ScenarioCity { name: format!("{}", cities.len()), x, y });
+  }
+  Ok(cities)
+}
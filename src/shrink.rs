@@ -0,0 +1,214 @@
+/*
+ *  tsp-sol - an experimental environment for traveling salesman solution analysis
+ *  Copyright (C) 2023  Jeffrey McAteer <jeffrey@jmcateer.com>
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; version 2 of the License ONLY.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License along
+ *  with this program; if not, write to the Free Software Foundation, Inc.,
+ *  51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+ */
+
+use super::*;
+
+use crate::scenario::ScenarioCity;
+use crate::jeff_algo::SolveOptions;
+
+/// Coordinates are snapped to these many decimals, coarsest first, while shrinking.
+const NUDGE_DECIMALS: [i32; 3] = [0, 1, 2];
+
+/// Smallest city found where jeff_algo still disagrees with brute_algo.
+pub struct ShrinkResult {
+  pub node_coordinates: Vec<(CityNum, CityXYCoord, CityXYCoord)>,
+  pub jeff_sol: Vec<CityNum>,
+  pub brute_sol: Vec<CityNum>,
+  pub jeff_len: fp,
+  pub brute_len: fp,
+  pub attempts: usize,
+}
+
+/// SHRINK_FAILURES=f turns off shrinking in delta and selective, it is on by default.
+pub fn shrink_failures_env_val() -> bool {
+  if let Ok(val) = env::var("SHRINK_FAILURES") {
+    if val.contains("t") || val.contains("T") {
+      return true;
+    }
+    return false;
+  }
+  true
+}
+
+/// Returns (jeff_sol, brute_sol) when jeff_algo finds a longer tour than brute_algo for these cities.
+pub fn find_mismatch(node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>, jalgo_options: &SolveOptions, thread_pool: &ThreadPool) -> Option<(Vec<CityNum>, Vec<CityNum>)> {
+  let weights = compute_weight_coords(node_coordinates);
+  let jeff_sol = jeff_algo::solve_with_options(node_coordinates, &weights, None, jalgo_options);
+  let brute_sol = brute_algo::solve(node_coordinates, &weights, None, thread_pool);
+
+  let distance_diff = compute_dist(&weights, &jeff_sol) - compute_dist(&weights, &brute_sol);
+  if distance_diff.abs() > fp_epsilon && !is_identical_tour(&jeff_sol, &brute_sol, &weights) { // account for floating point errors
+    return Some((jeff_sol, brute_sol));
+  }
+  None
+}
+
+/// Greedily minimizes a failing city: drop any city whose removal keeps the mismatch,
+/// then snap each coordinate to as few decimals as still reproduces it, repeating until nothing changes.
+/// Returns None if node_coordinates does not fail to begin with.
+pub fn shrink(node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>, jalgo_options: &SolveOptions, thread_pool: &ThreadPool) -> Option<ShrinkResult> {
+  let (current, (jeff_sol, brute_sol), attempts) = shrink_while(node_coordinates, &|candidate| find_mismatch(candidate, jalgo_options, thread_pool))?;
+  let weights = compute_weight_coords(&current);
+  let jeff_len = compute_dist(&weights, &jeff_sol);
+  let brute_len = compute_dist(&weights, &brute_sol);
+  Some(ShrinkResult {
+    node_coordinates: current,
+    jeff_sol,
+    brute_sol,
+    jeff_len,
+    brute_len,
+    attempts,
+  })
+}
+
+/// The shrinking behind shrink for any failure: fails returns Some(details) for cities that still fail.
+/// Returns the smallest failing city (renumbered from 0), its last details and the number of cities tried.
+pub fn shrink_while<T>(node_coordinates: &Vec<City>, fails: &dyn Fn(&Vec<City>) -> Option<T>) -> Option<(Vec<City>, T, usize)> {
+  let mut attempts = 1;
+  let mut failure = fails(node_coordinates)?;
+  let mut current = renumber(node_coordinates.clone());
+
+  let mut changed = true;
+  while changed {
+    changed = false;
+
+    // Removing cities first, it is the biggest simplification
+    let mut city_i = 0;
+    while city_i < current.len() && current.len() > 4 {
+      let mut candidate = current.clone();
+      candidate.remove(city_i);
+      let candidate = renumber(candidate);
+      attempts += 1;
+      if let Some(candidate_failure) = fails(&candidate) {
+        println!("Shrink: removed city {}, {} cities still fail", city_i, candidate.len());
+        current = candidate;
+        failure = candidate_failure;
+        changed = true;
+        // the next city has shifted into city_i
+      }
+      else {
+        city_i += 1;
+      }
+    }
+
+    // Then nudging each coordinate onto the coarsest grid that still fails
+    for city_i in 0..current.len() {
+      for axis in 0..2 {
+        for decimals in NUDGE_DECIMALS.iter() {
+          let mut candidate = current.clone();
+          let scale = (10.0 as fp).powi(*decimals);
+          if axis == 0 {
+            candidate[city_i].1 = (candidate[city_i].1 * scale).round() / scale;
+          }
+          else {
+            candidate[city_i].2 = (candidate[city_i].2 * scale).round() / scale;
+          }
+          if candidate == current || candidate.iter().filter(|c| c.1 == candidate[city_i].1 && c.2 == candidate[city_i].2).count() > 1 {
+            continue; // nothing to nudge, or snapping would stack 2 cities
+          }
+          attempts += 1;
+          if let Some(candidate_failure) = fails(&candidate) {
+            current = candidate;
+            failure = candidate_failure;
+            changed = true;
+            break;
+          }
+        }
+      }
+    }
+  }
+
+  Some((current, failure, attempts))
+}
+
+/// Shrinks a failing city and writes the counterexample to prefix_dir: a scenario file
+/// that reruns it, both solutions as images and jeff_algo's step-by-step images.
+pub fn shrink_and_report(node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>, jalgo_options: &SolveOptions, prefix_dir: &str, thread_pool: &ThreadPool) -> Option<ShrinkResult> {
+  println!("Shrinking {}-city failure...", node_coordinates.len());
+  let result = match shrink(node_coordinates, jalgo_options, thread_pool) {
+    Some(result) => result,
+    None => {
+      println!("Shrink: the {}-city failure did not reproduce", node_coordinates.len());
+      return None;
+    }
+  };
+
+  let coords_s: Vec<String> = result.node_coordinates.iter().map(|(_i, x, y)| format!("{},{}", x, y) ).collect();
+  println!("Smallest counterexample: {} cities after {} attempts (from {})", result.node_coordinates.len(), result.attempts, node_coordinates.len());
+  println!("  TSP_INITIAL_COORDS='{}'", coords_s.join(" "));
+  println!("  jeff_algo  {:.4} {:?}", result.jeff_len, result.jeff_sol);
+  println!("  brute_algo {:.4} {:?}", result.brute_len, result.brute_sol);
+
+  if let Err(e) = fs::create_dir_all(prefix_dir) {
+    println!("Cannot create {}: {}", prefix_dir, e);
+    return Some(result);
+  }
+  let mut scenario = Scenario::random(result.node_coordinates.len(), 0.05);
+  scenario.name = format!("shrunk-{}", result.node_coordinates.len());
+  scenario.insertion_order = jalgo_options.insertion_order;
+  scenario.initial_tour = jalgo_options.initial_tour;
+  scenario.deep = jalgo_options.deep;
+  scenario.cities = result.node_coordinates.iter().map(|(i, x, y)| ScenarioCity { name: format!("{}", i), x: *x, y: *y }).collect();
+  let scenario_file = Path::new(prefix_dir).join("shrunk.scenario.json");
+  scenario.save(&scenario_file);
+
+  save_state_image(format!("{}shrunk-jeff.png", prefix_dir), &result.jeff_sol, &result.node_coordinates);
+  save_state_image(format!("{}shrunk-brute.png", prefix_dir), &result.brute_sol, &result.node_coordinates);
+  let weights = compute_weight_coords(&result.node_coordinates);
+  jeff_algo::solve_with_options(&result.node_coordinates, &weights, Some(format!("{}shrunk-steps/", prefix_dir)), jalgo_options);
+
+  println!("  see {} (pattern-scan it with --scenario {})", prefix_dir, scenario_file.display());
+  Some(result)
+}
+
+fn renumber(mut node_coordinates: Vec<(CityNum, CityXYCoord, CityXYCoord)>) -> Vec<(CityNum, CityXYCoord, CityXYCoord)> {
+  for (i, city) in node_coordinates.iter_mut().enumerate() {
+    city.0 = i;
+  }
+  node_coordinates
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // Stands in for a solver mismatch: fails while 2 or more cities lie right of x = 9.5
+  fn two_cities_right_of_9_5(node_coordinates: &[City]) -> Option<usize> {
+    let num_right = node_coordinates.iter().filter(|(_i, x, _y)| *x > 9.5).count();
+    if num_right >= 2 { Some(num_right) } else { None }
+  }
+
+  #[test]
+  fn shrinks_to_a_smaller_city_that_still_fails() {
+    let node_coordinates: Vec<(CityNum, CityXYCoord, CityXYCoord)> = vec![
+      (0, 3.21, 4.87), (1, 10.37, 7.16), (2, 5.55, 11.02), (3, 11.21, 3.33),
+      (4, 6.74, 8.19), (5, 13.9, 12.45), (6, 4.08, 6.66),
+    ];
+    let (shrunk, num_right, attempts) = shrink_while(&node_coordinates, &|cities| two_cities_right_of_9_5(cities)).unwrap();
+    assert!(shrunk.len() < node_coordinates.len());
+    assert_eq!(shrunk.len(), 4); // shrink never goes below 4 cities
+    assert_eq!(num_right, 2);
+    assert_eq!(two_cities_right_of_9_5(&shrunk), Some(2));
+    assert!(shrunk.iter().all(|(_i, x, y)| x.fract() == 0.0 && y.fract() == 0.0), "{:?}", shrunk);
+    assert!(attempts > 1);
+
+    // a fixed point: shrinking the result again changes nothing
+    let (reshrunk, _, _) = shrink_while(&shrunk, &|cities| two_cities_right_of_9_5(cities)).unwrap();
+    assert_eq!(reshrunk, shrunk);
+  }
+}
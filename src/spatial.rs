@@ -0,0 +1,151 @@
+/*
+ *  tsp-sol - an experimental environment for traveling salesman solution analysis
+ *  Copyright (C) 2023  Jeffrey McAteer <jeffrey@jmcateer.com>
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; version 2 of the License ONLY.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License along
+ *  with this program; if not, write to the Free Software Foundation, Inc.,
+ *  51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+ */
+
+use super::*;
+
+/// Cities per grid cell once every city has been added; small cells keep ring searches short.
+const CITIES_PER_CELL: usize = 2;
+
+/// Uniform grid over the bounding box of a city, holding the indexes of the cities added so far.
+/// Used to find the tour cities near a point without looking at the whole tour.
+pub struct CityGrid {
+  min_x: fp,
+  min_y: fp,
+  cell_size: fp,
+  cols: usize,
+  rows: usize,
+  cells: Vec<Vec<(CityNum, CityXYCoord, CityXYCoord)>>,
+  len: usize,
+}
+
+impl CityGrid {
+  /// Empty grid sized for all of node_coordinates; cities are added with insert.
+  pub fn new(node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>) -> CityGrid {
+    let (smallest_x, largest_y, largest_x, smallest_y) = get_point_extents(node_coordinates);
+    let width = (largest_x - smallest_x).max(fp_epsilon);
+    let height = (largest_y - smallest_y).max(fp_epsilon);
+    let num_cells = (node_coordinates.len() / CITIES_PER_CELL).max(1) as fp;
+    let cell_size = ((width * height) / num_cells).sqrt().max(fp_epsilon);
+    let cols = ((width / cell_size) as usize) + 1;
+    let rows = ((height / cell_size) as usize) + 1;
+    CityGrid {
+      min_x: smallest_x,
+      min_y: smallest_y,
+      cell_size,
+      cols,
+      rows,
+      cells: vec![vec![]; cols * rows],
+      len: 0,
+    }
+  }
+
+  pub fn len(&self) -> usize {
+    self.len
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len == 0
+  }
+
+  fn cell_of(&self, x: fp, y: fp) -> (usize, usize) {
+    let col = (((x - self.min_x) / self.cell_size).max(0.0) as usize).min(self.cols - 1);
+    let row = (((y - self.min_y) / self.cell_size).max(0.0) as usize).min(self.rows - 1);
+    (col, row)
+  }
+
+  /// Adds the city at index citynum of node_coordinates.
+  pub fn insert(&mut self, node_coordinates: &[(CityNum, CityXYCoord, CityXYCoord)], citynum: CityNum) {
+    let (col, row) = self.cell_of(node_coordinates[citynum].1, node_coordinates[citynum].2);
+    self.cells[(row * self.cols) + col].push((citynum, node_coordinates[citynum].1, node_coordinates[citynum].2));
+    self.len += 1;
+  }
+
+  /// Cities close to (x, y), always including the min_count nearest (or every city if there are fewer).
+  /// Rings of cells are searched outwards; once min_count cities are found, rings keep being added
+  /// until no unsearched cell can hold a city closer than the min_count-th one.
+  pub fn nearby(&self, x: fp, y: fp, min_count: usize) -> Vec<CityNum> {
+    let min_count = min_count.min(self.len);
+    let (col, row) = self.cell_of(x, y);
+    let max_ring = self.cols.max(self.rows);
+    let mut found = vec![];
+    let mut needed_dist: Option<fp> = None;
+    for ring in 0..=max_ring {
+      if let Some(needed_dist) = needed_dist {
+        // every city in this ring is at least ring-1 cells away from (x, y)
+        if ((ring as fp) - 1.0) * self.cell_size > needed_dist {
+          break;
+        }
+      }
+      self.push_ring(col, row, ring, &mut found);
+      if needed_dist.is_none() && found.len() >= min_count.max(1) {
+        let mut dists: Vec<fp> = found.iter().map(|(_i, cx, cy)| ((cx - x) * (cx - x) + (cy - y) * (cy - y)).sqrt() ).collect();
+        let kth = min_count.max(1) - 1;
+        dists.select_nth_unstable_by(kth, |a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        needed_dist = Some(dists[kth]);
+      }
+    }
+    found.iter().map(|(i, _x, _y)| *i).collect()
+  }
+
+  fn push_ring(&self, col: usize, row: usize, ring: usize, found: &mut Vec<(CityNum, CityXYCoord, CityXYCoord)>) {
+    let (col, row, ring) = (col as isize, row as isize, ring as isize);
+    for r in (row - ring)..=(row + ring) {
+      if r < 0 || r >= self.rows as isize {
+        continue;
+      }
+      for c in (col - ring)..=(col + ring) {
+        if c < 0 || c >= self.cols as isize {
+          continue;
+        }
+        // only the outline of the ring, the inside was pushed by smaller rings
+        if r != row - ring && r != row + ring && c != col - ring && c != col + ring {
+          continue;
+        }
+        found.extend_from_slice(&self.cells[(r as usize * self.cols) + c as usize]);
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn nearby_contains_the_nearest_cities() {
+    let mut rng = fastrand::Rng::with_seed(7);
+    let node_coordinates: Vec<(CityNum, CityXYCoord, CityXYCoord)> = (0..500).map(|i| (i, rng.f32() * 100.0, rng.f32() * 40.0) ).collect();
+    let mut grid = CityGrid::new(&node_coordinates);
+    for i in 0..node_coordinates.len() {
+      grid.insert(&node_coordinates, i);
+    }
+    for _ in 0..50 {
+      let (x, y) = (rng.f32() * 100.0, rng.f32() * 40.0);
+      let mut by_dist: Vec<CityNum> = (0..node_coordinates.len()).collect();
+      by_dist.sort_by(|a, b| {
+        let da = (node_coordinates[*a].1 - x).powi(2) + (node_coordinates[*a].2 - y).powi(2);
+        let db = (node_coordinates[*b].1 - x).powi(2) + (node_coordinates[*b].2 - y).powi(2);
+        da.partial_cmp(&db).unwrap()
+      });
+      let nearby = grid.nearby(x, y, 8);
+      for nearest in by_dist[..8].iter() {
+        assert!(nearby.contains(nearest), "city {} missing near ({}, {})", nearest, x, y);
+      }
+    }
+  }
+}
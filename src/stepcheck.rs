@@ -0,0 +1,128 @@
+/*
+ *  tsp-sol - an experimental environment for traveling salesman solution analysis
+ *  Copyright (C) 2023  Jeffrey McAteer <jeffrey@jmcateer.com>
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; version 2 of the License ONLY.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License along
+ *  with this program; if not, write to the Free Software Foundation, Inc.,
+ *  51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+ */
+
+// Checks the readme's invariant one insertion at a time: if the tour is optimal for k cities,
+// jeff_algo's insertion keeps it optimal for k+1. Every intermediate tour is compared to
+// brute_algo on the same subset of cities.
+
+use super::*;
+
+use crate::jeff_algo::SolveOptions;
+
+/// The first intermediate tour that is longer than the optimal tour of its cities.
+pub struct StepBreak {
+  /// 0 is the initial tour, 1 the tour after the first insertion, ...
+  pub step: usize,
+  pub tour: Vec<CityNum>,
+  pub optimal_tour: Vec<CityNum>,
+  pub tour_len: fp,
+  pub optimal_len: fp,
+}
+
+impl StepBreak {
+  /// How much longer than optimal the tour is, in percent.
+  pub fn gap_percent(&self) -> fp {
+    100.0 * (self.tour_len - self.optimal_len) / self.optimal_len.max(fp_epsilon)
+  }
+}
+
+/// Solves node_coordinates with jeff_algo and brute-forces every intermediate tour's cities,
+/// printing one line per step. Returns the first step that is not optimal, None if every step is.
+pub fn first_broken_step(node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>, jalgo_options: &SolveOptions, thread_pool: &ThreadPool) -> Option<StepBreak> {
+  let weights = compute_weight_coords(node_coordinates);
+  let steps = jeff_algo::solve_steps(node_coordinates, &weights, jalgo_options);
+
+  for (step, tour) in steps.iter().enumerate() {
+    let tour_len = compute_dist(&weights, tour);
+    if tour.len() <= 3 {
+      println!("step {:>3}: {:>3} cities  jeff_algo {:.4}  (every tour of 3 cities is optimal)", step, tour.len(), tour_len);
+      continue;
+    }
+
+    // brute_algo works on the subset renumbered from 0, subset maps its answer back
+    let mut subset = tour.clone();
+    subset.sort();
+    let subset_coords: Vec<(CityNum, CityXYCoord, CityXYCoord)> = subset.iter().enumerate().map(|(i, citynum)| (i, node_coordinates[*citynum].1, node_coordinates[*citynum].2) ).collect();
+    let subset_weights = compute_weight_coords(&subset_coords);
+    let optimal_tour: Vec<CityNum> = brute_algo::solve(&subset_coords, &subset_weights, None, thread_pool).iter().map(|i| subset[*i]).collect();
+    let optimal_len = compute_dist(&weights, &optimal_tour);
+
+    if tour_len - optimal_len > fp_epsilon && !is_identical_tour(tour, &optimal_tour, &weights) {
+      println!("step {:>3}: {:>3} cities  jeff_algo {:.4}  optimal {:.4}  BROKEN", step, tour.len(), tour_len, optimal_len);
+      return Some(StepBreak { step, tour: tour.clone(), optimal_tour, tour_len, optimal_len });
+    }
+    println!("step {:>3}: {:>3} cities  jeff_algo {:.4}  optimal {:.4}  ok", step, tour.len(), tour_len, optimal_len);
+  }
+  None
+}
+
+/// Runs first_broken_step on node_coordinates and then on num_runs-1 random cities of the same size,
+/// stopping at the first break. The break is summarized and saved to prefix_dir as images of both
+/// tours plus the step trace of the whole run.
+pub fn check_and_report(node_coordinates: &[(CityNum, CityXYCoord, CityXYCoord)], num_runs: usize, jalgo_options: &SolveOptions, prefix_dir: &str, thread_pool: &ThreadPool) -> Option<StepBreak> {
+  let mut node_coordinates = node_coordinates.to_vec();
+  for run_i in 0..num_runs.max(1) {
+    if run_i > 0 {
+      node_coordinates = Scenario::random(node_coordinates.len(), 0.05).node_coordinates();
+    }
+    println!("Checking every step of jeff_algo on {} cities (run {} of {})", node_coordinates.len(), run_i + 1, num_runs.max(1));
+
+    let step_break = match first_broken_step(&node_coordinates, jalgo_options, thread_pool) {
+      Some(step_break) => step_break,
+      None => continue,
+    };
+
+    let coords_s: Vec<String> = node_coordinates.iter().map(|(_i, x, y)| format!("{},{}", x, y) ).collect();
+    println!("Invariant broke at step {} ({} of {} cities inserted): jeff_algo {:.4} is {:.3}% longer than optimal {:.4}",
+      step_break.step, step_break.tour.len(), node_coordinates.len(), step_break.tour_len, step_break.gap_percent(), step_break.optimal_len);
+    println!("  jeff_algo {:?}", step_break.tour);
+    println!("  optimal   {:?}", step_break.optimal_tour);
+    println!("  TSP_INITIAL_COORDS='{}'", coords_s.join(" "));
+
+    save_state_image(format!("{}step-{:03}-jeff.png", prefix_dir, step_break.step), &step_break.tour, &node_coordinates);
+    save_state_image(format!("{}step-{:03}-optimal.png", prefix_dir, step_break.step), &step_break.optimal_tour, &node_coordinates);
+    let weights = compute_weight_coords(&node_coordinates);
+    jeff_algo::solve_with_options(&node_coordinates, &weights, Some(prefix_dir.to_string()), jalgo_options);
+    println!("  see {}", prefix_dir);
+    return Some(step_break);
+  }
+  println!("Every step of {} run(s) stayed optimal", num_runs.max(1));
+  None
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn reports_the_first_broken_step() {
+    // found with `stepcheck 9 500 --deep 1`: the tours stay optimal up to 6 cities, inserting the 7th breaks it
+    let coords = [
+      (4.0206776, 4.688901), (5.7663383, 6.3060703), (4.424323, 6.107629), (10.409834, 6.665557), (10.6539955, 5.463212),
+      (10.082901, 6.6794014), (3.8476696, 4.4926815), (4.158247, 8.415159), (8.123304, 7.98514),
+    ];
+    let node_coordinates: Vec<(CityNum, CityXYCoord, CityXYCoord)> = coords.iter().enumerate().map(|(i, (x, y))| (i, *x, *y)).collect();
+    let jalgo_options = SolveOptions { deep: 1, ..SolveOptions::default() };
+
+    let step_break = first_broken_step(&node_coordinates, &jalgo_options, &ThreadPool::new(2)).unwrap();
+    assert_eq!(step_break.step, 4);
+    assert_eq!(step_break.tour.len(), 7);
+    assert!(step_break.tour_len > step_break.optimal_len + fp_epsilon);
+    assert!((step_break.gap_percent() - 0.107).abs() < 0.01, "{}", step_break.gap_percent());
+  }
+}
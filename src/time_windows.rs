@@ -0,0 +1,212 @@
+/*
+ *  tsp-sol - an experimental environment for traveling salesman solution analysis
+ *  Copyright (C) 2023  Jeffrey McAteer <jeffrey@jmcateer.com>
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; version 2 of the License ONLY.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License along
+ *  with this program; if not, write to the Free Software Foundation, Inc.,
+ *  51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+ */
+
+// Delivery windows: the tour leaves the depot at time 0, travel time is the edge weight, a vehicle
+// arriving before a city's earliest time waits, and arriving after its latest time is late.
+
+use super::*;
+
+use crate::jeff_algo::SolveOptions;
+
+/// [earliest, latest] arrival window and service time of every city, indexed like node_coordinates.
+pub struct TimeWindows {
+  pub windows: Vec<(fp, fp)>,
+  pub service_times: Vec<fp>,
+}
+
+impl TimeWindows {
+  /// Windows that never bind, for cities the sidecar file does not mention.
+  pub fn unconstrained(num_cities: usize) -> TimeWindows {
+    TimeWindows {
+      windows: vec![(0.0, fp::INFINITY); num_cities],
+      service_times: vec![0.0; num_cities],
+    }
+  }
+
+  /// Reads a sidecar file of "CITY EARLIEST LATEST [SERVICE]" lines, CITY being the number used in the
+  /// .tsp file. Empty lines and lines starting with # are skipped.
+  pub fn open(file_arg: &str, node_coordinates: &[(CityNum, CityXYCoord, CityXYCoord)]) -> Option<TimeWindows> {
+    let text = match fs::read_to_string(file_arg) {
+      Ok(text) => text,
+      Err(e) => {
+        report_error(&format!("Cannot open {}: {}", file_arg, e));
+        return None;
+      }
+    };
+
+    let mut time_windows = TimeWindows::unconstrained(node_coordinates.len());
+    for (line_i, line) in text.lines().enumerate() {
+      let line = line.trim();
+      if line.is_empty() || line.starts_with('#') {
+        continue;
+      }
+      let fields: Vec<&str> = line.split_whitespace().collect();
+      let citynum = fields.first()
+        .and_then(|id_s| id_s.parse::<usize>().ok())
+        .and_then(|id| node_coordinates.iter().position(|(file_id, _x, _y)| *file_id == id));
+      let numbers: Option<Vec<fp>> = fields.iter().skip(1).map(|n| n.parse::<fp>().ok()).collect();
+      match (citynum, numbers.as_deref()) {
+        (Some(citynum), Some([earliest, latest])) if earliest <= latest => {
+          time_windows.windows[citynum] = (*earliest, *latest);
+        }
+        (Some(citynum), Some([earliest, latest, service])) if earliest <= latest && *service >= 0.0 => {
+          time_windows.windows[citynum] = (*earliest, *latest);
+          time_windows.service_times[citynum] = *service;
+        }
+        _ => {
+          report_error(&format!("Err: {} line {}: expected \"CITY EARLIEST LATEST [SERVICE]\" for a city in the file, got {:?}", file_arg, line_i + 1, line));
+          return None;
+        }
+      }
+    }
+    Some(time_windows)
+  }
+}
+
+/// When each city of a tour is reached, for a tour starting at its depot tour[0].
+pub struct Schedule {
+  /// Arrival time at tour[i], the last entry is the return to the depot
+  pub arrivals: Vec<fp>,
+  /// How far past its latest time tour[i] (and finally the depot again) was reached, 0 when on time
+  pub lateness: Vec<fp>,
+}
+
+impl Schedule {
+  pub fn total_lateness(&self) -> fp {
+    self.lateness.iter().sum()
+  }
+
+  pub fn max_lateness(&self) -> fp {
+    self.lateness.iter().cloned().fold(0.0, fp::max)
+  }
+
+  pub fn is_feasible(&self) -> bool {
+    self.lateness.iter().all(|late| *late <= fp_epsilon)
+  }
+}
+
+pub fn schedule(tour: &[CityNum], weights: &[Vec<CityWeight>], time_windows: &TimeWindows) -> Schedule {
+  let mut arrivals = Vec::with_capacity(tour.len() + 1);
+  let mut lateness = Vec::with_capacity(tour.len() + 1);
+  let mut time: fp = 0.0;
+  for i in 0..=tour.len() {
+    let citynum = tour[i % tour.len()];
+    if i > 0 {
+      time += weights[tour[i - 1]][citynum];
+    }
+    let (earliest, latest) = time_windows.windows[citynum];
+    arrivals.push(time);
+    lateness.push((time - latest).max(0.0));
+    if i < tour.len() {
+      time = time.max(earliest) + time_windows.service_times[citynum];
+    }
+  }
+  Schedule { arrivals, lateness }
+}
+
+/// tour rotated so it starts at depot.
+pub fn starting_at(tour: &[CityNum], depot: CityNum) -> Vec<CityNum> {
+  let mut tour = tour.to_vec();
+  let depot_i = tour.iter().position(|c| *c == depot).unwrap_or(0);
+  tour.rotate_left(depot_i);
+  tour
+}
+
+// Feasible tours first, then shorter; between infeasible tours the one that is late by less.
+fn is_better(a: &(Vec<CityNum>, Schedule, fp), b: &(Vec<CityNum>, Schedule, fp)) -> bool {
+  match (a.1.is_feasible(), b.1.is_feasible()) {
+    (true, false) => true,
+    (false, true) => false,
+    (true, true) => a.2 < b.2 - fp_epsilon,
+    (false, false) => a.1.total_lateness() < b.1.total_lateness() - fp_epsilon,
+  }
+}
+
+/// Tour from depot keeping every window if jeff_algo can find one. Both directions of the plain
+/// jeff_algo tour are tried, then a build-up that inserts cities by deadline, each into the cheapest
+/// position that keeps the tour on time (jeff_algo::insert_point_step_feasible). A city with no
+/// feasible position goes where it adds the least lateness.
+pub fn solve_jalgo(node_coordinates: &Vec<(CityNum, CityXYCoord, CityXYCoord)>, weights: &Vec<Vec<CityWeight>>, time_windows: &TimeWindows, depot: CityNum, options: &SolveOptions) -> Vec<CityNum> {
+  let on_time = |tour: &Vec<CityNum>| schedule(tour, weights, time_windows).is_feasible();
+
+  let plain = starting_at(&jeff_algo::solve_with_options(node_coordinates, weights, None, options), depot);
+  let mut plain_reversed = plain.clone();
+  plain_reversed[1..].reverse();
+
+  let mut by_deadline: Vec<CityNum> = (0..weights.len()).filter(|c| *c != depot).collect();
+  by_deadline.sort_by(|a, b| {
+    let (a_window, b_window) = (time_windows.windows[*a], time_windows.windows[*b]);
+    a_window.1.partial_cmp(&b_window.1).unwrap_or(std::cmp::Ordering::Equal)
+      .then(a_window.0.partial_cmp(&b_window.0).unwrap_or(std::cmp::Ordering::Equal))
+  });
+  let mut built = vec![depot];
+  for citynum in by_deadline {
+    if jeff_algo::insert_point_step_feasible(&mut built, weights, citynum, &on_time).is_some() {
+      continue;
+    }
+    let least_late_i = (1..=built.len()).min_by(|a, b| {
+      let late = |insert_i: &usize| {
+        let mut tour = built.clone();
+        tour.insert(*insert_i, citynum);
+        schedule(&tour, weights, time_windows).total_lateness()
+      };
+      late(a).partial_cmp(&late(b)).unwrap_or(std::cmp::Ordering::Equal)
+    }).unwrap_or(1);
+    built.insert(least_late_i, citynum);
+  }
+
+  let mut best: Option<(Vec<CityNum>, Schedule, fp)> = None;
+  for tour in [plain, plain_reversed, built] {
+    let candidate = (tour.clone(), schedule(&tour, weights, time_windows), compute_dist(weights, &tour));
+    if best.as_ref().map(|best| is_better(&candidate, best)).unwrap_or(true) {
+      best = Some(candidate);
+    }
+  }
+  best.map(|(tour, _schedule, _dist)| tour).unwrap_or_default()
+}
+
+/// Shortest tour from depot that keeps every window, None if there is none.
+pub fn solve_brute(weights: &Vec<Vec<CityWeight>>, time_windows: &TimeWindows, depot: CityNum) -> Option<Vec<CityNum>> {
+  brute_algo::solve_st_filtered(weights, depot, &|tour| schedule(tour, weights, time_windows).is_feasible())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn windows_force_the_longer_direction() {
+    // a square visited clockwise or counter-clockwise costs the same; the window on city 3 picks one
+    let node_coordinates: Vec<(CityNum, CityXYCoord, CityXYCoord)> = vec![(0, 5.0, 5.0), (1, 10.0, 5.0), (2, 10.0, 10.0), (3, 5.0, 10.0)];
+    let weights = compute_weight_coords(&node_coordinates);
+    let mut time_windows = TimeWindows::unconstrained(4);
+    time_windows.windows[3] = (0.0, 6.0);
+    time_windows.service_times[3] = 1.0;
+
+    let brute = solve_brute(&weights, &time_windows, 0).expect("0 3 2 1 is on time");
+    assert_eq!(brute, vec![0, 3, 2, 1]);
+    let jeff = solve_jalgo(&node_coordinates, &weights, &time_windows, 0, &SolveOptions::default());
+    assert_eq!(jeff, vec![0, 3, 2, 1]);
+    let late = schedule(&[0, 1, 2, 3], &weights, &time_windows);
+    assert!(!late.is_feasible());
+    assert!((late.total_lateness() - 9.0).abs() < fp_epsilon, "{}", late.total_lateness());
+
+    time_windows.windows[1] = (0.0, 3.0);
+    assert!(solve_brute(&weights, &time_windows, 0).is_none());
+  }
+}
@@ -0,0 +1,156 @@
+/*
+ *  tsp-sol - an experimental environment for traveling salesman solution analysis
+ *  Copyright (C) 2023  Jeffrey McAteer <jeffrey@jmcateer.com>
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; version 2 of the License ONLY.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License along
+ *  with this program; if not, write to the Free Software Foundation, Inc.,
+ *  51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+ */
+
+use super::*;
+
+use serde::Serialize;
+
+use crate::jeff_algo::{SolveOptions, StepChoice};
+
+/// Frames per second of the jalgo-trace.apng step animation.
+const TRACE_FPS: usize = 2;
+
+/// One tour edge citynum_to_insert could be placed in, before any removals.
+#[derive(Serialize, Debug, Clone)]
+pub struct CandidateEdge {
+  pub from: CityNum,
+  pub to: CityNum,
+  pub delta: fp,
+}
+
+/// Everything jeff_algo considered and decided while inserting one city.
+#[derive(Serialize, Debug, Clone)]
+pub struct TraceStep {
+  pub step: usize,
+  pub inserted_city: CityNum,
+  /// Every edge of the tour before this step, with the delta of inserting directly into it
+  pub candidate_edges: Vec<CandidateEdge>,
+  /// Indexes removed one after the other before inserting, the (n, m, l) triple at deep 3
+  pub removal_idxs: Vec<usize>,
+  pub removed_cities: Vec<CityNum>,
+  /// Change in tour length of the whole remove + re-insert move
+  pub delta: fp,
+  pub tour: Vec<CityNum>,
+  pub tour_len: fp,
+}
+
+/// Step-by-step record of one jeff_algo::solve_with_options run.
+#[derive(Serialize, Debug, Clone)]
+pub struct SolveTrace {
+  pub insertion_order: String,
+  pub initial_tour: String,
+  pub deep: usize,
+  pub cities: Vec<(CityNum, CityXYCoord, CityXYCoord)>,
+  pub initial_tour_cities: Vec<CityNum>,
+  pub initial_tour_len: fp,
+  pub steps: Vec<TraceStep>,
+}
+
+impl SolveTrace {
+  pub fn new(node_coordinates: &[(CityNum, CityXYCoord, CityXYCoord)], weights: &[Vec<CityWeight>], options: &SolveOptions, initial_tour: &[CityNum]) -> SolveTrace {
+    SolveTrace {
+      insertion_order: options.insertion_order.label(),
+      initial_tour: options.initial_tour.name().to_string(),
+      deep: options.deep,
+      cities: node_coordinates.to_vec(),
+      initial_tour_cities: initial_tour.to_vec(),
+      initial_tour_len: compute_dist(weights, initial_tour),
+      steps: vec![],
+    }
+  }
+
+  pub fn record_step(&mut self, weights: &[Vec<CityWeight>], tour_before: &[CityNum], tour_after: &[CityNum], choice: &StepChoice) {
+    let candidate_edges = jeff_algo::insertion_deltas(tour_before, weights, choice.citynum_to_insert).into_iter()
+      .map(|(from, to, delta)| CandidateEdge { from, to, delta })
+      .collect();
+    self.steps.push(TraceStep {
+      step: self.steps.len() + 1,
+      inserted_city: choice.citynum_to_insert,
+      candidate_edges,
+      removal_idxs: choice.removal_idxs.clone(),
+      removed_cities: choice.removed_citynums.clone(),
+      delta: choice.delta,
+      tour: tour_after.to_vec(),
+      tour_len: compute_dist(weights, tour_after),
+    });
+  }
+
+  /// Writes prefix/jalgo-trace.json, one image per step under prefix/jalgo-trace/
+  /// (step-000.png is the initial tour) and prefix/jalgo-trace.apng looping over them.
+  pub fn write(&self, prefix: &str) -> Result<(), String> {
+    let prefix = Path::new(prefix);
+    fs::create_dir_all(prefix).map_err(|e| format!("Cannot create {}: {}", prefix.display(), e))?;
+
+    let json_file = prefix.join("jalgo-trace.json");
+    let json_s = serde_json::to_string_pretty(self).map_err(|e| format!("Error serializing {}: {}", json_file.display(), e))?;
+    fs::write(&json_file, json_s).map_err(|e| format!("Error writing {}: {}", json_file.display(), e))?;
+
+    let mut frame_files = vec![];
+    let frame_file = prefix.join("jalgo-trace").join("step-000.png").to_string_lossy().to_string();
+    save_state_image(frame_file.clone(), &self.initial_tour_cities, &self.cities);
+    frame_files.push(frame_file);
+    for step in self.steps.iter() {
+      let frame_file = prefix.join("jalgo-trace").join(format!("step-{:03}.png", step.step)).to_string_lossy().to_string();
+      save_state_image(frame_file.clone(), &step.tour, &self.cities);
+      frame_files.push(frame_file);
+    }
+
+    let apng_file = prefix.join("jalgo-trace.apng").to_string_lossy().to_string();
+    animation::write_apng(&frame_files, &apng_file, TRACE_FPS)?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn trace_records_one_step_per_inserted_city() {
+    let node_coordinates: Vec<(CityNum, CityXYCoord, CityXYCoord)> = vec![
+      (0, 3.2, 4.9), (1, 10.4, 7.2), (2, 5.6, 11.0), (3, 11.2, 3.3),
+      (4, 6.7, 8.2), (5, 12.9, 11.5), (6, 4.1, 6.7), (7, 8.8, 5.1),
+    ];
+    let weights = compute_weight_coords(&node_coordinates);
+    let options = SolveOptions::default();
+    let prefix = env::temp_dir().join(format!("tsp-sol-trace-{}", std::process::id()));
+    let prefix_s = prefix.to_string_lossy().to_string();
+
+    let tour = jeff_algo::solve_with_options(&node_coordinates, &weights, Some(prefix_s.clone()), &options);
+    assert_eq!(tour, jeff_algo::solve_with_options(&node_coordinates, &weights, None, &options));
+
+    let trace: serde_json::Value = serde_json::from_str(&fs::read_to_string(prefix.join("jalgo-trace.json")).unwrap()).unwrap();
+    let initial_tour: Vec<CityNum> = serde_json::from_value(trace["initial_tour_cities"].clone()).unwrap();
+    let steps = trace["steps"].as_array().unwrap();
+    assert_eq!(initial_tour.len() + steps.len(), node_coordinates.len());
+
+    let mut visited = initial_tour.clone();
+    for (step_i, step) in steps.iter().enumerate() {
+      let inserted_city = step["inserted_city"].as_u64().unwrap() as CityNum;
+      assert!(!visited.contains(&inserted_city), "city {} inserted twice", inserted_city);
+      visited.push(inserted_city);
+      assert_eq!(step["tour"].as_array().unwrap().len(), initial_tour.len() + step_i + 1);
+    }
+    visited.sort();
+    assert_eq!(visited, (0..node_coordinates.len()).collect::<Vec<CityNum>>());
+    let last_tour: Vec<CityNum> = serde_json::from_value(steps.last().unwrap()["tour"].clone()).unwrap();
+    assert_eq!(last_tour, tour);
+    assert!((steps.last().unwrap()["tour_len"].as_f64().unwrap() - compute_dist(&weights, &tour) as f64).abs() < 1e-4);
+    fs::remove_dir_all(&prefix).unwrap_or(());
+  }
+}
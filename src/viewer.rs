@@ -0,0 +1,671 @@
+/*
+ *  tsp-sol - an experimental environment for traveling salesman solution analysis
+ *  Copyright (C) 2023  Jeffrey McAteer <jeffrey@jmcateer.com>
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; version 2 of the License ONLY.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License along
+ *  with this program; if not, write to the Free Software Foundation, Inc.,
+ *  51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+ */
+
+use super::*;
+
+use serde::{Serialize, Deserialize};
+
+use crate::jeff_algo::{InsertionOrder, InitialTour, SolveOptions};
+
+use std::net::{TcpListener, TcpStream};
+use std::io::{BufRead, Read};
+
+// Largest city we are willing to brute force when the viewer asks for a re-solve;
+// anything larger only gets a jeff_algo tour.
+const MAX_BRUTE_CITIES_FOR_VIEWER: usize = 10;
+
+// Largest request body we read; a re-solve of a few hundred cities is a few kB of JSON.
+const MAX_REQUEST_BODY_BYTES: usize = 1024 * 1024;
+
+/// One distinct optimal tour seen during a scan, samples refer to these by index.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScanTour {
+  pub tour: Vec<CityNum>,
+  pub rgb: String,
+  pub len: fp,
+}
+
+/// Structured output of pattern_scan_coords, written next to the .png
+/// and read by the `serve` viewer instead of the old one-div-per-sample html.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ScanData {
+  pub name: String,
+  pub cities: Vec<(fp, fp)>,
+  pub bounds: (fp, fp, fp, fp), // x_min, x_max, y_min, y_max
+  pub granularity: fp,
+  pub initial_solution: Vec<CityNum>,
+  pub tours: Vec<ScanTour>,
+  pub samples: Vec<(fp, fp, usize)>, // inserted point x, y, index into tours
+
+  #[serde(skip)]
+  tour_idxs: HashMap<(u8, u8, u8), usize>,
+}
+
+impl ScanData {
+  pub fn new(name: &str, node_coordinates: &[(CityNum, CityXYCoord, CityXYCoord)], bounds: (fp, fp, fp, fp), granularity: fp) -> ScanData {
+    ScanData {
+      name: name.to_string(),
+      cities: node_coordinates.iter().map(|(_i, x, y)| (*x, *y) ).collect(),
+      bounds,
+      granularity,
+      ..Default::default()
+    }
+  }
+
+  // Tours are keyed by their path_to_rgb color so rotated + reversed copies of the same tour share one entry.
+  pub fn add_sample(&mut self, point_x: fp, point_y: fp, tour: &[CityNum], rgb_key: &(u8, u8, u8), tour_len: fp) {
+    let tour_idx = match self.tour_idxs.get(rgb_key) {
+      Some(idx) => *idx,
+      None => {
+        self.tours.push(ScanTour {
+          tour: tour.to_vec(),
+          rgb: format!("{:02x}{:02x}{:02x}", rgb_key.0, rgb_key.1, rgb_key.2),
+          len: tour_len,
+        });
+        self.tour_idxs.insert(*rgb_key, self.tours.len() - 1);
+        self.tours.len() - 1
+      }
+    };
+    self.samples.push( (point_x, point_y, tour_idx) );
+  }
+
+  pub fn save<P: AsRef<Path>>(&self, file_path: P) {
+    let file_path = file_path.as_ref();
+    match serde_json::to_string(self) {
+      Ok(json_s) => {
+        if let Err(e) = fs::write(file_path, json_s) {
+          println!("Error writing {}: {:?}", file_path.display(), e);
+        }
+      }
+      Err(e) => {
+        println!("Error serializing scan data for {}: {:?}", file_path.display(), e);
+      }
+    }
+  }
+}
+
+#[derive(Deserialize, Debug)]
+struct SolveRequest {
+  cities: Vec<(fp, fp)>,
+  #[serde(default)]
+  insertion_order: InsertionOrder,
+  #[serde(default)]
+  initial_tour: InitialTour,
+  #[serde(default = "default_deep")]
+  deep: usize,
+}
+
+fn default_deep() -> usize {
+  jeff_algo::DEFAULT_DEEP
+}
+
+#[derive(Serialize, Debug)]
+struct SolveResponse {
+  jalgo: Vec<CityNum>,
+  jalgo_len: fp,
+  brute: Option<Vec<CityNum>>,
+  brute_len: Option<fp>,
+  identical: Option<bool>,
+}
+
+pub fn serve(scan_dir: &str, port: u16, thread_pool: &ThreadPool) {
+  let listener = match TcpListener::bind(("127.0.0.1", port)) {
+    Ok(l) => l,
+    Err(e) => {
+      println!("Cannot listen on 127.0.0.1:{}: {}", port, e);
+      return;
+    }
+  };
+  println!("Serving scans from {} at http://127.0.0.1:{}/", scan_dir, port);
+
+  for stream in listener.incoming() {
+    match stream {
+      Ok(stream) => {
+        if let Err(e) = handle_connection(stream, scan_dir, thread_pool) {
+          eprintln!("Error handling viewer request: {:?}", e);
+        }
+      }
+      Err(e) => {
+        eprintln!("Error accepting viewer connection: {:?}", e);
+      }
+    }
+  }
+}
+
+fn handle_connection(mut stream: TcpStream, scan_dir: &str, thread_pool: &ThreadPool) -> std::io::Result<()> {
+  let mut reader = BufReader::new(stream.try_clone()?);
+
+  let mut request_line = String::new();
+  reader.read_line(&mut request_line)?;
+
+  let mut content_length: Option<usize> = Some(0);
+  loop {
+    let mut header_line = String::new();
+    if reader.read_line(&mut header_line)? == 0 || header_line.trim().is_empty() {
+      break;
+    }
+    let header_line = header_line.to_lowercase();
+    if let Some(val) = header_line.strip_prefix("content-length:") {
+      content_length = val.trim().parse().ok();
+    }
+  }
+  let content_length = match content_length {
+    Some(content_length) if content_length > MAX_REQUEST_BODY_BYTES => {
+      return respond(&mut stream, "413 Payload Too Large", "text/plain", format!("Request bodies are limited to {} bytes", MAX_REQUEST_BODY_BYTES).as_bytes());
+    }
+    Some(content_length) => content_length,
+    None => {
+      return respond(&mut stream, "400 Bad Request", "text/plain", b"Invalid Content-Length");
+    }
+  };
+
+  let mut body = vec![0u8; content_length];
+  reader.read_exact(&mut body)?;
+
+  let request_parts: Vec<&str> = request_line.split_whitespace().collect();
+  let method = request_parts.first().copied().unwrap_or("");
+  let url = request_parts.get(1).copied().unwrap_or("/");
+
+  match (method, url) {
+    ("GET", "/") | ("GET", "/index.html") => {
+      respond(&mut stream, "200 OK", "text/html", VIEWER_HTML.as_bytes())
+    }
+    ("GET", "/scans") => {
+      let json_s = serde_json::to_string(&list_scan_files(scan_dir)).unwrap_or("[]".to_string());
+      respond(&mut stream, "200 OK", "application/json", json_s.as_bytes())
+    }
+    ("GET", scan_url) if scan_url.starts_with("/scan/") => {
+      let scan_name = &scan_url["/scan/".len()..];
+      // Only hand out files directly inside scan_dir
+      if scan_name.contains('/') || scan_name.contains('\\') || scan_name.contains("..") || !scan_name.ends_with(".json") {
+        return respond(&mut stream, "400 Bad Request", "text/plain", b"Invalid scan name");
+      }
+      match fs::read(Path::new(scan_dir).join(scan_name)) {
+        Ok(bytes) => respond(&mut stream, "200 OK", "application/json", &bytes),
+        Err(_) => respond(&mut stream, "404 Not Found", "text/plain", b"No such scan"),
+      }
+    }
+    ("POST", "/solve") => {
+      let solve_req: SolveRequest = match serde_json::from_slice(&body) {
+        Ok(r) => r,
+        Err(e) => {
+          return respond(&mut stream, "400 Bad Request", "text/plain", format!("{}", e).as_bytes());
+        }
+      };
+      if solve_req.cities.len() < 3 {
+        return respond(&mut stream, "400 Bad Request", "text/plain", b"Need at least 3 cities");
+      }
+      let json_s = serde_json::to_string(&solve_cities(&solve_req.cities, &SolveOptions { insertion_order: solve_req.insertion_order, initial_tour: solve_req.initial_tour, deep: solve_req.deep }, thread_pool)).unwrap_or("{}".to_string());
+      respond(&mut stream, "200 OK", "application/json", json_s.as_bytes())
+    }
+    _ => {
+      respond(&mut stream, "404 Not Found", "text/plain", b"Not found")
+    }
+  }
+}
+
+fn respond(stream: &mut TcpStream, status: &str, content_type: &str, body: &[u8]) -> std::io::Result<()> {
+  write!(stream, "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", status, content_type, body.len())?;
+  stream.write_all(body)?;
+  stream.flush()
+}
+
+fn list_scan_files(scan_dir: &str) -> Vec<String> {
+  let mut scan_names = vec![];
+  if let Ok(entries) = fs::read_dir(scan_dir) {
+    for entry in entries.flatten() {
+      let file_name = entry.file_name().to_string_lossy().to_string();
+      if file_name.ends_with(".json") && !file_name.ends_with(".scenario.json") {
+        scan_names.push(file_name);
+      }
+    }
+  }
+  scan_names.sort();
+  scan_names
+}
+
+fn solve_cities(cities: &[(fp, fp)], jalgo_options: &SolveOptions, thread_pool: &ThreadPool) -> SolveResponse {
+  let node_coordinates: Vec<(CityNum, CityXYCoord, CityXYCoord)> = cities.iter().enumerate().map(|(i, (x, y))| (i, *x, *y) ).collect();
+  let weights = compute_weight_coords(&node_coordinates);
+
+  let jalgo = jeff_algo::solve_with_options(&node_coordinates, &weights, None, jalgo_options);
+  let jalgo_len = compute_dist(&weights, &jalgo);
+
+  let mut response = SolveResponse {
+    jalgo,
+    jalgo_len,
+    brute: None,
+    brute_len: None,
+    identical: None,
+  };
+
+  if node_coordinates.len() <= MAX_BRUTE_CITIES_FOR_VIEWER {
+    let brute = brute_algo::solve(&node_coordinates, &weights, None, thread_pool);
+    let brute_len = compute_dist(&weights, &brute);
+    response.identical = Some( (jalgo_len - brute_len).abs() <= fp_epsilon || is_identical_tour(&response.jalgo, &brute, &weights) );
+    response.brute = Some(brute);
+    response.brute_len = Some(brute_len);
+  }
+
+  response
+}
+
+pub const VIEWER_HTML: &str = r##"<!DOCTYPE html>
+<html>
+  <head>
+    <title>tsp-sol scan viewer</title>
+    <style>
+      html, body {
+        background: #c0c0c0;
+        font-family: monospace;
+      }
+      #controls > * {
+        margin-right: 6px;
+      }
+      #viewer {
+        display: flex;
+        gap: 12px;
+        margin-top: 8px;
+      }
+      #details {
+        white-space: pre;
+      }
+    </style>
+  </head>
+  <body>
+    <div id="controls">
+      <select id="scan-select"></select>
+      <button id="prev-btn">&lt;</button>
+      <input id="frame-slider" type="range" min="0" max="0" value="0"/>
+      <button id="next-btn">&gt;</button>
+      <button id="play-btn">Play</button>
+      <button id="solve-btn">Re-solve</button>
+      <button id="reset-btn">Reset cities</button>
+      <span id="status"></span>
+    </div>
+    <div id="viewer">
+      <canvas id="scan-canvas" width="900" height="900"></canvas>
+      <div id="details"></div>
+    </div>
+    <script>
+      var canvas = document.getElementById("scan-canvas");
+      var ctx = canvas.getContext("2d");
+      var region_canvas = document.createElement("canvas");
+      region_canvas.width = canvas.width;
+      region_canvas.height = canvas.height;
+
+      var scan_groups = {}; // "multi-pattern-scan" -> ["multi-pattern-scan-000.json", ...]
+      var frames = [];
+      var scan = null;
+      var sample_lookup = {};
+      var cities = [];
+      var cities_edited = false;
+      var hover_sample = null;
+      var pinned_point = null;
+      var solved = null;
+      var drag_city_i = -1;
+      var drag_moved = false;
+      var play_timer = null;
+
+      function status(msg) {
+        document.getElementById("status").textContent = msg;
+      }
+
+      function to_canvas(x, y) {
+        var b = scan.bounds;
+        return [ (x - b[0]) / (b[1] - b[0]) * canvas.width, (y - b[2]) / (b[3] - b[2]) * canvas.height ];
+      }
+
+      function to_world(cx, cy) {
+        var b = scan.bounds;
+        return [ b[0] + (cx / canvas.width) * (b[1] - b[0]), b[2] + (cy / canvas.height) * (b[3] - b[2]) ];
+      }
+
+      function sample_key(x, y) {
+        var b = scan.bounds;
+        return Math.round((x - b[0]) / scan.granularity) + "," + Math.round((y - b[2]) / scan.granularity);
+      }
+
+      function render_regions() {
+        var rctx = region_canvas.getContext("2d");
+        rctx.fillStyle = "black";
+        rctx.fillRect(0, 0, region_canvas.width, region_canvas.height);
+        sample_lookup = {};
+        var px_w = Math.ceil(scan.granularity / (scan.bounds[1] - scan.bounds[0]) * canvas.width);
+        var px_h = Math.ceil(scan.granularity / (scan.bounds[3] - scan.bounds[2]) * canvas.height);
+        for (var i = 0; i < scan.samples.length; i += 1) {
+          var s = scan.samples[i];
+          var c = to_canvas(s[0], s[1]);
+          rctx.fillStyle = "#" + scan.tours[s[2]].rgb;
+          rctx.fillRect(c[0] - px_w / 2, c[1] - px_h / 2, px_w, px_h);
+          sample_lookup[sample_key(s[0], s[1])] = s;
+        }
+      }
+
+      // Resolves city numbers in a tour to coordinates; the city past the end is the inserted point.
+      function tour_coords(tour, point) {
+        var coords = [];
+        for (var i = 0; i < tour.length; i += 1) {
+          coords.push(tour[i] < cities.length ? cities[tour[i]] : point);
+        }
+        return coords;
+      }
+
+      function draw_tour(coords, style, width, dash) {
+        if (coords.length < 2) {
+          return;
+        }
+        ctx.lineWidth = width;
+        ctx.strokeStyle = style;
+        ctx.setLineDash(dash);
+        ctx.beginPath();
+        var last = to_canvas(coords[coords.length - 1][0], coords[coords.length - 1][1]);
+        ctx.moveTo(last[0], last[1]);
+        for (var i = 0; i < coords.length; i += 1) {
+          var c = to_canvas(coords[i][0], coords[i][1]);
+          ctx.lineTo(c[0], c[1]);
+        }
+        ctx.stroke();
+        ctx.setLineDash([]);
+      }
+
+      function tour_len(coords) {
+        var total = 0.0;
+        for (var i = 0; i < coords.length; i += 1) {
+          var a = coords[i];
+          var b = coords[(i + 1) % coords.length];
+          total += Math.sqrt((a[0] - b[0]) * (a[0] - b[0]) + (a[1] - b[1]) * (a[1] - b[1]));
+        }
+        return total;
+      }
+
+      function weights_text(points) {
+        var heaviest = 0.0;
+        var w = [];
+        for (var r = 0; r < points.length; r += 1) {
+          w.push([]);
+          for (var c = 0; c < points.length; c += 1) {
+            var d = Math.sqrt(Math.pow(points[r][0] - points[c][0], 2) + Math.pow(points[r][1] - points[c][1], 2));
+            w[r].push(d);
+            heaviest = Math.max(heaviest, d);
+          }
+        }
+        var s = "";
+        for (var r = 0; r < points.length; r += 1) {
+          for (var c = 0; c < points.length; c += 1) {
+            s += (r == c ? "x.x       " : (w[r][c] / heaviest).toFixed(6) + "  ");
+          }
+          s += "\n";
+        }
+        return s;
+      }
+
+      function draw() {
+        if (scan == null) {
+          return;
+        }
+        ctx.clearRect(0, 0, canvas.width, canvas.height);
+        ctx.globalAlpha = cities_edited ? 0.35 : 1.0;
+        ctx.drawImage(region_canvas, 0, 0);
+        ctx.globalAlpha = 1.0;
+
+        if (scan.initial_solution.length > 0) {
+          draw_tour(tour_coords(scan.initial_solution, null), "white", 1, []);
+        }
+
+        var details = scan.name + (cities_edited ? " (cities edited, regions are stale)" : "") + "\n\n";
+        var point = pinned_point || (hover_sample ? [hover_sample[0], hover_sample[1]] : null);
+        if (point != null) {
+          details += "point = " + point[0].toFixed(3) + ", " + point[1].toFixed(3) + "\n";
+          if (hover_sample != null && !cities_edited && pinned_point == null) {
+            var t = scan.tours[hover_sample[2]];
+            draw_tour(tour_coords(t.tour, point), "black", 2, [10, 10]);
+            details += "tour = [" + t.tour.join(", ") + "]\nlen = " + t.len.toFixed(4) + "\ncolor = #" + t.rgb + "\n\n";
+          }
+          details += weights_text(cities.concat([point]));
+        }
+
+        if (solved != null) {
+          if (solved.brute != null) {
+            draw_tour(tour_coords(solved.brute, pinned_point), "white", 3, []);
+          }
+          draw_tour(tour_coords(solved.jalgo, pinned_point), "black", 2, [10, 10]);
+          details += "\njalgo = [" + solved.jalgo.join(", ") + "] len = " + solved.jalgo_len.toFixed(4) + "\n";
+          if (solved.brute != null) {
+            details += "brute = [" + solved.brute.join(", ") + "] len = " + solved.brute_len.toFixed(4) + "\n";
+            details += "identical = " + solved.identical + "\n";
+          }
+        }
+
+        if (pinned_point != null) {
+          var pc = to_canvas(pinned_point[0], pinned_point[1]);
+          ctx.strokeStyle = "yellow";
+          ctx.lineWidth = 2;
+          ctx.strokeRect(pc[0] - 5, pc[1] - 5, 10, 10);
+        }
+
+        for (var i = 0; i < cities.length; i += 1) {
+          var c = to_canvas(cities[i][0], cities[i][1]);
+          ctx.strokeStyle = "red";
+          ctx.lineWidth = 3;
+          ctx.beginPath();
+          ctx.arc(c[0], c[1], 10, 0, 2 * Math.PI);
+          ctx.stroke();
+          ctx.fillStyle = "#e1e1ff";
+          ctx.font = "bold 14px sans-serif";
+          ctx.fillText("" + i, c[0] + 12, c[1] + 12);
+        }
+
+        document.getElementById("details").textContent = details;
+      }
+
+      function load_frame(i) {
+        if (frames.length < 1) {
+          return;
+        }
+        i = Math.max(0, Math.min(frames.length - 1, i));
+        document.getElementById("frame-slider").value = i;
+        status("Loading " + frames[i] + "...");
+        fetch("/scan/" + frames[i]).then(function(r) { return r.json(); }).then(function(data) {
+          scan = data;
+          cities = scan.cities.map(function(c) { return [c[0], c[1]]; });
+          cities_edited = false;
+          hover_sample = null;
+          pinned_point = null;
+          solved = null;
+          render_regions();
+          draw();
+          status(frames[i] + " (" + (i + 1) + "/" + frames.length + ", " + scan.samples.length + " samples, " + scan.tours.length + " tours)");
+        });
+      }
+
+      function select_group(group_name) {
+        frames = scan_groups[group_name] || [];
+        var slider = document.getElementById("frame-slider");
+        slider.max = Math.max(0, frames.length - 1);
+        load_frame(0);
+      }
+
+      function current_frame() {
+        return parseInt(document.getElementById("frame-slider").value);
+      }
+
+      function solve() {
+        var points = cities.slice();
+        if (pinned_point != null) {
+          points.push(pinned_point);
+        }
+        status("Solving " + points.length + " cities...");
+        fetch("/solve", { method: "POST", body: JSON.stringify({ cities: points }) }).then(function(r) {
+          if (!r.ok) {
+            return r.text().then(function(t) { throw t; });
+          }
+          return r.json();
+        }).then(function(data) {
+          solved = data;
+          status("Solved " + points.length + " cities");
+          draw();
+        }).catch(function(e) {
+          status("Solve failed: " + e);
+        });
+      }
+
+      canvas.addEventListener("mousedown", function(e) {
+        if (scan == null) {
+          return;
+        }
+        drag_moved = false;
+        for (var i = 0; i < cities.length; i += 1) {
+          var c = to_canvas(cities[i][0], cities[i][1]);
+          if (Math.abs(c[0] - e.offsetX) < 12 && Math.abs(c[1] - e.offsetY) < 12) {
+            drag_city_i = i;
+            return;
+          }
+        }
+      });
+
+      canvas.addEventListener("mousemove", function(e) {
+        if (scan == null) {
+          return;
+        }
+        var w = to_world(e.offsetX, e.offsetY);
+        if (drag_city_i >= 0) {
+          cities[drag_city_i] = w;
+          cities_edited = true;
+          drag_moved = true;
+          solved = null;
+        }
+        else {
+          hover_sample = sample_lookup[sample_key(w[0], w[1])] || null;
+        }
+        draw();
+      });
+
+      canvas.addEventListener("mouseup", function(e) {
+        if (scan == null) {
+          return;
+        }
+        if (drag_city_i < 0 && !drag_moved) {
+          var w = to_world(e.offsetX, e.offsetY);
+          var s = sample_lookup[sample_key(w[0], w[1])];
+          pinned_point = s ? [s[0], s[1]] : w;
+          solved = null;
+        }
+        drag_city_i = -1;
+        draw();
+      });
+
+      canvas.addEventListener("mouseleave", function(e) {
+        hover_sample = null;
+        drag_city_i = -1;
+        draw();
+      });
+
+      document.getElementById("scan-select").addEventListener("change", function(e) {
+        select_group(e.target.value);
+      });
+      document.getElementById("frame-slider").addEventListener("input", function(e) {
+        load_frame(current_frame());
+      });
+      document.getElementById("prev-btn").addEventListener("click", function(e) {
+        load_frame(current_frame() - 1);
+      });
+      document.getElementById("next-btn").addEventListener("click", function(e) {
+        load_frame(current_frame() + 1);
+      });
+      document.getElementById("play-btn").addEventListener("click", function(e) {
+        if (play_timer != null) {
+          clearInterval(play_timer);
+          play_timer = null;
+          e.target.textContent = "Play";
+          return;
+        }
+        e.target.textContent = "Pause";
+        play_timer = setInterval(function() {
+          load_frame((current_frame() + 1) % Math.max(1, frames.length));
+        }, 200);
+      });
+      document.getElementById("solve-btn").addEventListener("click", function(e) {
+        solve();
+      });
+      document.getElementById("reset-btn").addEventListener("click", function(e) {
+        load_frame(current_frame());
+      });
+
+      fetch("/scans").then(function(r) { return r.json(); }).then(function(names) {
+        var select = document.getElementById("scan-select");
+        for (var i = 0; i < names.length; i += 1) {
+          // multi-pattern-scan-003.json -> multi-pattern-scan
+          var group_name = names[i].replace(/\.json$/, "").replace(/-[0-9]+$/, "");
+          if (!(group_name in scan_groups)) {
+            scan_groups[group_name] = [];
+            var opt = document.createElement("option");
+            opt.value = group_name;
+            opt.textContent = group_name;
+            select.appendChild(opt);
+          }
+          scan_groups[group_name].push(names[i]);
+        }
+        if (select.options.length > 0) {
+          select_group(select.options[0].value);
+        }
+        else {
+          status("No scan data found, run pattern-scan or multi-pattern-scan first.");
+        }
+      });
+    </script>
+  </body>
+</html>
+"##;
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // Sends request to a handle_connection running on a local port and returns the raw response.
+  fn round_trip(request: &str) -> String {
+    let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = std::thread::spawn(move || {
+      let (stream, _) = listener.accept().unwrap();
+      handle_connection(stream, "views", &ThreadPool::new(2)).unwrap();
+    });
+    let mut client = TcpStream::connect(addr).unwrap();
+    client.write_all(request.as_bytes()).unwrap();
+    let mut response = String::new();
+    client.read_to_string(&mut response).unwrap();
+    server.join().unwrap();
+    response
+  }
+
+  #[test]
+  fn solve_endpoint_answers_and_limits_bodies() {
+    let body = r#"{"cities": [[3.0, 3.0], [12.0, 3.0], [12.0, 12.0], [3.0, 12.0], [7.5, 4.0]]}"#;
+    let response = round_trip(&format!("POST /solve HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}", body.len(), body));
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+    let json: serde_json::Value = serde_json::from_str(response.split("\r\n\r\n").nth(1).unwrap()).unwrap();
+    assert_eq!(json["jalgo"].as_array().unwrap().len(), 5);
+    assert_eq!(json["identical"], true);
+    assert!((json["jalgo_len"].as_f64().unwrap() - json["brute_len"].as_f64().unwrap()).abs() < 1e-3);
+
+    let response = round_trip(&format!("POST /solve HTTP/1.1\r\nContent-Length: {}\r\n\r\n", MAX_REQUEST_BODY_BYTES + 1));
+    assert!(response.starts_with("HTTP/1.1 413 "), "{}", response);
+    let response = round_trip("POST /solve HTTP/1.1\r\nContent-Length: lots\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 400 "), "{}", response);
+  }
+}
@@ -0,0 +1,302 @@
+/*
+ *  tsp-sol - an experimental environment for traveling salesman solution analysis
+ *  Copyright (C) 2023  Jeffrey McAteer <jeffrey@jmcateer.com>
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; version 2 of the License ONLY.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License along
+ *  with this program; if not, write to the Free Software Foundation, Inc.,
+ *  51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+ */
+
+// Multiple salesmen (mTSP) and capacitated vehicle routing (CVRP) from one depot.
+// Routes are built by cheapest insertion across every route at once, the same delta
+// jeff_algo's insert_point_step uses, then each route is re-solved on its own by jeff_algo.
+
+use super::*;
+
+use crate::jeff_algo::SolveOptions;
+
+/// Every route starts and ends at depot; a route is stored as [depot, customer, customer, ...] and closes back to depot.
+pub struct VrpProblem {
+  pub node_coordinates: Vec<(CityNum, CityXYCoord, CityXYCoord)>,
+  pub weights: Vec<Vec<CityWeight>>,
+  pub depot: CityNum,
+  /// Indexed like node_coordinates, the depot's demand is ignored
+  pub demands: Vec<usize>,
+  /// None for uncapacitated salesmen
+  pub capacity: Option<usize>,
+  /// None opens a new route whenever no existing route has room
+  pub num_vehicles: Option<usize>,
+}
+
+impl VrpProblem {
+  /// num_vehicles salesmen sharing a depot, no demands or capacities.
+  pub fn mtsp(node_coordinates: &[(CityNum, CityXYCoord, CityXYCoord)], weights: &[Vec<CityWeight>], depot: CityNum, num_vehicles: usize) -> VrpProblem {
+    VrpProblem {
+      node_coordinates: node_coordinates.to_vec(),
+      weights: weights.to_vec(),
+      depot,
+      demands: vec![0; weights.len()],
+      capacity: None,
+      num_vehicles: Some(num_vehicles),
+    }
+  }
+
+  pub fn route_load(&self, route: &[CityNum]) -> usize {
+    route.iter().filter(|c| **c != self.depot).map(|c| self.demands[*c]).sum()
+  }
+
+  fn fits(&self, route: &[CityNum], citynum: CityNum) -> bool {
+    match self.capacity {
+      Some(capacity) => self.route_load(route) + self.demands[citynum] <= capacity,
+      None => true,
+    }
+  }
+}
+
+/// Total length of every route, each closed back to the depot.
+pub fn routes_dist(weights: &[Vec<CityWeight>], routes: &[Vec<CityNum>]) -> fp {
+  routes.iter().map(|route| compute_dist(weights, route)).sum()
+}
+
+/// True for CVRPLIB files, which the tsplib crate refuses to parse because of their DEMAND_SECTION.
+pub fn is_cvrp_file(file_arg: &str) -> bool {
+  match fs::read_to_string(file_arg) {
+    Ok(text) => text.lines().any(|line| line.trim().starts_with("DEMAND_SECTION")),
+    Err(_) => false,
+  }
+}
+
+/// Parses a CVRPLIB (TSPLIB + DEMAND_SECTION, DEPOT_SECTION, CAPACITY) file. The fleet size comes from
+/// a VEHICLES line, a "No of trucks: K" comment or a "-kK" name suffix, in that order; none of them leaves the fleet open.
+pub fn open_cvrp(file_arg: &str) -> Option<VrpProblem> {
+  let text = match fs::read_to_string(file_arg) {
+    Ok(text) => text,
+    Err(e) => {
+      report_error(&format!("Cannot open {}: {}", file_arg, e));
+      return None;
+    }
+  };
+
+  // The routing sections are cut out here, everything else goes to tsplib::parse
+  let mut tsplib_text = String::new();
+  let mut demand_lines = vec![];
+  let mut depot_ids = vec![];
+  let mut num_vehicles: Option<usize> = None;
+  let mut section = "";
+  for line in text.lines() {
+    let trimmed = line.trim();
+    let is_keyword = trimmed.chars().next().map(|c| c.is_ascii_alphabetic()).unwrap_or(false);
+    if is_keyword {
+      section = "";
+      if trimmed.starts_with("DEMAND_SECTION") {
+        section = "demand";
+        continue;
+      }
+      if trimmed.starts_with("DEPOT_SECTION") {
+        section = "depot";
+        continue;
+      }
+      if trimmed.starts_with("VEHICLES") {
+        num_vehicles = trimmed.split(':').nth(1).and_then(|v| v.trim().parse().ok());
+        continue;
+      }
+    }
+    match section {
+      "demand" => demand_lines.push(trimmed.to_string()),
+      "depot" => if trimmed != "-1" && !trimmed.is_empty() { depot_ids.push(trimmed.to_string()) },
+      _ => { tsplib_text += line; tsplib_text += "\n"; }
+    }
+  }
+
+  let instance = match tsplib::parse( BufReader::new(tsplib_text.as_bytes()) ) {
+    Ok(i) => i,
+    Err(e) => {
+      report_error(&format!("Error parsing tsplib file {}: {}", file_arg, e));
+      return None;
+    }
+  };
+  let num_vehicles = num_vehicles.or_else(|| fleet_size_from_comment(&instance.comment)).or_else(|| fleet_size_from_name(&instance.name));
+  let capacity = if instance.capacity > 0 { Some(instance.capacity) } else { None };
+
+  let (file_coordinates, explicit_weights, metric) = tsp_instance_coordinates(file_arg, instance)?;
+  let weights = explicit_weights.unwrap_or_else(|| metric.weights(&file_coordinates) );
+  let node_coordinates = metric.view_coordinates(&file_coordinates);
+  let citynum_of = |id_s: &str| -> Option<CityNum> {
+    let id: usize = id_s.parse().ok()?;
+    node_coordinates.iter().position(|(file_id, _x, _y)| *file_id == id)
+  };
+
+  let mut demands = vec![0; node_coordinates.len()];
+  for demand_line in demand_lines.iter() {
+    let fields: Vec<&str> = demand_line.split_whitespace().collect();
+    let parsed = match fields.as_slice() {
+      [id_s, demand_s] => citynum_of(id_s).zip(demand_s.parse::<usize>().ok()),
+      _ => None,
+    };
+    match parsed {
+      Some((citynum, demand)) => demands[citynum] = demand,
+      None => {
+        report_error(&format!("Err: cannot read DEMAND_SECTION line {:?} in {}", demand_line, file_arg));
+        return None;
+      }
+    }
+  }
+
+  let depot = match depot_ids.as_slice() {
+    [] => 0,
+    [depot_id] => match citynum_of(depot_id) {
+      Some(depot) => depot,
+      None => {
+        report_error(&format!("Err: depot {} is not a city of {}", depot_id, file_arg));
+        return None;
+      }
+    },
+    _ => {
+      report_error(&format!("Err: {} has {} depots, only single-depot problems are supported", file_arg, depot_ids.len()));
+      return None;
+    }
+  };
+
+  Some(VrpProblem { node_coordinates, weights, depot, demands, capacity, num_vehicles })
+}
+
+fn fleet_size_from_comment(comments: &[String]) -> Option<usize> {
+  for comment in comments.iter() {
+    if let Some(trucks_i) = comment.find("trucks:") {
+      let digits: String = comment[trucks_i + "trucks:".len()..].trim_start().chars().take_while(|c| c.is_ascii_digit()).collect();
+      if let Ok(num_trucks) = digits.parse() {
+        return Some(num_trucks);
+      }
+    }
+  }
+  None
+}
+
+// CVRPLIB names end in -kK, e.g. A-n32-k5
+fn fleet_size_from_name(name: &str) -> Option<usize> {
+  let k_i = name.rfind("-k")?;
+  name[k_i + 2..].parse().ok()
+}
+
+/// Routes serving every customer, or None (with the reason reported) when the fleet cannot carry the demand.
+pub fn solve(problem: &VrpProblem, options: &SolveOptions) -> Option<Vec<Vec<CityNum>>> {
+  let weights = &problem.weights;
+  let depot = problem.depot;
+  if let Some(capacity) = problem.capacity {
+    if let Some(citynum) = (0..weights.len()).find(|c| *c != depot && problem.demands[*c] > capacity) {
+      report_error(&format!("Customer {} needs {} which is more than a vehicle's capacity of {}", citynum, problem.demands[citynum], capacity));
+      return None;
+    }
+  }
+
+  let mut routes: Vec<Vec<CityNum>> = vec![vec![depot]; problem.num_vehicles.unwrap_or(0)];
+  let mut unrouted: Vec<CityNum> = (0..weights.len()).filter(|c| *c != depot).collect();
+
+  // A fixed fleet puts every vehicle to work: each route is seeded with the customer farthest from
+  // the depot and the earlier seeds, otherwise cheapest insertion would pile everyone into one route.
+  let mut seeds = vec![depot];
+  for route in routes.iter_mut() {
+    let seed_i = (0..unrouted.len()).filter(|i| problem.fits(route, unrouted[*i])).max_by(|a, b| {
+      let dist_a = seeds.iter().map(|s| weights[*s][unrouted[*a]] + weights[unrouted[*a]][*s]).fold(fp::INFINITY, fp::min);
+      let dist_b = seeds.iter().map(|s| weights[*s][unrouted[*b]] + weights[unrouted[*b]][*s]).fold(fp::INFINITY, fp::min);
+      dist_a.partial_cmp(&dist_b).unwrap_or(std::cmp::Ordering::Equal)
+    });
+    if let Some(seed_i) = seed_i {
+      let seed = unrouted.remove(seed_i);
+      route.push(seed);
+      seeds.push(seed);
+    }
+  }
+
+  while !unrouted.is_empty() {
+    // (delta, unrouted index, route index or routes.len() for a new route, insert position)
+    let mut best: Option<(fp, usize, usize, usize)> = None;
+    for (unrouted_i, citynum) in unrouted.iter().enumerate() {
+      for (route_i, route) in routes.iter().enumerate() {
+        if !problem.fits(route, *citynum) {
+          continue;
+        }
+        for (edge_i, (_from, _to, delta)) in jeff_algo::insertion_deltas(route, weights, *citynum).iter().enumerate() {
+          if best.map(|b| *delta < b.0).unwrap_or(true) {
+            best = Some((*delta, unrouted_i, route_i, edge_i + 1));
+          }
+        }
+      }
+      if problem.num_vehicles.is_none() {
+        let delta = weights[depot][*citynum] + weights[*citynum][depot];
+        if best.map(|b| delta < b.0).unwrap_or(true) {
+          best = Some((delta, unrouted_i, routes.len(), 1));
+        }
+      }
+    }
+
+    let (_delta, unrouted_i, route_i, insert_i) = match best {
+      Some(best) => best,
+      None => {
+        report_error(&format!("{} vehicles cannot carry every customer, {} customers are left over", routes.len(), unrouted.len()));
+        return None;
+      }
+    };
+    let citynum = unrouted.remove(unrouted_i);
+    if route_i == routes.len() {
+      routes.push(vec![depot]);
+    }
+    routes[route_i].insert(insert_i, citynum);
+  }
+
+  // Each route is a TSP of its own: let jeff_algo re-order it and keep the result if it is shorter
+  for route in routes.iter_mut() {
+    if route.len() <= 3 {
+      continue;
+    }
+    let sub_coordinates: Vec<(CityNum, CityXYCoord, CityXYCoord)> = route.iter().enumerate().map(|(i, c)| (i, problem.node_coordinates[*c].1, problem.node_coordinates[*c].2) ).collect();
+    let sub_weights: Vec<Vec<CityWeight>> = route.iter().map(|a| route.iter().map(|b| weights[*a][*b]).collect() ).collect();
+    let mut sub_tour = jeff_algo::solve_with_options(&sub_coordinates, &sub_weights, None, options);
+    // index 0 of the sub problem is the depot
+    let depot_i = sub_tour.iter().position(|c| *c == 0).unwrap_or(0);
+    sub_tour.rotate_left(depot_i);
+    let resolved: Vec<CityNum> = sub_tour.iter().map(|i| route[*i]).collect();
+    if compute_dist(weights, &resolved) < compute_dist(weights, route) - fp_epsilon {
+      *route = resolved;
+    }
+  }
+
+  Some(routes)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn routes_respect_capacity_and_visit_every_customer() {
+    let mut rng = fastrand::Rng::with_seed(42);
+    let node_coordinates: Vec<(CityNum, CityXYCoord, CityXYCoord)> = (0..30).map(|i| (i, 3.0 + rng.f32() * 9.0, 3.0 + rng.f32() * 9.0) ).collect();
+    let weights = compute_weight_coords(&node_coordinates);
+    let demands: Vec<usize> = (0..30).map(|_| rng.usize(1..10)).collect();
+    let problem = VrpProblem { node_coordinates, weights, depot: 0, demands, capacity: Some(30), num_vehicles: None };
+
+    let routes = solve(&problem, &SolveOptions::default()).expect("an open fleet always fits");
+    let mut visited: Vec<CityNum> = routes.iter().flat_map(|route| route[1..].to_vec()).collect();
+    visited.sort();
+    assert_eq!(visited, (1..30).collect::<Vec<CityNum>>());
+    for route in routes.iter() {
+      assert_eq!(route[0], 0);
+      assert!(problem.route_load(route) <= 30, "{:?} carries {}", route, problem.route_load(route));
+    }
+
+    // 2 vehicles of 30 cannot carry all of it
+    let fixed_fleet = VrpProblem { num_vehicles: Some(2), ..problem };
+    assert!(fixed_fleet.demands.iter().skip(1).sum::<usize>() > 60);
+    assert!(solve(&fixed_fleet, &SolveOptions::default()).is_none());
+  }
+}